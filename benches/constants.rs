@@ -0,0 +1,42 @@
+//! Times running a loop that loads many distinct constants,
+//! enough that both `Con` and `ConLong` are used.
+//! Run with `cargo bench --bench constants`.
+
+use std::time::{Duration, Instant};
+
+use passerine::{common::source::Source, compile, run};
+
+/// The number of distinct real constants summed in the loop.
+const CONSTANTS: usize = 300;
+/// The number of times the loop body is run.
+const ITERATIONS: usize = 20_000;
+/// The number of times the program is run; the fastest is reported.
+const RUNS: usize = 5;
+
+fn program() -> String {
+    let terms = (0..CONSTANTS)
+        .map(|n| format!("{}.5", n))
+        .collect::<Vec<_>>()
+        .join(" + ");
+
+    format!(
+        "total = 0.0\nfor i in 0..{} {{\n    total = total + {}\n}}\ntotal\n",
+        ITERATIONS, terms,
+    )
+}
+
+fn main() {
+    let closure = compile(Source::source(&program())).unwrap();
+    let bytes = closure.lambda.code.len();
+
+    let fastest = (0..RUNS).map(|_| {
+        let start = Instant::now();
+        run(closure.clone()).unwrap();
+        start.elapsed()
+    }).min().unwrap_or(Duration::ZERO);
+
+    println!(
+        "summed {} constants {} times in {:?} (fastest of {}), {} bytes of bytecode",
+        CONSTANTS, ITERATIONS, fastest, RUNS, bytes,
+    );
+}
@@ -0,0 +1,42 @@
+//! Times compiling a program that repeatedly reads and rebinds one local,
+//! in a scope with many other locals, which exercises the hoister's lookup cache.
+//! Run with `cargo bench --bench hoist`.
+
+use std::time::{Duration, Instant};
+
+use passerine::{common::source::Source, compile};
+
+/// The number of unrelated locals declared before the hot one.
+const LOCALS: usize = 1000;
+/// The number of lines that read and rebind the hot local.
+const LINES: usize = 3000;
+/// The number of times the program is compiled; the fastest is reported.
+const RUNS: usize = 5;
+
+fn program() -> String {
+    let mut source = String::new();
+    for n in 0..LOCALS {
+        source.push_str(&format!("v{} = {}\n", n, n));
+    }
+    source.push_str("x = 0\n");
+    for _ in 0..LINES {
+        source.push_str("x = x + 1\n");
+    }
+    source.push_str("x\n");
+    source
+}
+
+fn main() {
+    let source = Source::source(&program());
+
+    let fastest = (0..RUNS).map(|_| {
+        let start = Instant::now();
+        compile(source.clone()).unwrap();
+        start.elapsed()
+    }).min().unwrap_or(Duration::ZERO);
+
+    println!(
+        "compiled {} rebindings among {} locals in {:?} (fastest of {})",
+        LINES, LOCALS, fastest, RUNS,
+    );
+}
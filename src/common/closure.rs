@@ -1,5 +1,5 @@
 use std::{
-    rc::Rc,
+    rc::{Rc, Weak},
     cell::RefCell,
 };
 
@@ -9,17 +9,69 @@ use crate::common::{
     data::Data,
 };
 
+/// A reference to a captured variable.
+/// Captures are strong by default, keeping the variable alive.
+/// A weak capture does not, which can be used to break reference cycles,
+/// for instance, in a closure that refers to itself.
+#[derive(Debug, Clone)]
+pub enum Capture {
+    Strong(Rc<RefCell<Data>>),
+    Weak(Weak<RefCell<Data>>),
+}
+
+impl Capture {
+    /// Returns a strong reference to the captured variable,
+    /// or `None` if a weak capture's variable has been freed.
+    pub fn upgrade(&self) -> Option<Rc<RefCell<Data>>> {
+        match self {
+            Capture::Strong(cell) => Some(cell.clone()),
+            Capture::Weak(weak)   => weak.upgrade(),
+        }
+    }
+
+    /// Returns a weak version of this capture.
+    pub fn downgrade(&self) -> Capture {
+        match self {
+            Capture::Strong(cell) => Capture::Weak(Rc::downgrade(cell)),
+            Capture::Weak(weak)   => Capture::Weak(weak.clone()),
+        }
+    }
+
+    /// Returns whether this capture is weak.
+    pub fn is_weak(&self) -> bool {
+        matches!(self, Capture::Weak(_))
+    }
+
+    /// Returns a pointer to the captured variable,
+    /// which stays the same even after a weak capture's variable has been freed.
+    fn as_ptr(&self) -> *const RefCell<Data> {
+        match self {
+            Capture::Strong(cell) => Rc::as_ptr(cell),
+            Capture::Weak(weak)   => weak.as_ptr(),
+        }
+    }
+}
+
+impl PartialEq for Capture {
+    /// Captures are equal if they refer to the same variable,
+    /// regardless of whether either capture is weak,
+    /// or whether a weak capture's variable has been freed.
+    fn eq(&self, other: &Capture) -> bool {
+        std::ptr::eq(self.as_ptr(), other.as_ptr())
+    }
+}
+
 // TODO: take a reference to lambda?
 
 /// Wraps a `Lambda` with some scope context.
 /// Each closure is unique when constructed,
 /// Because it depends on the surrounding environment it was constructed in.
 /// It holds a set of references to variables it captures.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Closure {
     pub id: String,
     pub lambda: Rc<Lambda>,
-    pub captures: Vec<Rc<RefCell<Data>>>,
+    pub captures: Vec<Capture>,
 }
 
 impl Closure {
@@ -32,13 +84,47 @@ impl Closure {
             captures: vec![],
         }
     }
+
+    /// Makes the capture at the given index weak,
+    /// so that it no longer keeps the captured variable alive.
+    /// Once the variable is freed, loading it is a runtime error.
+    pub fn weaken(&mut self, index: usize) {
+        self.captures[index] = self.captures[index].downgrade();
+    }
+}
+
+impl PartialEq for Closure {
+    /// Closures are compared by identity rather than structurally:
+    /// two closures are only equal if they wrap the same `Lambda`
+    /// and capture the very same variables,
+    /// i.e. if one is a copy of the other.
+    /// This avoids deeply comparing, or looping forever on, captured data.
+    fn eq(&self, other: &Closure) -> bool {
+        Rc::ptr_eq(&self.lambda, &other.lambda)
+        && self.captures == other.captures
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use super::*;
     // use crate::common::lambda::Lambda;
 
+    #[test]
+    fn freed_capture_equal() {
+        let cell = Rc::new(RefCell::new(Data::Integer(1)));
+        let strong = Capture::Strong(cell.clone());
+        let weak = strong.downgrade();
+        assert_eq!(weak, strong);
+
+        // a closure whose weak capture was freed is still equal to itself
+        drop(strong);
+        drop(cell);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(weak, weak.clone());
+        assert_ne!(weak, Capture::Strong(Rc::new(RefCell::new(Data::Integer(1)))));
+    }
+
     // #[test]
     // fn unique() {
     //     let lambda = Lambda::empty();
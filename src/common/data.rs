@@ -11,8 +11,14 @@ use std::{
 };
 
 use crate::common::{
+    span::Span,
     lambda::Lambda,
     closure::Closure,
+    map::Map,
+};
+use crate::core::{
+    ffi::Partial,
+    math::round_places,
 };
 
 /// Built-in Passerine datatypes.
@@ -33,10 +39,16 @@ pub enum Data {
     Boolean(bool),
     /// A UTF-8 encoded string.
     String(String),
+    /// A single character of a string, i.e. a unicode scalar value.
+    Char(char),
+    /// Raw binary data.
+    Bytes(Vec<u8>),
     /// Represents a function, ie.e some bytecode without a context.
     Lambda(Rc<Lambda>),
     /// Some bytecode with a context that can be run.
     Closure(Box<Closure>),
+    /// A native function, which may be partially applied.
+    Partial(Box<Partial>),
 
     // TODO: rework how labels and tags work
     /// `Kind` is the base component of an unconstructed label
@@ -50,9 +62,26 @@ pub enum Data {
     // Compound Datatypes
     /// The empty Tuple
     Unit, // an empty typle
+    /// The absence of a value, distinct from the empty Tuple.
+    Nil,
+    /// An error caught by `try`, along with where it was raised.
+    /// It can be matched against the pattern `Error message`,
+    /// and raised again with `error`.
+    Error {
+        message: String,
+        span:    Span,
+    },
     /// A non-empty Tuple.
     Tuple(Vec<Data>),
-    // // TODO: Hashmap?
+    /// A list of items, which may be of different types.
+    List(Vec<Data>),
+    /// A half-open range of integers, `start..end`.
+    /// The start is inclusive, the end is exclusive,
+    /// so `0..3` covers `0`, `1`, and `2`.
+    Range(i64, i64),
+    /// A map from keys to values, which preserves insertion order.
+    /// See `Key` for which data can be used as a key.
+    Map(Map),
     // // I mean, it's overkill for small things
     // // yet if people have very big records, yk.
     // Record(Vec<(Local, Data)>),
@@ -67,20 +96,82 @@ impl Eq for Data {}
 impl Display for Data {
     /// Displays some Passerine Data in a pretty manner, as if it were printed to console.
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.with_precision(None))
+    }
+}
+
+impl Data {
+    /// Displays data like `Display` does,
+    /// but with reals rounded to a number of digits after the decimal point,
+    /// including reals nested inside of other data.
+    /// Halfway cases are rounded away from zero, like `magic "round"` does.
+    /// If `precision` is `None`, reals are shown in full,
+    /// i.e. the shortest representation that reads back as the same number.
+    pub fn with_precision(&self, precision: Option<usize>) -> WithPrecision<'_> {
+        WithPrecision { data: self, precision }
+    }
+
+    /// Returns the number of arguments a function takes, see `Lambda::arity`,
+    /// or `None` if the data is not a function.
+    pub fn arity(&self) -> Option<usize> {
         match self {
+            Data::Lambda(lambda)   => Some(lambda.arity),
+            Data::Closure(closure) => Some(closure.lambda.arity),
+            Data::Partial(partial) => Some(partial.remaining()),
+            _ => None,
+        }
+    }
+}
+
+/// Some data displayed with reals rounded to a given precision,
+/// see `Data::with_precision`.
+pub struct WithPrecision<'a> {
+    data:      &'a Data,
+    precision: Option<usize>,
+}
+
+impl Display for WithPrecision<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let precision = self.precision;
+        let nested = |d: &Data| format!("{}", d.with_precision(precision));
+
+        match self.data {
             Data::Heaped(_)   => unreachable!("Can not display heaped data"),
             Data::NotInit     => unreachable!("found uninitialized data on top of stack"),
-            Data::Real(n)     => write!(f, "{}", n),
+            Data::Real(n)     => match precision {
+                Some(p) => write!(f, "{:.*}", p, round_places(*n, p as u64)),
+                None    => write!(f, "{}", n),
+            },
             Data::Integer(n)  => write!(f, "{}", n),
             Data::Boolean(b)  => write!(f, "{}", if *b { "true" } else { "false" }),
             Data::String(s)   => write!(f, "{}", s),
+            Data::Char(c)     => write!(f, "{}", c),
+            Data::Bytes(b)    => write!(f, "b\"{}\"", b.iter()
+                .map(|b| std::ascii::escape_default(*b).to_string())
+                .collect::<String>()
+            ),
             Data::Lambda(_)   => unreachable!("Can not display naked functions"),
-            Data::Closure(c)  => write!(f, "Function ~ {}", c.id),
+            // functions are compared by identity, so only their arity is shown
+            Data::Closure(c)  => write!(f, "<fn/{}>", c.lambda.arity),
+            Data::Partial(p)  => write!(f, "<fn/{}>", p.remaining()),
             Data::Kind(_)     => unreachable!("Can not display naked labels"),
-            Data::Label(n, v) => write!(f, "{} {}", n, v),
+            Data::Label(n, v) => write!(f, "{} {}", n, nested(v)),
             Data::Unit        => write!(f, "()"),
+            Data::Nil         => write!(f, "nil"),
+            Data::Error { message, .. } => write!(f, "Error {}", message),
             Data::Tuple(t)    => write!(f, "({})", t.iter()
-                .map(|i| format!("{}", i))
+                .map(nested)
+                .collect::<Vec<String>>()
+                .join(", ")
+            ),
+            Data::List(l)     => write!(f, "[{}]", l.iter()
+                .map(nested)
+                .collect::<Vec<String>>()
+                .join(", ")
+            ),
+            Data::Range(s, e) => write!(f, "{}..{}", s, e),
+            Data::Map(m)      => write!(f, "{{{}}}", m.iter()
+                .map(|(k, v)| format!("{}: {}", k, nested(v)))
                 .collect::<Vec<String>>()
                 .join(", ")
             ),
@@ -93,6 +184,8 @@ impl Display for Data {
 impl Debug for Data {
     /// Displays some Passerine Data following Rust conventions,
     /// with certain fields omitted.
+    /// Strings and characters are quoted and escaped,
+    /// whereas `Display` shows their raw contents.
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
             Data::Heaped(h)   => write!(f, "Heaped({:?})", h.borrow()),
@@ -101,12 +194,179 @@ impl Debug for Data {
             Data::Integer(n)  => write!(f, "Integer({:?})", n),
             Data::Boolean(b)  => write!(f, "Boolean({:?})", b),
             Data::String(s)   => write!(f, "String({:?})", s),
+            Data::Char(c)     => write!(f, "Char({:?})", c),
+            Data::Bytes(b)    => write!(f, "Bytes({:?})", b),
             Data::Lambda(_)   => write!(f, "Function(...)"),
             Data::Closure(c)  => write!(f, "Closure({})", c.id),
+            Data::Partial(p)  => write!(f, "Partial({:?})", p.args),
             Data::Kind(n)     => write!(f, "Kind({})", n),
             Data::Label(n, v) => write!(f, "Label({}, {:?})", n, v),
             Data::Unit        => write!(f, "Unit"),
+            Data::Nil         => write!(f, "Nil"),
+            Data::Error { message, .. } => write!(f, "Error({:?})", message),
             Data::Tuple(t)    => write!(f, "Tuple({:?})", t),
+            Data::List(l)     => write!(f, "List({:?})", l),
+            Data::Range(s, e) => write!(f, "Range({:?}, {:?})", s, e),
+            Data::Map(m)      => write!(f, "Map({:?})", m),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{key::Key, source::Source};
+    use crate::core::ffi::FFIFunction;
+
+    /// Names the variant of some data.
+    /// There is deliberately no catch-all arm,
+    /// so adding a variant to `Data` fails to compile until it is listed here,
+    /// and thus until it is covered by `every_variant`.
+    fn variant(data: &Data) -> &'static str {
+        match data {
+            Data::Heaped(_)    => "Heaped",
+            Data::NotInit      => "NotInit",
+            Data::Real(_)      => "Real",
+            Data::Integer(_)   => "Integer",
+            Data::Boolean(_)   => "Boolean",
+            Data::String(_)    => "String",
+            Data::Char(_)      => "Char",
+            Data::Bytes(_)     => "Bytes",
+            Data::Lambda(_)    => "Lambda",
+            Data::Closure(_)   => "Closure",
+            Data::Partial(_)   => "Partial",
+            Data::Kind(_)      => "Kind",
+            Data::Label(_, _)  => "Label",
+            Data::Unit         => "Unit",
+            Data::Nil          => "Nil",
+            Data::Error { .. } => "Error",
+            Data::Tuple(_)     => "Tuple",
+            Data::List(_)      => "List",
+            Data::Range(_, _)  => "Range",
+            Data::Map(_)       => "Map",
+        }
+    }
+
+    /// One value of every variant of `Data`.
+    fn every_variant() -> Vec<Data> {
+        let mut map = Map::new();
+        map.insert(Key::String("a".to_string()), Data::Integer(1));
+        let source = Source::source("oops");
+
+        vec![
+            Data::Heaped(Rc::new(RefCell::new(Data::Integer(1)))),
+            Data::NotInit,
+            Data::Real(1.5),
+            Data::Integer(-7),
+            Data::Boolean(true),
+            Data::String("hello".to_string()),
+            Data::Char('x'),
+            Data::Bytes(vec![1, 2, 3]),
+            Data::Lambda(Rc::new(Lambda::empty())),
+            Data::Closure(Box::new(Closure::wrap(Rc::new(Lambda::empty())))),
+            Data::Partial(Box::new(Partial::new(FFIFunction::new(Box::new(Ok)).with_arity(2)))),
+            Data::Kind("Some".to_string()),
+            Data::Label(Box::new("Some".to_string()), Box::new(Data::Integer(1))),
+            Data::Unit,
+            Data::Nil,
+            Data::Error { message: "oops".to_string(), span: Span::new(&source, 0, 4) },
+            Data::Tuple(vec![Data::Integer(1), Data::String("two".to_string())]),
+            Data::List(vec![Data::List(vec![Data::Integer(1)])]),
+            Data::Range(0, 3),
+            Data::Map(map),
+        ]
+    }
+
+    #[test]
+    fn variants_covered() {
+        let variants = every_variant();
+        let names = variants.iter().map(variant).collect::<std::collections::HashSet<_>>();
+        assert_eq!(names.len(), variants.len(), "each variant should only be listed once");
+        assert_eq!(names.len(), 20);
+    }
+
+    #[test]
+    fn clone_equal() {
+        for data in every_variant() {
+            assert_eq!(data.clone(), data, "a clone of {} should be equal", variant(&data));
+        }
+
+        // reals follow IEEE equality, so NaN is the one value not equal to its clone
+        let nan = Data::Real(f64::NAN);
+        assert_ne!(nan.clone(), nan);
+    }
+
+    #[test]
+    fn clone_distinct() {
+        // values of different variants are never equal
+        let variants = every_variant();
+        for (i, a) in variants.iter().enumerate() {
+            for b in variants.iter().skip(i + 1) {
+                assert_ne!(a, b, "{} should not equal {}", variant(a), variant(b));
+            }
+        }
+    }
+
+    #[test]
+    fn clone_independent() {
+        for data in every_variant() {
+            let original = data.clone();
+            let mut copy = data.clone();
+
+            match &mut copy {
+                Data::Real(n)          => *n += 1.0,
+                Data::Integer(n)       => *n += 1,
+                Data::Boolean(b)       => *b = !*b,
+                Data::String(s)        => s.push('!'),
+                Data::Char(c)          => *c = 'y',
+                Data::Bytes(b)         => b.push(4),
+                Data::Kind(k)          => k.push('!'),
+                Data::Label(_, inner)  => **inner = Data::Integer(2),
+                Data::Error { message, .. } => message.push('!'),
+                Data::Tuple(items)     => items.push(Data::Unit),
+                Data::List(items)      => match &mut items[0] {
+                    Data::List(nested) => nested.push(Data::Integer(2)),
+                    _ => unreachable!(),
+                },
+                Data::Range(_, end)    => *end += 1,
+                Data::Partial(p)       => p.args.push(Data::Unit),
+                Data::Map(m)           => { m.insert(Key::Integer(2), Data::Unit); },
+                // heaped data is shared on purpose, see `heaped_shared`,
+                // and the rest hold nothing that can be mutated in place
+                  Data::Heaped(_)
+                | Data::NotInit
+                | Data::Lambda(_)
+                | Data::Closure(_)
+                | Data::Unit
+                | Data::Nil => continue,
+            }
+
+            assert_eq!(data, original, "mutating a clone of {} should not affect the original", variant(&data));
+            assert_ne!(copy, original, "the clone of {} should have been mutated", variant(&data));
+        }
+    }
+
+    #[test]
+    fn heaped_shared() {
+        // heaped data is how variables are shared between closures,
+        // so a clone refers to the very same cell
+        let cell = Rc::new(RefCell::new(Data::Integer(1)));
+        let heaped = Data::Heaped(cell.clone());
+        let copy = heaped.clone();
+        cell.replace(Data::Integer(2));
+        assert_eq!(copy, Data::Heaped(Rc::new(RefCell::new(Data::Integer(2)))));
+        assert_eq!(heaped, copy);
+    }
+
+    #[test]
+    fn string_display_debug() {
+        let string = Data::String("a\nb \"c\"".to_string());
+        assert_eq!(format!("{}", string), "a\nb \"c\"");
+        assert_eq!(format!("{:?}", string), "String(\"a\\nb \\\"c\\\"\")");
+
+        // strings nested in other data keep their representation
+        let tuple = Data::Tuple(vec![string, Data::Char('\n')]);
+        assert_eq!(format!("{}", tuple), "(a\nb \"c\", \n)");
+        assert_eq!(format!("{:?}", tuple), "Tuple([String(\"a\\nb \\\"c\\\"\"), Char('\\n')])");
+    }
+}
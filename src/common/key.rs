@@ -0,0 +1,76 @@
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter, Result},
+};
+
+use crate::common::data::Data;
+
+/// The subset of `Data` that can be used as the key of a map.
+/// `Data` itself can not be hashed: two reals may be unequal to themselves (`NaN`),
+/// functions are compared by identity, and compound data may be on the heap.
+/// So keys are restricted to integers, strings, and booleans,
+/// which have a well-defined notion of equality and hashing.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Integer(i64),
+    String(String),
+    Boolean(bool),
+}
+
+impl TryFrom<Data> for Key {
+    type Error = String;
+
+    /// Converts some `Data` into a `Key`,
+    /// returning an error if the data can not be used as a key.
+    fn try_from(data: Data) -> std::result::Result<Self, Self::Error> {
+        match data {
+            Data::Integer(i) => Ok(Key::Integer(i)),
+            Data::String(s)  => Ok(Key::String(s)),
+            Data::Boolean(b) => Ok(Key::Boolean(b)),
+            other => Err(format!(
+                "The data '{}' can not be used as a key, only integers, strings, and booleans can",
+                other,
+            )),
+        }
+    }
+}
+
+impl From<Key> for Data {
+    fn from(key: Key) -> Data {
+        match key {
+            Key::Integer(i) => Data::Integer(i),
+            Key::String(s)  => Data::String(s),
+            Key::Boolean(b) => Data::Boolean(b),
+        }
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Key::Integer(i) => write!(f, "{}", i),
+            Key::String(s)  => write!(f, "{:?}", s),
+            Key::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for data in [Data::Integer(1), Data::String("a".to_string()), Data::Boolean(true)] {
+            let key = Key::try_from(data.clone()).unwrap();
+            assert_eq!(Data::from(key), data);
+        }
+    }
+
+    #[test]
+    fn rejected() {
+        assert!(Key::try_from(Data::Real(1.5)).is_err());
+        assert!(Key::try_from(Data::Real(f64::NAN)).is_err());
+        assert!(Key::try_from(Data::List(vec![])).is_err());
+    }
+}
@@ -1,9 +1,12 @@
-use std::fmt;
+use std::{
+    fmt,
+    rc::Rc,
+};
 
 use crate::common::{
     opcode::Opcode,
     data::Data,
-    number::build_number,
+    number::{build_number, split_number, split_number_padded},
     span::Span,
 };
 
@@ -18,6 +21,32 @@ pub enum Captured {
     Nonlocal(usize),
 }
 
+/// A variable local to a chunk of bytecode,
+/// i.e. a value stored on the stack in the chunk's frame.
+/// Kept around so tools like debuggers can show variables by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Local {
+    name: String,
+    span: Span,
+}
+
+impl Local {
+    /// Creates a new local, declared by name at a specific span.
+    pub fn new(name: &str, span: &Span) -> Local {
+        Local { name: name.to_string(), span: span.clone() }
+    }
+
+    /// The name of the variable in the source.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The span where the variable was first declared.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
 /// Represents a single interpretable chunk of bytecode,
 /// think a function.
 #[derive(Debug, Clone, PartialEq)]
@@ -26,12 +55,20 @@ pub struct Lambda {
     // So structs can be made, and state preserved in the repl.
     /// Number of variables declared in this scope.
     pub decls: usize,
+    /// The number of arguments this function takes, one after the other,
+    /// i.e. `2` for `a b -> a + b`; `0` for top-level chunks.
+    pub arity: usize,
+    /// The variables local to this chunk, in the order they are stored on the stack.
+    pub locals: Vec<Local>,
     /// Each byte is an opcode or a number-stream.
     pub code: Vec<u8>,
     /// Each usize indexes the bytecode op that begins each line.
     pub spans: Vec<(usize, Span)>,
     /// Number-stream indexed, used to load constants.
     pub constants: Vec<Data>,
+    /// The span of the source literal each constant was loaded from, if any.
+    /// Indexed in the same way as `constants`.
+    pub constant_spans: Vec<Option<Span>>,
     /// List of positions of locals in the scope where this lambda is defined,
     /// indexes must be gauranteed to be data on the heap.
     pub captures: Vec<Captured>,
@@ -41,13 +78,20 @@ pub struct Lambda {
 }
 
 impl Lambda {
+    /// The number of bytes reserved for a placeholder,
+    /// enough to address 2^28 bytes of bytecode.
+    pub const PLACEHOLDER_WIDTH: usize = 4;
+
     /// Creates a new empty `Lambda` to be filled.
     pub fn empty() -> Lambda {
         Lambda {
             decls:     0,
+            arity:     0,
+            locals:    vec![],
             code:      vec![],
             spans:     vec![],
             constants: vec![],
+            constant_spans: vec![],
             captures:  vec![],
             ffi:       vec![],
         }
@@ -55,7 +99,7 @@ impl Lambda {
 
     /// Emits an opcode as a byte.
     pub fn emit(&mut self, op: Opcode) {
-        self.code.push(op as u8)
+        self.code.push(op.to_byte())
     }
 
     /// Emits a series of bytes.
@@ -63,6 +107,19 @@ impl Lambda {
         self.code.append(bytes)
     }
 
+    /// Emits an op that loads the constant at an index in the constants table.
+    /// Most constant pools are small,
+    /// so a single byte is used for the index if possible.
+    pub fn emit_constant(&mut self, index: usize) {
+        if index <= u8::MAX as usize {
+            self.emit(Opcode::Con);
+            self.emit_bytes(&mut vec![index as u8]);
+        } else {
+            self.emit(Opcode::ConLong);
+            self.emit_bytes(&mut split_number(index));
+        }
+    }
+
     /// Emits a span, should be called before an opcode is emmited.
     /// This function ties opcodes to spans in source.
     /// See index_span as well.
@@ -70,6 +127,31 @@ impl Lambda {
         self.spans.push((self.code.len(), span.clone()))
     }
 
+    /// Emits a fixed-width placeholder for a number that is not yet known,
+    /// such as the target of a forward jump.
+    /// Returns the index of the placeholder, to be passed to `patch`.
+    pub fn emit_placeholder(&mut self) -> usize {
+        let index = self.code.len();
+        self.emit_bytes(&mut split_number_padded(0, Lambda::PLACEHOLDER_WIDTH));
+        index
+    }
+
+    /// Overwrites a placeholder emmited by `emit_placeholder` with a number.
+    pub fn patch(&mut self, placeholder: usize, number: usize) {
+        let bytes = split_number_padded(number, Lambda::PLACEHOLDER_WIDTH);
+        self.code[placeholder..(placeholder + bytes.len())].copy_from_slice(&bytes);
+    }
+
+    /// Emits `Nop`s until the length of the bytecode is a multiple of `alignment`,
+    /// so that the next opcode emitted starts at an aligned index.
+    /// An alignment of 0 or 1 leaves the bytecode as is.
+    pub fn align(&mut self, alignment: usize) {
+        if alignment == 0 { return; }
+        while self.code.len() % alignment != 0 {
+            self.emit(Opcode::Nop);
+        }
+    }
+
     /// Removes the last emitted byte.
     pub fn demit(&mut self) {
         self.code.pop();
@@ -85,11 +167,36 @@ impl Lambda {
             Some(d) => d,
             None => {
                 self.constants.push(data);
+                self.constant_spans.push(None);
                 self.constants.len() - 1
             },
         }
     }
 
+    /// Like `index_data`, but records the span of the literal the constant came from.
+    /// If the constant is already in the table, the span it was first recorded with is kept.
+    pub fn index_spanned_data(&mut self, data: Data, span: &Span) -> usize {
+        let index = self.index_data(data);
+        if self.constant_spans[index].is_none() && !span.is_empty() {
+            self.constant_spans[index] = Some(span.clone());
+        }
+        index
+    }
+
+    /// Returns the span of the source literal a constant was loaded from, if known.
+    pub fn constant_span(&self, index: usize) -> Option<&Span> {
+        self.constant_spans.get(index).and_then(|span| span.as_ref())
+    }
+
+    /// Formats a constant for disassembly,
+    /// along with the source text of the literal it was loaded from, if known.
+    fn describe_constant(&self, index: usize) -> String {
+        match self.constant_span(index) {
+            Some(span) => format!("{}\t{:?}\t{}", index, self.constants[index], span.contents()),
+            None       => format!("{}\t{:?}", index, self.constants[index]),
+        }
+    }
+
     /// Look up the nearest span at or before the index of a specific bytecode op.
     pub fn index_span(&self, index: usize) -> Span {
         let mut best = &Span::empty();
@@ -102,6 +209,140 @@ impl Lambda {
         best.clone()
     }
 
+    /// Links two top-level chunks of bytecode into one,
+    /// which runs this chunk and then the other,
+    /// evaluating to the value of the other, like a block would.
+    /// This chunk is copied as-is, whereas the other chunk is relocated:
+    /// its constants are added to this chunk's constants table,
+    /// and its locals, captures, and FFI functions come after this chunk's.
+    /// Because numbers in bytecode are variable-length,
+    /// relocated ops may change size,
+    /// so jump targets are recalculated after the ops have been moved.
+    /// Returns an error if the other chunk contains a byte that is not a valid opcode.
+    pub fn merge(&self, other: &Lambda) -> Result<Lambda, String> {
+        let mut merged = self.clone();
+        merged.decls += other.decls;
+        merged.locals.extend(other.locals.iter().cloned());
+        merged.captures.extend(other.captures.iter().cloned());
+        merged.ffi.extend(other.ffi.iter().cloned());
+
+        // discard the value of this chunk
+        if !self.code.is_empty() { merged.emit(Opcode::Del); }
+
+        // where each op of the other chunk ended up,
+        // and jump targets to fill in once all ops have been moved
+        let mut positions = vec![None; other.code.len() + 1];
+        let mut targets = vec![];
+        let mut index = 0;
+
+        while index < other.code.len() {
+            positions[index] = Some(merged.code.len());
+            let opcode = Opcode::from_byte(other.code[index])
+                .ok_or_else(|| format!("The byte {} at index {} is not a valid opcode", other.code[index], index))?;
+            index += 1;
+
+            // fixed-width operands are handled before everything else
+            if opcode == Opcode::Con {
+                let constant = self.relocate_constant(&mut merged, other, other.code[index] as usize);
+                merged.emit_constant(constant);
+                index += 1;
+                continue;
+            }
+
+            if !opcode.has_operand() {
+                merged.emit(opcode);
+                continue;
+            }
+
+            let (operand, consumed) = build_number(&other.code[index..]);
+            index += consumed;
+
+            if opcode == Opcode::DelLocals {
+                let (count, consumed) = build_number(&other.code[index..]);
+                index += consumed;
+                merged.emit(opcode);
+                merged.emit_bytes(&mut split_number(operand + self.decls));
+                merged.emit_bytes(&mut split_number(count));
+                continue;
+            }
+
+            let operand = match opcode {
+                Opcode::ConLong => {
+                    let constant = self.relocate_constant(&mut merged, other, operand);
+                    merged.emit_constant(constant);
+                    continue;
+                },
+                Opcode::Closure => {
+                    let constant = self.relocate_data(other.constants[operand].clone());
+                    merged.index_data(constant)
+                },
+                  Opcode::GetGlobal
+                | Opcode::SetGlobal => self.relocate_constant(&mut merged, other, operand),
+                  Opcode::Capture
+                | Opcode::Declare
+                | Opcode::Mutate
+                | Opcode::Load    => operand + self.decls,
+                  Opcode::SaveCap
+                | Opcode::LoadCap => operand + self.captures.len(),
+                  Opcode::FFICall
+                | Opcode::FFICallKeywords => operand + self.ffi.len(),
+                _ if opcode.is_jump() => {
+                    merged.emit(opcode);
+                    targets.push((merged.emit_placeholder(), operand));
+                    continue;
+                },
+                _ => operand,
+            };
+
+            merged.emit(opcode);
+            merged.emit_bytes(&mut split_number(operand));
+        }
+        positions[other.code.len()] = Some(merged.code.len());
+
+        let position = |old: usize| positions.get(old).copied().flatten()
+            .ok_or_else(|| format!("The index {} is not the start of an op", old));
+
+        for (placeholder, target) in targets {
+            merged.patch(placeholder, position(target)?);
+        }
+
+        for (old, span) in other.spans.iter() {
+            merged.spans.push((position(*old)?, span.clone()));
+        }
+
+        Ok(merged)
+    }
+
+    /// Adds a constant loaded by the other chunk to the merged chunk,
+    /// keeping the span of the literal it came from.
+    /// Returns the index of the constant in the merged chunk.
+    fn relocate_constant(&self, merged: &mut Lambda, other: &Lambda, index: usize) -> usize {
+        let constant = self.relocate_data(other.constants[index].clone());
+        match other.constant_span(index) {
+            Some(span) => merged.index_spanned_data(constant, span),
+            None       => merged.index_data(constant),
+        }
+    }
+
+    /// Moves a constant of a chunk being merged into this chunk, see `Lambda::merge`.
+    /// Functions capture the locals and captures of the chunk they are defined in,
+    /// which come after this chunk's once merged.
+    fn relocate_data(&self, data: Data) -> Data {
+        match data {
+            Data::Lambda(lambda) => {
+                let mut lambda = (*lambda).clone();
+                for captured in lambda.captures.iter_mut() {
+                    match captured {
+                        Captured::Local(index)    => *index += self.decls,
+                        Captured::Nonlocal(index) => *index += self.captures.len(),
+                    }
+                }
+                Data::Lambda(Rc::new(lambda))
+            },
+            other => other,
+        }
+    }
+
     /// Adds a ffi function to the ffi table,
     /// without checking for duplicates.
     /// The `Compiler` ensures that functions are valid
@@ -139,73 +380,215 @@ impl fmt::Display for Lambda {
 
         while index < self.code.len() {
             index += 1;
-            match Opcode::from_byte(self.code[index - 1]) {
+            let opcode = match Opcode::from_byte(self.code[index - 1]) {
+                Some(opcode) => opcode,
+                None => {
+                    writeln!(f, "Unknown \t{}\tNot an opcode", self.code[index - 1])?;
+                    continue;
+                },
+            };
+            write!(f, "{:<8}\t", opcode.name())?;
+
+            match opcode {
                 Opcode::Con => {
+                    let constant_index = self.code[index] as usize;
+                    index += 1;
+                    writeln!(f, "{}", self.describe_constant(constant_index))?;
+                },
+                Opcode::ConLong => {
                     let (constant_index, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "Load Con\t{}\t{:?}", constant_index, self.constants[constant_index])?;
+                    writeln!(f, "{}", self.describe_constant(constant_index))?;
                 },
-                Opcode::NotInit => { writeln!(f, "NotInit \t\tDeclare variable")?; }
-                Opcode::Del     => { writeln!(f, "Delete  \t\t--")?; },
+                Opcode::NotInit => { writeln!(f, "\tDeclare variable")?; }
                 Opcode::Capture => {
                     let (local_index, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "Capture \t{}\tIndexed local moved to heap", local_index)?;
+                    writeln!(f, "{}\tIndexed local moved to heap", local_index)?;
                 },
-                Opcode::Save => {
+                Opcode::Declare | Opcode::Mutate => {
                     let (local_index, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "Save    \t{}\tIndexed local", local_index)?;
+                    writeln!(f, "{}\tIndexed local", local_index)?;
                 },
                 Opcode::SaveCap => {
                     let (upvalue_index, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "Save Cap\t{}\tIndexed upvalue on heap", upvalue_index)?;
+                    writeln!(f, "{}\tIndexed upvalue on heap", upvalue_index)?;
                 },
                 Opcode::Load => {
                     let (local_index, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "Load    \t{}\tIndexed local", local_index)?;
+                    writeln!(f, "{}\tIndexed local", local_index)?;
                 },
                 Opcode::LoadCap => {
                     let (upvalue_index, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "Load Cap\t{}\tIndexed upvalue on heap", upvalue_index)?;
+                    writeln!(f, "{}\tIndexed upvalue on heap", upvalue_index)?;
                 },
-                Opcode::Call => { writeln!(f, "Call    \t\tRun top function using next stack value")?; }
+                Opcode::Call => { writeln!(f, "\tRun top function using next stack value")?; }
                 Opcode::Return => {
                     let (num_locals, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "Return  \t{}\tLocals on stack deleted", num_locals)?;
+                    writeln!(f, "{}\tLocals on stack deleted", num_locals)?;
                 },
                 Opcode::Closure => {
                     let (todo_index, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "Closure \t{}\tIndex of lambda to be wrapped", todo_index)?;
+                    writeln!(f, "{}\tIndex of lambda to be wrapped", todo_index)?;
                 },
-                Opcode::Print   => { writeln!(f, "Print    \t\t--")?; },
-                Opcode::Label   => { writeln!(f, "Label    \t\t--")?; },
                 Opcode::Tuple => {
                     let (length, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "Tuple   \t{}\tValues tupled together", length)?;
+                    writeln!(f, "{}\tValues tupled together", length)?;
                 },
-                Opcode::UnLabel => { writeln!(f, "UnLabel  \t\t--")?; },
-                Opcode::UnData  => { writeln!(f, "UnData   \t\t--")?; },
                 Opcode::UnTuple => {
                     let (item_index, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "UnTuple \t{}\tItem accessed", item_index)?;
+                    writeln!(f, "{}\tItem accessed", item_index)?;
+                },
+                Opcode::Try => {
+                    let (target, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    writeln!(f, "{}\tIndex of op on error", target)?;
+                },
+                Opcode::List => {
+                    let (length, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    writeln!(f, "{}\tValues collected into list", length)?;
+                },
+                Opcode::Jump => {
+                    let (target, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    writeln!(f, "{}\tIndex of next op", target)?;
+                },
+                Opcode::Next => {
+                    let (target, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    writeln!(f, "{}\tIndex of op when exhausted", target)?;
+                },
+                Opcode::JumpIfFalseNoPop => {
+                    let (target, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    writeln!(f, "{}\tIndex of next op if false", target)?;
+                },
+                Opcode::JumpIfTrueNoPop => {
+                    let (target, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    writeln!(f, "{}\tIndex of next op if true", target)?;
                 },
-                Opcode::Copy    => { writeln!(f, "Copy     \t\t--")?; },
                 Opcode::FFICall => {
                     let (ffi_index, consumed) = build_number(&self.code[index..]);
                     index += consumed;
-                    writeln!(f, "Return  \t{}\tIndexed FFI function called", ffi_index)?;
+                    writeln!(f, "{}\tIndexed FFI function called", ffi_index)?;
+                },
+                Opcode::FFICallKeywords => {
+                    let (ffi_index, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    writeln!(f, "{}\tIndexed FFI function called with keywords", ffi_index)?;
                 },
+                Opcode::DelLocals => {
+                    let (local_index, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    let (count, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    writeln!(f, "{}\t{} indexed locals deleted", local_index, count)?;
+                },
+                Opcode::GetGlobal | Opcode::SetGlobal => {
+                    let (constant_index, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    writeln!(f, "{}", self.describe_constant(constant_index))?;
+                },
+
+                // opcodes without arguments
+                  Opcode::Del
+                | Opcode::Dup
+                | Opcode::Print
+                | Opcode::Label
+                | Opcode::UnData
+                | Opcode::UnLabel
+                | Opcode::BuildRange
+                | Opcode::Index
+                | Opcode::Raise
+                | Opcode::EndTry
+                | Opcode::Swap
+                | Opcode::Map
+                | Opcode::Filter
+                | Opcode::Fold
+                | Opcode::LoadUnit
+                | Opcode::LoadTrue
+                | Opcode::LoadFalse
+                | Opcode::Nop
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::Propagate
+                | Opcode::Exit
+                | Opcode::Random
+                | Opcode::RandomInt => { writeln!(f, "\t--")?; },
             }
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{source::Source, closure::Closure};
+    use crate::compiler::{lex, parse, desugar, hoist, gen};
+    use crate::vm::VM;
+
+    fn compile(source: &str) -> Lambda {
+        let lambda = lex(Source::source(source))
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(hoist)
+            .and_then(|sst| Ok(gen(sst)?))
+            .unwrap();
+        (*lambda).clone()
+    }
+
+    fn run(lambda: Lambda) -> VM {
+        let mut vm = VM::init(Closure::wrap(Rc::new(lambda)));
+        vm.run().unwrap();
+        vm
+    }
+
+    #[test]
+    fn merge() {
+        let first  = compile("a = 2\nf = n -> n * a\nf 10");
+        // `g` captures a local, whose index changes once merged
+        let second = compile("
+            total = 0
+            for x in [1, 2, 3] { total = total + x }
+            g = y -> y + total
+            (g 10, 1.5)
+        ");
+
+        let merged = first.merge(&second).unwrap();
+        assert_eq!(merged.decls, first.decls + second.decls);
+
+        let mut vm = run(merged);
+        assert_eq!(vm.stack.pop_data(), Data::Tuple(vec![Data::Integer(16), Data::Real(1.5)]));
+    }
+
+    #[test]
+    fn merge_long_constants() {
+        // the first chunk fills up the constants table,
+        // so the constants of the second no longer fit in a single byte
+        let items = (0..300).map(|n| n.to_string()).collect::<Vec<_>>().join(", ");
+        let first  = compile(&format!("xs = [{}]", items));
+        let second = compile("ys = (1000, 2000)\nfor y in [3000] { ys = (ys, y) }\nys");
+
+        let merged = first.merge(&second).unwrap();
+        assert!(merged.constants.len() > u8::MAX as usize);
+        assert!(merged.code.contains(&Opcode::ConLong.to_byte()));
+
+        let mut vm = run(merged);
+        assert_eq!(vm.stack.pop_data(), Data::Tuple(vec![
+            Data::Tuple(vec![Data::Integer(1000), Data::Integer(2000)]),
+            Data::Integer(3000),
+        ]));
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::common::{
+    data::Data,
+    key::Key,
+};
+
+/// A map from `Key`s to `Data`, which remembers the order keys were inserted in.
+/// Iterating over a map, or printing it, always follows this order,
+/// so programs that use maps behave the same from run to run.
+/// Updating the value of a key already in the map does not change its position.
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<(Key, Data)>,
+    indices: HashMap<Key, usize>,
+}
+
+impl Map {
+    /// Creates a new empty `Map`.
+    pub fn new() -> Map {
+        Map { entries: vec![], indices: HashMap::new() }
+    }
+
+    /// Sets a key to a value,
+    /// returning the value the key was previously set to, if any.
+    pub fn insert(&mut self, key: Key, value: Data) -> Option<Data> {
+        if let Some(index) = self.indices.get(&key) {
+            return Some(std::mem::replace(&mut self.entries[*index].1, value));
+        }
+
+        self.indices.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+        None
+    }
+
+    /// Returns the value of a key, if it is in the map.
+    pub fn get(&self, key: &Key) -> Option<&Data> {
+        self.indices.get(key).map(|index| &self.entries[*index].1)
+    }
+
+    /// Returns the `n`th key-value pair inserted into the map.
+    pub fn get_index(&self, n: usize) -> Option<(&Key, &Data)> {
+        self.entries.get(n).map(|(k, v)| (k, v))
+    }
+
+    /// Returns the number of keys in the map.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the map has no keys.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the key-value pairs of the map, in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Data)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    /// Iterates over the values of the map, in insertion order.
+    pub fn values(&self) -> impl Iterator<Item = &Data> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
+
+impl IntoIterator for Map {
+    type Item = (Key, Data);
+    type IntoIter = std::vec::IntoIter<(Key, Data)>;
+
+    /// Consumes the map, yielding its key-value pairs in insertion order.
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl PartialEq for Map {
+    /// Maps are equal if they have the same keys set to the same values,
+    /// regardless of the order those keys were inserted in.
+    fn eq(&self, other: &Map) -> bool {
+        self.len() == other.len()
+        && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(name: &str) -> Key {
+        Key::String(name.to_string())
+    }
+
+    #[test]
+    fn insertion_order() {
+        let mut map = Map::new();
+        for (i, name) in ["c", "a", "b"].iter().enumerate() {
+            map.insert(key(name), Data::Integer(i as i64));
+        }
+        // updating a key keeps its place
+        assert_eq!(map.insert(key("c"), Data::Integer(3)), Some(Data::Integer(0)));
+
+        assert_eq!(
+            map.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>(),
+            vec![key("c"), key("a"), key("b")],
+        );
+        assert_eq!(map.get(&key("c")), Some(&Data::Integer(3)));
+        assert_eq!(format!("{}", Data::Map(map)), "{\"c\": 3, \"a\": 1, \"b\": 2}");
+    }
+
+    #[test]
+    fn equality() {
+        let mut a = Map::new();
+        a.insert(key("x"), Data::Integer(1));
+        a.insert(key("y"), Data::Integer(2));
+
+        let mut b = Map::new();
+        b.insert(key("y"), Data::Integer(2));
+        b.insert(key("x"), Data::Integer(1));
+
+        assert_eq!(a, b);
+        b.insert(key("x"), Data::Integer(3));
+        assert_ne!(a, b);
+    }
+}
@@ -8,6 +8,8 @@
 pub mod source;
 pub mod span;
 pub mod data;
+pub mod key;
+pub mod map;
 pub mod number;
 pub mod opcode;
 pub mod lambda;
@@ -31,6 +31,23 @@ pub fn split_number(n: usize) -> Vec<u8> {
     bytes
 }
 
+/// Splits a number into a vector of bytes that is always `width` bytes long,
+/// by padding the front of the chain with empty data bytes.
+/// `build_number` decodes padded and unpadded chains alike;
+/// this is used to reserve space for numbers that are not yet known,
+/// e.g. the targets of forward jumps.
+/// Panics if the number does not fit in `width` bytes.
+pub fn split_number_padded(n: usize, width: usize) -> Vec<u8> {
+    let mut bytes = split_number(n);
+    if bytes.len() > width {
+        panic!("The number {} does not fit in {} bytes", n, width);
+    }
+
+    let mut padded = vec![0; width - bytes.len()];
+    padded.append(&mut bytes);
+    padded
+}
+
 /// This takes a stream of bytes, and builds the next number in it.
 /// Note that this function tries to build a number no matter what,
 /// even if the byte stream does not have a number, is empty, or ends after a continue bit is set.
@@ -89,6 +106,15 @@ mod test {
         assert_eq!((x, eat), build_number(&extra));
     }
 
+    #[test]
+    fn padded() {
+        let x = 1337;
+        let padded = split_number_padded(x, 4);
+        assert_eq!(padded.len(), 4);
+        assert_eq!(build_number(&padded), (x, 4));
+        assert_eq!(split_number_padded(0, 1), split_number(0));
+    }
+
     #[test]
     fn zero() {
         let mut zero = split_number(0);
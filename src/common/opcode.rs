@@ -2,22 +2,26 @@
 /// Under the hood, it's just a byte.
 /// This allows non opcode bytes to be inserted in bytecode streams.
 #[repr(u8)]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Opcode {
-    /// Load a constant.
+    /// Load a constant, whose index in the constant pool fits in a single byte.
     Con = 0,
+    /// Load a constant, whose index is a variable-length number.
+    ConLong,
     /// Load uninitialized Data.
     NotInit,
     /// Delete a value off the stack.
     Del,
     /// Calls out to a Rust function via FFI
     FFICall,
-    /// Copies topmost value on the stack.
-    Copy,
+    /// Duplicates the topmost value on the stack.
+    Dup,
     /// Moves a variable onto the heap.
     Capture,
-    /// Save a constant into a variable.
-    Save,
+    /// Save a value into a variable that has not been assigned to yet.
+    Declare,
+    /// Save a value into a variable that has already been assigned to.
+    Mutate,
     /// Save a value to a captured variable.
     SaveCap,
     /// Push a copy of a variable onto the stack.
@@ -42,16 +46,342 @@ pub enum Opcode {
     UnLabel,
     /// Sestructures a tuple.
     UnTuple,
+    /// Constructs a range from two integers.
+    BuildRange,
+    /// Constructs a list.
+    List,
+    /// Jumps unconditionally to an index in the bytecode.
+    Jump,
+    /// Advances an iterator, pushing its next item,
+    /// or jumps to an index in the bytecode if it is exhausted.
+    Next,
+    /// Indexes into a list, string, or byte string, or slices it using a range.
+    Index,
+    /// Raises a user error with the value on top of the stack as its message.
+    Raise,
+    /// Installs an error handler, which jumps to an index in the bytecode on error.
+    Try,
+    /// Removes the topmost error handler, wrapping the value on top of the stack.
+    EndTry,
+    /// Swaps the top two values on the stack.
+    Swap,
+    /// Calls a function on each item of a list, collecting the results.
+    Map,
+    /// Keeps the items of a list that a function returns true for.
+    Filter,
+    /// Combines the items of a list using a function and an initial value.
+    Fold,
+    /// Load Unit, without going through the constant pool.
+    LoadUnit,
+    /// Load `true`, without going through the constant pool.
+    LoadTrue,
+    /// Load `false`, without going through the constant pool.
+    LoadFalse,
+    /// Does nothing, used to pad bytecode.
+    Nop,
+    /// Tests whether the two items of a tuple are equal.
+    Equal,
+    /// Tests whether the two items of a tuple are not equal.
+    NotEqual,
+    /// Returns `nil` from the current function if the value on top of the stack is `nil`.
+    Propagate,
+    /// Returns the value on top of the stack from the current function early,
+    /// discarding its locals and any temporaries.
+    Exit,
+    /// Generates a random real between 0 and 1, using the VM's source of random numbers.
+    Random,
+    /// Generates a random integer in the half-open range given by a tuple of two integers.
+    RandomInt,
+    /// Loads the global variable named by a string constant.
+    GetGlobal,
+    /// Saves the topmost value on the stack into the global variable named by a string constant.
+    SetGlobal,
+    /// Jumps to the target if the boolean on top of the stack is false,
+    /// leaving the boolean on the stack as the result, used for `and`.
+    JumpIfFalseNoPop,
+    /// Jumps to the target if the boolean on top of the stack is true,
+    /// leaving the boolean on the stack as the result, used for `or`.
+    JumpIfTrueNoPop,
+    /// Deletes the locals of a block that has exited,
+    /// given the index of the first local and how many there are.
+    DelLocals,
+    /// Calls out to a Rust function via FFI, passing it keyword arguments,
+    /// given as a tuple of name-value pairs on top of the argument.
+    FFICallKeywords,
 }
 
 impl Opcode {
     /// Convert a raw byte to an opcode.
-    /// Note that non-opcode bytes should never be interpreted as an opcode.
-    /// Under the hood, this is just a transmute, so the regular cautions apply.
-    /// This *should* never cause a crash
-    /// and if it does, the vm's designed to crash hard
-    /// so it'll be pretty obvious.
-    pub fn from_byte(byte: u8) -> Opcode {
-        unsafe { std::mem::transmute(byte) }
+    /// Returns `None` if the byte does not represent an opcode,
+    /// which should only happen with corrupted or hand-written bytecode.
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        Opcode::ALL.get(byte as usize).copied()
+    }
+
+    /// Convert an opcode to the raw byte it's represented by.
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+
+    /// Every opcode, in the order of the bytes representing them.
+    /// New opcodes must be added here as well.
+    pub const ALL: &'static [Opcode] = &[
+        Opcode::Con,
+        Opcode::ConLong,
+        Opcode::NotInit,
+        Opcode::Del,
+        Opcode::FFICall,
+        Opcode::Dup,
+        Opcode::Capture,
+        Opcode::Declare,
+        Opcode::Mutate,
+        Opcode::SaveCap,
+        Opcode::Load,
+        Opcode::LoadCap,
+        Opcode::Call,
+        Opcode::Return,
+        Opcode::Closure,
+        Opcode::Print,
+        Opcode::Label,
+        Opcode::Tuple,
+        Opcode::UnData,
+        Opcode::UnLabel,
+        Opcode::UnTuple,
+        Opcode::BuildRange,
+        Opcode::List,
+        Opcode::Jump,
+        Opcode::Next,
+        Opcode::Index,
+        Opcode::Raise,
+        Opcode::Try,
+        Opcode::EndTry,
+        Opcode::Swap,
+        Opcode::Map,
+        Opcode::Filter,
+        Opcode::Fold,
+        Opcode::LoadUnit,
+        Opcode::LoadTrue,
+        Opcode::LoadFalse,
+        Opcode::Nop,
+        Opcode::Equal,
+        Opcode::NotEqual,
+        Opcode::Propagate,
+        Opcode::Exit,
+        Opcode::Random,
+        Opcode::RandomInt,
+        Opcode::GetGlobal,
+        Opcode::SetGlobal,
+        Opcode::JumpIfFalseNoPop,
+        Opcode::JumpIfTrueNoPop,
+        Opcode::DelLocals,
+        Opcode::FFICallKeywords,
+    ];
+
+    /// Returns the number of operands that follow the opcode in bytecode.
+    /// `Con`'s operand is a single byte,
+    /// every other operand is a variable-length number, see `common::number`.
+    /// Jumps always have a single operand, their target.
+    /// There is deliberately no catch-all arm,
+    /// so a new opcode must be classified before it can be used.
+    pub fn operand_count(self) -> usize {
+        match self {
+              Opcode::Con
+            | Opcode::ConLong
+            | Opcode::FFICall
+            | Opcode::FFICallKeywords
+            | Opcode::Capture
+            | Opcode::Declare
+            | Opcode::Mutate
+            | Opcode::SaveCap
+            | Opcode::Load
+            | Opcode::LoadCap
+            | Opcode::Return
+            | Opcode::Closure
+            | Opcode::Tuple
+            | Opcode::UnTuple
+            | Opcode::List
+            | Opcode::Jump
+            | Opcode::Next
+            | Opcode::Try
+            | Opcode::GetGlobal
+            | Opcode::SetGlobal
+            | Opcode::JumpIfFalseNoPop
+            | Opcode::JumpIfTrueNoPop => 1,
+
+            Opcode::DelLocals => 2,
+
+              Opcode::NotInit
+            | Opcode::Del
+            | Opcode::Dup
+            | Opcode::Call
+            | Opcode::Print
+            | Opcode::Label
+            | Opcode::UnData
+            | Opcode::UnLabel
+            | Opcode::BuildRange
+            | Opcode::Index
+            | Opcode::Raise
+            | Opcode::EndTry
+            | Opcode::Swap
+            | Opcode::Map
+            | Opcode::Filter
+            | Opcode::Fold
+            | Opcode::LoadUnit
+            | Opcode::LoadTrue
+            | Opcode::LoadFalse
+            | Opcode::Nop
+            | Opcode::Equal
+            | Opcode::NotEqual
+            | Opcode::Propagate
+            | Opcode::Exit
+            | Opcode::Random
+            | Opcode::RandomInt => 0,
+        }
+    }
+
+    /// Returns whether the opcode is followed by at least one operand in bytecode.
+    pub fn has_operand(self) -> bool {
+        self.operand_count() > 0
+    }
+
+    /// Returns whether the operand of the opcode is the index of an op to continue from,
+    /// i.e. whether the opcode may jump.
+    /// Jump targets must be recalculated whenever bytecode is moved around.
+    pub fn is_jump(self) -> bool {
+        matches!(
+            self,
+            Opcode::Jump
+            | Opcode::Next
+            | Opcode::Try
+            | Opcode::JumpIfFalseNoPop
+            | Opcode::JumpIfTrueNoPop
+        )
+    }
+
+    /// Returns the name of the opcode, as shown when disassembling bytecode.
+    pub fn name(self) -> &'static str {
+        match self {
+            Opcode::Con     => "Con",
+            Opcode::ConLong => "ConLong",
+            Opcode::NotInit => "NotInit",
+            Opcode::Del     => "Del",
+            Opcode::FFICall => "FFICall",
+            Opcode::Dup     => "Dup",
+            Opcode::Capture => "Capture",
+            Opcode::Declare => "Declare",
+            Opcode::Mutate  => "Mutate",
+            Opcode::SaveCap => "SaveCap",
+            Opcode::Load    => "Load",
+            Opcode::LoadCap => "LoadCap",
+            Opcode::Call    => "Call",
+            Opcode::Return  => "Return",
+            Opcode::Closure => "Closure",
+            Opcode::Print   => "Print",
+            Opcode::Label   => "Label",
+            Opcode::Tuple   => "Tuple",
+            Opcode::UnData  => "UnData",
+            Opcode::UnLabel => "UnLabel",
+            Opcode::UnTuple => "UnTuple",
+            Opcode::BuildRange => "BuildRange",
+            Opcode::List    => "List",
+            Opcode::Jump    => "Jump",
+            Opcode::Next    => "Next",
+            Opcode::Index   => "Index",
+            Opcode::Raise   => "Raise",
+            Opcode::Try     => "Try",
+            Opcode::EndTry  => "EndTry",
+            Opcode::Swap    => "Swap",
+            Opcode::Map     => "Map",
+            Opcode::Filter  => "Filter",
+            Opcode::Fold    => "Fold",
+            Opcode::LoadUnit  => "LoadUnit",
+            Opcode::LoadTrue  => "LoadTrue",
+            Opcode::LoadFalse => "LoadFalse",
+            Opcode::Nop       => "Nop",
+            Opcode::Equal     => "Equal",
+            Opcode::NotEqual  => "NotEqual",
+            Opcode::Propagate => "Propagate",
+            Opcode::Exit      => "Exit",
+            Opcode::Random    => "Random",
+            Opcode::RandomInt => "RandomInt",
+            Opcode::GetGlobal => "GetGlobal",
+            Opcode::SetGlobal => "SetGlobal",
+            Opcode::JumpIfFalseNoPop => "JumpIfFalseNoPop",
+            Opcode::JumpIfTrueNoPop  => "JumpIfTrueNoPop",
+            Opcode::DelLocals => "DelLocals",
+            Opcode::FFICallKeywords => "FFICallKeywords",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn round_trip() {
+        for (index, opcode) in Opcode::ALL.iter().enumerate() {
+            // opcodes must be listed in order, without any gaps
+            assert_eq!(opcode.to_byte() as usize, index);
+            assert_eq!(Opcode::from_byte(opcode.to_byte()), Some(*opcode));
+        }
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(Opcode::from_byte(Opcode::ALL.len() as u8), None);
+        assert_eq!(Opcode::from_byte(u8::MAX), None);
+    }
+
+    #[test]
+    fn classification() {
+        let jumps = [
+            Opcode::Jump,
+            Opcode::Next,
+            Opcode::Try,
+            Opcode::JumpIfFalseNoPop,
+            Opcode::JumpIfTrueNoPop,
+        ];
+        let operands = [
+            Opcode::Con,
+            Opcode::ConLong,
+            Opcode::FFICall,
+            Opcode::FFICallKeywords,
+            Opcode::Capture,
+            Opcode::Declare,
+            Opcode::Mutate,
+            Opcode::SaveCap,
+            Opcode::Load,
+            Opcode::LoadCap,
+            Opcode::Return,
+            Opcode::Closure,
+            Opcode::Tuple,
+            Opcode::UnTuple,
+            Opcode::List,
+            Opcode::GetGlobal,
+            Opcode::SetGlobal,
+            Opcode::DelLocals,
+        ];
+
+        for opcode in Opcode::ALL.iter().copied() {
+            let jump = jumps.contains(&opcode);
+            assert_eq!(opcode.is_jump(), jump, "{} is_jump", opcode.name());
+
+            // jump targets are operands too
+            let operand = jump || operands.contains(&opcode);
+            assert_eq!(opcode.has_operand(), operand, "{} has_operand", opcode.name());
+            // only `DelLocals` has more than one operand
+            let count = if opcode == Opcode::DelLocals { 2 } else if operand { 1 } else { 0 };
+            assert_eq!(opcode.operand_count(), count, "{} operand_count", opcode.name());
+        }
+    }
+
+    #[test]
+    fn unique_names() {
+        let names = Opcode::ALL.iter()
+            .map(|o| o.name())
+            .collect::<HashSet<_>>();
+        assert_eq!(names.len(), Opcode::ALL.len());
     }
 }
@@ -5,7 +5,6 @@ use std::{
         Debug,
         Display,
     },
-    usize,
     rc::Rc,
 };
 
@@ -45,7 +44,7 @@ impl Span {
 
     /// Checks if a `Span` is empty.
     pub fn is_empty(&self) -> bool {
-        self.source == None
+        self.source.is_none()
     }
 
     /// Return the index of the end of the `Span`.
@@ -87,6 +86,29 @@ impl Span {
         Span::new(a.source.as_ref().unwrap(), offset, length)
     }
 
+    /// Creates a new `Span` covering both this `Span` and another.
+    /// Method form of `Span::combine`.
+    pub fn merge(&self, other: &Span) -> Span {
+        Span::combine(self, other)
+    }
+
+    /// Returns the line and column this `Span` starts at, both starting from 1.
+    /// Columns are counted in characters rather than bytes.
+    /// Returns `None` if the `Span` is empty.
+    pub fn line_col(&self) -> Option<(usize, usize)> {
+        let source = self.source.as_ref()?;
+        let (line, col) = Span::line_index(&source.contents, self.offset)?;
+        Some((line + 1, col + 1))
+    }
+
+    /// Returns the line and column this `Span` ends at, both starting from 1.
+    /// See `Span::line_col`.
+    pub fn end_line_col(&self) -> Option<(usize, usize)> {
+        let source = self.source.as_ref()?;
+        let (line, col) = Span::line_index(&source.contents, self.end())?;
+        Some((line + 1, col + 1))
+    }
+
     /// Combines a set of `Span`s (think fold-left over `Span::combine`).
     pub fn join(mut spans: Vec<Span>) -> Span {
         let mut combined = match spans.pop() {
@@ -125,6 +147,15 @@ impl Span {
         string.split('\n').map(|l| l.to_string()).collect()
     }
 
+    /// Clamps an index into a string to the end of that string,
+    /// moving it back to the start of the character it falls in if need be,
+    /// so that the string can be safely sliced at that index.
+    fn clamp(string: &str, index: usize) -> usize {
+        let mut index = index.min(string.len());
+        while !string.is_char_boundary(index) { index -= 1; }
+        index
+    }
+
     /// Returns the start and end lines and columns of the `Span` if the `Span` is not empty.
     fn line_index(string: &str, index: usize) -> Option<(usize, usize)> {
         let lines = Span::lines_newline(&string[..index]);
@@ -162,6 +193,14 @@ impl Display for Span {
     /// 14 >    another { error }
     /// 15 > }
     /// ```
+    /// `Span`s that reach past the end of the source are cut short.
+    /// A zero-length `Span` at the end of the source,
+    /// such as one pointing at a missing token,
+    /// places a single caret right after the last line:
+    /// ```plain
+    /// 1 | x = (1, 2
+    ///   |          ^
+    /// ```
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         if self.is_empty() {
             panic!("Can't display the section corresponding with an empty Span")
@@ -170,11 +209,23 @@ impl Display for Span {
         let full_source = &self.source.as_ref().unwrap().contents;
         let lines = Span::lines(full_source);
 
-        let (start_line, start_col) = match Span::line_index(full_source, self.offset) {
+        let offset = Span::clamp(full_source, self.offset);
+        let end    = Span::clamp(full_source, self.end()).max(offset);
+
+        // a zero-length span at the end of the source points at the end of the last line,
+        // rather than past any trailing newlines
+        let (offset, end) = if offset == end && end == full_source.len() {
+            let last = full_source.trim_end_matches('\n').len();
+            (last, last)
+        } else {
+            (offset, end)
+        };
+
+        let (start_line, start_col) = match Span::line_index(full_source, offset) {
             Some(li) => li,
             None     => unreachable!(),
         };
-        let (end_line, _end_col) = match Span::line_index(full_source, self.end()) {
+        let (end_line, _end_col) = match Span::line_index(full_source, end) {
             Some(li) => li,
             None     => unreachable!(),
         };
@@ -202,7 +253,7 @@ impl Display for Span {
                 " {} | {}{}",
                 " ".repeat(padding),
                 " ".repeat(start_col),
-                "^".repeat(self.length.max(1)),
+                "^".repeat(full_source[offset..end].chars().count().max(1)),
             );
 
             writeln!(f, "{}", location)?;
@@ -279,6 +330,66 @@ mod test {
         assert_eq!(Span::combine(&a, &b), Span::new(&source, 0, 13));
     }
 
+    #[test]
+    fn merge() {
+        let source = Source::source("heck, that's awesome");
+        let a = Span::new(&source, 13, 7);
+        let b = Span::new(&source, 0, 4);
+
+        assert_eq!(a.merge(&b), Span::new(&source, 0, 20));
+        assert_eq!(a.merge(&Span::empty()), a);
+        assert_eq!(Span::empty().merge(&b), b);
+    }
+
+    #[test]
+    fn line_col() {
+        // 'é' and 'ö' are both two bytes long
+        let source = Source::source("héllo\nwörld");
+        let wo = Span::new(&source, 7, 3);
+        let ld = Span::new(&source, 11, 2);
+
+        assert_eq!(wo.contents(), "wö");
+        assert_eq!(wo.line_col(), Some((2, 1)));
+        assert_eq!(wo.end_line_col(), Some((2, 3)));
+        assert_eq!(ld.line_col(), Some((2, 4)));
+        assert_eq!(Span::new(&source, 6, 0).line_col(), Some((1, 6)));
+        assert_eq!(Span::empty().line_col(), None);
+    }
+
+    #[test]
+    fn display_multibyte() {
+        let source = Source::source("x = \"ünïcode\"");
+        let span = Span::new(&source, 4, 11);
+        assert_eq!(format!("{}", span), "\
+            In ./source:1:5\n   \
+               |\n \
+             1 | x = \"ünïcode\"\n   \
+               |     ^^^^^^^^^\n   \
+               |\n\
+            "
+        )
+    }
+
+    #[test]
+    fn display_eof() {
+        let expected = "\
+            In ./source:1:10\n   \
+               |\n \
+             1 | x = (1, 2\n   \
+               |          ^\n   \
+               |\n\
+        ";
+
+        // a zero-length span at the end of the source
+        let source = Source::source("x = (1, 2");
+        assert_eq!(format!("{}", Span::point(&source, 9)), expected);
+
+        // trailing newlines are skipped, and spans past the end are cut short
+        let source = Source::source("x = (1, 2\n\n");
+        assert_eq!(format!("{}", Span::point(&source, 11)), expected);
+        assert_eq!(format!("{}", Span::new(&source, 11, 5)), expected);
+    }
+
     #[test]
     fn span_and_contents() {
         let source = Source::source("hello, this is some text!");
@@ -100,39 +100,144 @@ impl TryFrom<AST> for ASTPattern {
 /// When macros are added, for instance, they will be here,
 /// But not in the `CST`, which is the desugared syntax tree,
 /// and represents language-level constructs
+///
+/// The `AST` is the public output of the parser,
+/// so tools like formatters and linters can consume parsed programs
+/// without going further down the compiler pipeline.
+/// To keep its shape stable for these tools,
+/// new constructs should be added as new variants
+/// rather than by changing the fields of existing ones.
 #[derive(Debug, Clone, PartialEq)]
 pub enum AST {
+    /// A variable name, i.e. `x`.
     Symbol(String),
+    /// A literal value, i.e. `1.0` or `"hello"`.
     Data(Data),
+    /// A sequence of expressions, i.e. `{ a; b }`,
+    /// a whole program is parsed as a single block.
     Block(Vec<Spanned<AST>>),
+    /// Juxtaposed expressions, such as a call `f x` or a macro invocation.
     Form(Vec<Spanned<AST>>),
+    /// An expression wrapped in parenthesis, i.e. `(a)`.
     Group(Box<Spanned<AST>>),
+    /// A pattern, produced when parsing macros.
     CSTPattern(ASTPattern),
+    /// An argument pattern, produced when parsing macros.
     ArgPattern(ArgPattern),
+    /// A tuple, i.e. `(a, b)`.
     Tuple(Vec<Spanned<AST>>),
+    /// A list, i.e. `[a, b]`.
+    List(Vec<Spanned<AST>>),
+    /// An assignment, i.e. `pattern = expression`,
+    /// which evaluates to the assigned value.
     Assign {
         pattern:    Box<Spanned<ASTPattern>>,
         expression: Box<Spanned<AST>>,
     },
+    /// An assignment that can not be reassigned, i.e. `const pattern = expression`.
+    Const {
+        pattern:    Box<Spanned<ASTPattern>>,
+        expression: Box<Spanned<AST>>,
+    },
+    /// An update of an existing variable, i.e. `pattern += expression`,
+    /// where the expression is the updated value.
+    Mutate {
+        pattern:    Box<Spanned<ASTPattern>>,
+        expression: Box<Spanned<AST>>,
+    },
+    /// A function, i.e. `pattern -> expression`.
     Lambda {
         pattern:    Box<Spanned<ASTPattern>>,
         expression: Box<Spanned<AST>>,
     },
+    /// A reverse function application, i.e. `argument.function`.
     Composition {
         argument: Box<Spanned<AST>>,
         function: Box<Spanned<AST>>,
     },
+    /// A label applied to an expression, i.e. `Label expression`.
     Label(String, Box<Spanned<AST>>),
+    /// An expression whose errors are caught, i.e. `try expression`.
+    Try(Box<Spanned<AST>>),
+    /// An expression that returns `nil` early if it is `nil`, i.e. `expression?`.
+    Propagate(Box<Spanned<AST>>),
+    /// A keyword argument passed to an FFI function, i.e. `name: value`,
+    /// as in `magic "name" (1, name: value)`.
+    Keyword {
+        name:  String,
+        value: Box<Spanned<AST>>,
+    },
+    /// An early return from the enclosing function, i.e. `return expression`.
+    Return(Box<Spanned<AST>>),
+    /// A range of integers, i.e. `start..end`.
+    Range {
+        start: Box<Spanned<AST>>,
+        end:   Box<Spanned<AST>>,
+    },
+    /// Indexing into some data, i.e. `expression[index]`.
+    Index {
+        expression: Box<Spanned<AST>>,
+        index:      Box<Spanned<AST>>,
+    },
+    /// A loop, i.e. `for pattern in iterable body`.
+    For {
+        pattern:  Box<Spanned<ASTPattern>>,
+        iterable: Box<Spanned<AST>>,
+        body:     Box<Spanned<AST>>,
+    },
+    /// A list comprehension, i.e. `[element for pattern in iterable if filter]`,
+    /// where the filter is optional.
+    Comprehension {
+        element:  Box<Spanned<AST>>,
+        pattern:  Box<Spanned<ASTPattern>>,
+        iterable: Box<Spanned<AST>>,
+        filter:   Option<Box<Spanned<AST>>>,
+    },
+    /// An expression with local bindings, i.e. `expression where pattern = value, ...`.
+    /// The bindings are only visible to later bindings and the expression.
+    Where {
+        expression: Box<Spanned<AST>>,
+        bindings:   Vec<(Spanned<ASTPattern>, Spanned<AST>)>,
+    },
+    /// A macro definition, i.e. `syntax arg_pat expression`.
     Syntax {
         arg_pat:    Box<Spanned<ArgPattern>>,
         expression: Box<Spanned<AST>>,
     },
     // TODO: Currently quite basic
     // Use a symbol or the like?
+    /// A call out to a Rust function, i.e. `magic "name" expression`.
     FFI {
         name:       String,
         expression: Box<Spanned<AST>>,
     },
+    /// An import of another module, i.e. `import "path"`.
+    /// Imports are resolved into expressions before desugaring.
+    Import(String),
+    /// A string with embedded expressions, i.e. `"x is ${x + 1}"`.
+    Interpolation(Vec<Fragment>),
+}
+
+/// A fragment of an interpolated string, see `AST::Interpolation`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fragment {
+    /// Text included as is, with escapes already converted.
+    Text(String),
+    /// An expression embedded between `${` and `}`.
+    Expression(Spanned<AST>),
+}
+
+impl Fragment {
+    /// Applies a fallible transformation to the embedded expression, if there is one.
+    pub fn map_expression<E>(
+        self,
+        f: impl FnOnce(Spanned<AST>) -> Result<Spanned<AST>, E>,
+    ) -> Result<Fragment, E> {
+        match self {
+            Fragment::Text(text) => Ok(Fragment::Text(text)),
+            Fragment::Expression(expression) => Ok(Fragment::Expression(f(expression)?)),
+        }
+    }
 }
 
 impl AST {
@@ -147,6 +252,28 @@ impl AST {
         }
     }
 
+    /// Shortcut for creating an `AST::Const` variant.
+    pub fn constant(
+        pattern:    Spanned<ASTPattern>,
+        expression: Spanned<AST>
+    ) -> AST {
+        AST::Const {
+            pattern:    Box::new(pattern),
+            expression: Box::new(expression)
+        }
+    }
+
+    /// Shortcut for creating an `AST::Mutate` variant.
+    pub fn mutate(
+        pattern:    Spanned<ASTPattern>,
+        expression: Spanned<AST>
+    ) -> AST {
+        AST::Mutate {
+            pattern:    Box::new(pattern),
+            expression: Box::new(expression)
+        }
+    }
+
     /// Shortcut for creating an `AST::Lambda` variant.
     pub fn lambda(
         pattern:    Spanned<ASTPattern>,
@@ -169,6 +296,67 @@ impl AST {
         }
     }
 
+    /// Shortcut for creating an `AST::Range` variant.
+    pub fn range(
+        start: Spanned<AST>,
+        end:   Spanned<AST>,
+    ) -> AST {
+        AST::Range {
+            start: Box::new(start),
+            end:   Box::new(end),
+        }
+    }
+
+    /// Shortcut for creating an `AST::Index` variant.
+    pub fn index(
+        expression: Spanned<AST>,
+        index:      Spanned<AST>,
+    ) -> AST {
+        AST::Index {
+            expression: Box::new(expression),
+            index:      Box::new(index),
+        }
+    }
+
+    /// Shortcut for creating an `AST::For` variant.
+    pub fn for_loop(
+        pattern:  Spanned<ASTPattern>,
+        iterable: Spanned<AST>,
+        body:     Spanned<AST>,
+    ) -> AST {
+        AST::For {
+            pattern:  Box::new(pattern),
+            iterable: Box::new(iterable),
+            body:     Box::new(body),
+        }
+    }
+
+    /// Shortcut for creating an `AST::Comprehension` variant.
+    pub fn comprehension(
+        element:  Spanned<AST>,
+        pattern:  Spanned<ASTPattern>,
+        iterable: Spanned<AST>,
+        filter:   Option<Spanned<AST>>,
+    ) -> AST {
+        AST::Comprehension {
+            element:  Box::new(element),
+            pattern:  Box::new(pattern),
+            iterable: Box::new(iterable),
+            filter:   filter.map(Box::new),
+        }
+    }
+
+    /// Shortcut for creating an `AST::Where` variant.
+    pub fn where_clause(
+        expression: Spanned<AST>,
+        bindings:   Vec<(Spanned<ASTPattern>, Spanned<AST>)>,
+    ) -> AST {
+        AST::Where {
+            expression: Box::new(expression),
+            bindings,
+        }
+    }
+
     /// Shortcut for creating an `AST::Syntax` variant.
     /// i.e. a macro definition
     pub fn syntax(
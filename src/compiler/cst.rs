@@ -57,6 +57,14 @@ pub enum CST {
         pattern:    Box<Spanned<CSTPattern>>,
         expression: Box<Spanned<CST>>,
     },
+    Const {
+        pattern:    Box<Spanned<CSTPattern>>,
+        expression: Box<Spanned<CST>>,
+    },
+    Mutate {
+        pattern:    Box<Spanned<CSTPattern>>,
+        expression: Box<Spanned<CST>>,
+    },
     Lambda {
         pattern:    Box<Spanned<CSTPattern>>,
         expression: Box<Spanned<CST>>,
@@ -66,10 +74,28 @@ pub enum CST {
         arg: Box<Spanned<CST>>,
     },
     Label(String, Box<Spanned<CST>>),
+    Try(Box<Spanned<CST>>),
+    Propagate(Box<Spanned<CST>>),
+    Return(Box<Spanned<CST>>),
     Tuple(Vec<Spanned<CST>>),
+    List(Vec<Spanned<CST>>),
+    Range {
+        start: Box<Spanned<CST>>,
+        end:   Box<Spanned<CST>>,
+    },
+    Index {
+        expression: Box<Spanned<CST>>,
+        index:      Box<Spanned<CST>>,
+    },
+    For {
+        pattern:  Box<Spanned<CSTPattern>>,
+        iterable: Box<Spanned<CST>>,
+        body:     Box<Spanned<CST>>,
+    },
     FFI {
         name:       String,
         expression: Box<Spanned<CST>>,
+        keywords:   Vec<(String, Spanned<CST>)>,
     },
 }
 
@@ -85,6 +111,28 @@ impl CST {
         }
     }
 
+    /// Shortcut for creating an `CST::Const` variant.
+    pub fn constant(
+        pattern:    Spanned<CSTPattern>,
+        expression: Spanned<CST>
+    ) -> CST {
+        CST::Const {
+            pattern:    Box::new(pattern),
+            expression: Box::new(expression)
+        }
+    }
+
+    /// Shortcut for creating an `CST::Mutate` variant.
+    pub fn mutate(
+        pattern:    Spanned<CSTPattern>,
+        expression: Spanned<CST>
+    ) -> CST {
+        CST::Mutate {
+            pattern:    Box::new(pattern),
+            expression: Box::new(expression)
+        }
+    }
+
     /// Shortcut for creating an `CST::Lambda` variant.
     pub fn lambda(
         pattern:    Spanned<CSTPattern>,
@@ -109,11 +157,41 @@ impl CST {
         }
     }
 
+    /// Shortcut for creating a `CST::Range` variant.
+    pub fn range(start: Spanned<CST>, end: Spanned<CST>) -> CST {
+        CST::Range {
+            start: Box::new(start),
+            end:   Box::new(end),
+        }
+    }
+
+    /// Shortcut for creating a `CST::Index` variant.
+    pub fn index(expression: Spanned<CST>, index: Spanned<CST>) -> CST {
+        CST::Index {
+            expression: Box::new(expression),
+            index:      Box::new(index),
+        }
+    }
+
+    /// Shortcut for creating a `CST::For` variant.
+    pub fn for_loop(
+        pattern:  Spanned<CSTPattern>,
+        iterable: Spanned<CST>,
+        body:     Spanned<CST>,
+    ) -> CST {
+        CST::For {
+            pattern:  Box::new(pattern),
+            iterable: Box::new(iterable),
+            body:     Box::new(body),
+        }
+    }
+
     /// Shortcut for creating an `CST::FFI` variant.
     pub fn ffi(name: &str, expression: Spanned<CST>) -> CST {
         CST::FFI {
             name: name.to_string(),
             expression: Box::new(expression),
+            keywords: vec![],
         }
     }
 }
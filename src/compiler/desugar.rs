@@ -3,11 +3,14 @@ use std::{
     collections::HashSet,
 };
 
-use crate::common::span::{Span, Spanned};
+use crate::common::{
+    span::{Span, Spanned},
+    data::Data,
+};
 
 use crate::compiler::{
     rule::Rule,
-    ast::{AST, ASTPattern, ArgPattern},
+    ast::{AST, ASTPattern, ArgPattern, Fragment},
     cst::{CST, CSTPattern},
     syntax::Syntax
 };
@@ -50,14 +53,36 @@ impl Transformer {
             AST::Form(f) => self.form(f)?,
             AST::Group(a) => self.walk(*a)?.item,
             AST::Tuple(t) => self.tuple(t)?,
+            AST::List(l) => self.list(l)?,
             AST::CSTPattern(_) => return Err(Syntax::error("Unexpected pattern", &ast.span)),
             AST::ArgPattern(_)  => return Err(Syntax::error("Unexpected argument pattern", &ast.span)),
             AST::Label(n, e) => CST::Label(n, Box::new(self.walk(*e)?)),
+            AST::Try(e) => CST::Try(Box::new(self.walk(*e)?)),
+            AST::Propagate(e) => CST::Propagate(Box::new(self.walk(*e)?)),
+            AST::Keyword { .. } => return Err(Syntax::error(
+                "Keyword arguments can only be passed to FFI functions, i.e. `magic \"name\" (1, x: 2)`",
+                &ast.span,
+            )),
+            AST::Return(e) => CST::Return(Box::new(self.walk(*e)?)),
+            AST::Range { start, end } => CST::range(self.walk(*start)?, self.walk(*end)?),
+            AST::Index { expression, index } => CST::index(self.walk(*expression)?, self.walk(*index)?),
             AST::Syntax { arg_pat, expression } => self.rule(*arg_pat, *expression)?,
             AST::Assign { pattern, expression } => self.assign(*pattern, *expression)?,
+            AST::Const { pattern, expression } => self.constant(*pattern, *expression)?,
+            AST::Mutate { pattern, expression } => self.mutate(*pattern, *expression)?,
             AST::Lambda { pattern, expression } => self.lambda(*pattern, *expression)?,
+            AST::For { pattern, iterable, body } => self.for_loop(*pattern, *iterable, *body)?,
+            AST::Where { expression, bindings } => self.where_clause(*expression, bindings)?,
+            AST::Comprehension { element, pattern, iterable, filter } => {
+                self.comprehension(*element, *pattern, *iterable, filter.map(|f| *f))?
+            },
             AST::Composition { argument, function } => self.composition(*argument, *function)?,
             AST::FFI { name, expression } => self.ffi(name, *expression)?,
+            AST::Interpolation(fragments) => self.interpolation(fragments, &ast.span)?,
+            AST::Import(_) => return Err(Syntax::error(
+                "Imports must be resolved before desugaring, see `compiler::import`",
+                &ast.span,
+            )),
         };
 
         Ok(Spanned::new(cst, ast.span))
@@ -194,6 +219,17 @@ impl Transformer {
         Ok(CST::Tuple(expressions))
     }
 
+    /// Desugar a list.
+    /// Like a tuple, but with brackets.
+    pub fn list(&mut self, list: Vec<Spanned<AST>>) -> Result<CST, Syntax> {
+        let mut expressions = vec![];
+        for expression in list {
+            expressions.push(self.walk(expression)?)
+        }
+
+        Ok(CST::List(expressions))
+    }
+
     /// Desugar a function application.
     /// A composition takes the form `c . b . a`
     /// and is left-associative `(c . b) . a`.
@@ -204,8 +240,87 @@ impl Transformer {
 
     /// Desugar a FFI call.
     /// We walk the expression that may be passed to the FFI.
+    /// Keyword arguments, like the `x: 2` in `magic "name" (1, x: 2)`,
+    /// are separated from the positional arguments, which are passed as
+    /// `()` if there are none, the argument itself if there is one, and a tuple otherwise.
+    /// Positional arguments are evaluated before keyword arguments.
     pub fn ffi(&mut self, name: String, expression: Spanned<AST>) -> Result<CST, Syntax> {
-        Ok(CST::FFI { name, expression: Box::new(self.walk(expression)?) })
+        let is_keyword = |item: &Spanned<AST>| matches!(item.item, AST::Keyword { .. });
+        let mut arguments = &expression;
+        while let AST::Group(inner) = &arguments.item { arguments = inner; }
+
+        let has_keywords = match &arguments.item {
+            AST::Tuple(items) => items.iter().any(is_keyword),
+            _ => is_keyword(arguments),
+        };
+
+        if !has_keywords {
+            return Ok(CST::ffi(&name, self.walk(expression)?));
+        }
+
+        let mut expression = expression;
+        while let AST::Group(inner) = expression.item { expression = *inner; }
+
+        let span = expression.span.clone();
+        let items = match expression.item {
+            AST::Tuple(items) => items,
+            keyword => vec![Spanned::new(keyword, span.clone())],
+        };
+
+        let mut positional = vec![];
+        let mut keywords: Vec<(String, Spanned<CST>)> = vec![];
+        for item in items {
+            match item.item {
+                AST::Keyword { name: keyword, .. } if keywords.iter().any(|(k, _)| k == &keyword) => {
+                    return Err(Syntax::error(
+                        &format!("The keyword argument '{}' was passed more than once", keyword),
+                        &item.span,
+                    ));
+                },
+                AST::Keyword { name: keyword, value } => keywords.push((keyword, self.walk(*value)?)),
+                other => positional.push(self.walk(Spanned::new(other, item.span))?),
+            }
+        }
+
+        let expression = match positional.len() {
+            0 => Spanned::new(CST::Data(Data::Unit), span),
+            1 => positional.pop().unwrap(),
+            _ => Spanned::new(CST::Tuple(positional), span),
+        };
+
+        Ok(CST::FFI { name, expression: Box::new(expression), keywords })
+    }
+
+    /// Desugars an interpolated string, like `"x is ${x + 1}"`.
+    /// Each embedded expression is converted to a string,
+    /// then the fragments are concatenated together, from left to right:
+    /// ```ignore
+    /// "x is ${x + 1}"
+    /// -- becomes
+    /// magic "add" ("x is ", magic "to_string" (x + 1))
+    /// ```
+    pub fn interpolation(&mut self, fragments: Vec<Fragment>, span: &Span) -> Result<CST, Syntax> {
+        let mut result: Option<Spanned<AST>> = None;
+        for fragment in fragments {
+            let piece = match fragment {
+                Fragment::Text(text) => Spanned::new(AST::Data(Data::String(text)), span.clone()),
+                Fragment::Expression(expression) => {
+                    let inner = expression.span.clone();
+                    Spanned::new(AST::ffi("to_string", expression), inner)
+                },
+            };
+
+            result = Some(match result {
+                None => piece,
+                Some(left) => {
+                    let arguments = Spanned::new(AST::Tuple(vec![left, piece]), span.clone());
+                    Spanned::new(AST::ffi("add", arguments), span.clone())
+                },
+            });
+        }
+
+        let result = result.expect("An interpolated string has at least one fragment");
+        Ok(self.walk(result)?.item)
     }
 
     /// Desugars a block,
@@ -231,6 +346,112 @@ impl Transformer {
         ))
     }
 
+    /// Desugars a constant, see `assign`.
+    pub fn constant(&mut self, p: Spanned<ASTPattern>, e: Spanned<AST>) -> Result<CST, Syntax> {
+        let p_span = p.span.clone();
+
+        Ok(CST::constant(
+            p.map(CSTPattern::try_from)
+                .map_err(|err| Syntax::error(&err, &p_span))?,
+            self.walk(e)?
+        ))
+    }
+
+    /// Desugars an update of an existing variable, see `assign`.
+    pub fn mutate(&mut self, p: Spanned<ASTPattern>, e: Spanned<AST>) -> Result<CST, Syntax> {
+        let p_span = p.span.clone();
+
+        Ok(CST::mutate(
+            p.map(CSTPattern::try_from)
+                .map_err(|err| Syntax::error(&err, &p_span))?,
+            self.walk(e)?
+        ))
+    }
+
+    /// Desugars a for loop.
+    /// Like an assignment, this converts the loop's `ASTPattern` into a `CSTPattern`.
+    pub fn for_loop(
+        &mut self,
+        p: Spanned<ASTPattern>,
+        i: Spanned<AST>,
+        b: Spanned<AST>,
+    ) -> Result<CST, Syntax> {
+        let p_span = p.span.clone();
+
+        Ok(CST::for_loop(
+            p.map(CSTPattern::try_from)
+                .map_err(|err| Syntax::error(&err, &p_span))?,
+            self.walk(i)?,
+            self.walk(b)?,
+        ))
+    }
+
+    /// Desugars local bindings into nested function calls,
+    /// so that each binding is only in scope for what follows it:
+    /// ```ignore
+    /// e where x = 1, y = 2
+    /// -- becomes
+    /// (x -> (y -> e) 2) 1
+    /// ```
+    /// Assigning to a name in a block updates any variable already in scope,
+    /// whereas function parameters shadow it.
+    pub fn where_clause(
+        &mut self,
+        e: Spanned<AST>,
+        b: Vec<(Spanned<ASTPattern>, Spanned<AST>)>,
+    ) -> Result<CST, Syntax> {
+        let mut body = self.walk(e)?;
+        let mut bindings = vec![];
+        for (p, v) in b {
+            let p_span = p.span.clone();
+            let pattern = p.map(CSTPattern::try_from)
+                .map_err(|err| Syntax::error(&err, &p_span))?;
+            bindings.push((pattern, self.walk(v)?));
+        }
+
+        for (pattern, value) in bindings.into_iter().rev() {
+            let span = Span::combine(&pattern.span, &body.span);
+            let function = Spanned::new(CST::lambda(pattern, body), span);
+            let span = Span::combine(&function.span, &value.span);
+            body = Spanned::new(CST::call(function, value), span);
+        }
+
+        Ok(body.item)
+    }
+
+    /// Desugars a list comprehension into a `map`, and a `filter` if there is one,
+    /// both of which are run by the VM:
+    /// ```ignore
+    /// [e for p in xs if c]
+    /// -- becomes
+    /// magic "map" (p -> e, magic "filter" (p -> c, xs))
+    /// ```
+    /// The result is always a list, whatever is iterated over.
+    pub fn comprehension(
+        &mut self,
+        e: Spanned<AST>,
+        p: Spanned<ASTPattern>,
+        i: Spanned<AST>,
+        f: Option<Spanned<AST>>,
+    ) -> Result<CST, Syntax> {
+        let iterable = match f {
+            Some(c) => {
+                let span = Span::combine(&p.span, &c.span);
+                let test = Spanned::new(AST::lambda(p.clone(), c), span.clone());
+                let span = Span::combine(&span, &i.span);
+                let arguments = Spanned::new(AST::Tuple(vec![test, i]), span.clone());
+                Spanned::new(AST::ffi("filter", arguments), span)
+            },
+            None => i,
+        };
+
+        let span = Span::combine(&p.span, &e.span);
+        let function = Spanned::new(AST::lambda(p, e), span.clone());
+        let span = Span::combine(&span, &iterable.span);
+        let arguments = Spanned::new(AST::Tuple(vec![function, iterable]), span);
+        self.ffi("map".to_string(), arguments)
+    }
+
     /// Desugars a lambda
     /// This converts both patterns and expressions;
     /// On top of this, it desugars `a b c -> d`
@@ -0,0 +1,394 @@
+use crate::common::{
+    data::Data,
+    span::Spanned,
+};
+
+use crate::compiler::{
+    parse::Prec,
+    ast::{AST, ASTPattern, ArgPattern, Fragment},
+};
+
+/// The number of spaces each level of a block is indented by.
+const INDENT: &str = "    ";
+
+/// Binary operators, which are parsed into FFI calls,
+/// paired with the FFI name they are parsed into.
+const OPERATORS: &[(&str, &str, Prec)] = &[
+    ("add",         "+",  Prec::AddSub),
+    ("sub",         "-",  Prec::AddSub),
+    ("mul",         "*",  Prec::MulDiv),
+    ("div",         "/",  Prec::MulDiv),
+    ("rem",         "%",  Prec::MulDiv),
+    ("pow",         "**", Prec::Pow),
+    ("and",         "and", Prec::And),
+    ("or",          "or",  Prec::Or),
+    ("equal",       "==", Prec::Logic),
+    ("not_equal",   "!=", Prec::Logic),
+    ("contains",    "in", Prec::Logic),
+    ("bit_and",     "&",  Prec::BitAnd),
+    ("bit_or",      "|",  Prec::BitOr),
+    ("bit_xor",     "^^", Prec::BitXor),
+    ("shift_left",  "<<", Prec::Shift),
+    ("shift_right", ">>", Prec::Shift),
+];
+
+/// Formats an `AST` as canonical Passerine source,
+/// i.e. with consistent spacing, and blocks indented by four spaces.
+/// The `AST` passed in is treated as a whole program,
+/// so the outermost block is not wrapped in curly braces.
+/// Formatting the parsed output of already-formatted source
+/// returns that source unchanged.
+pub fn format_ast(ast: &AST) -> String {
+    match ast {
+        AST::Block(b) if b.is_empty() => "".to_string(),
+        AST::Block(b) => b.iter()
+            .map(|e| format!("{}\n", expression(&e.item)))
+            .collect(),
+        other => format!("{}\n", expression(other)),
+    }
+}
+
+/// Looks up the operator an FFI call was parsed from, if any.
+/// Only calls with a bare (i.e. ungrouped) pair of arguments were parsed from operators,
+/// `magic "add" (a, b)` is left as is.
+fn operator<'a>(name: &str, argument: &'a AST) -> Option<(&'static str, Prec, &'a AST, &'a AST)> {
+    let (_, symbol, prec) = OPERATORS.iter().find(|(n, _, _)| *n == name)?;
+
+    match argument {
+        AST::Tuple(t) if t.len() == 2 => Some((symbol, *prec, &t[0].item, &t[1].item)),
+        _ => None,
+    }
+}
+
+/// Returns the precedence an expression would be parsed at,
+/// used to determine whether it must be wrapped in parenthesis.
+fn prec(ast: &AST) -> Prec {
+    match ast {
+          AST::Assign { .. }
+        | AST::Const { .. }
+        | AST::Mutate { .. }    => Prec::Assign,
+        AST::Where { .. }       => Prec::Where,
+        AST::Return(_)          => Prec::Lambda,
+        AST::Keyword { .. }     => Prec::Named,
+        AST::Tuple(_)           => Prec::Pair,
+        AST::Lambda { pattern, .. } if !pattern.span.is_empty() => Prec::Lambda,
+        AST::Range { .. }       => Prec::Range,
+        AST::Composition { .. } => Prec::Compose,
+          AST::Form(_)
+        | AST::For { .. }
+        | AST::Syntax { .. }    => Prec::Call,
+        AST::FFI { name, expression } => match operator(name, &expression.item) {
+            Some((_, prec, _, _)) => prec,
+            None => Prec::Index,
+        },
+          AST::Index { .. }
+        | AST::Label(_, _)
+        | AST::Try(_)
+        | AST::Propagate(_)     => Prec::Index,
+        _                       => Prec::End,
+    }
+}
+
+/// Formats an expression, wrapping it in parenthesis
+/// if it would not be parsed at the required precedence otherwise.
+fn operand(ast: &AST, required: Prec) -> String {
+    if prec(ast) < required {
+        format!("({})", expression(ast))
+    } else {
+        expression(ast)
+    }
+}
+
+/// Formats a list of expressions, separated by commas.
+fn items(items: &[Spanned<AST>]) -> String {
+    items.iter()
+        .map(|i| operand(&i.item, Prec::Pair.associate_left()))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Formats a single expression.
+fn expression(ast: &AST) -> String {
+    match ast {
+        AST::Symbol(s)     => s.clone(),
+        AST::Data(d)       => literal(d),
+        AST::Block(b)      => block(b),
+        AST::Form(f)       => {
+            let mut formatted = vec![operand(&f[0].item, Prec::Call)];
+            for argument in f[1..].iter() {
+                formatted.push(operand(&argument.item, Prec::Call.associate_left()));
+            }
+            formatted.join(" ")
+        },
+        AST::Group(e)      => format!("({})", expression(&e.item)),
+        AST::CSTPattern(p) => pattern(p),
+        AST::ArgPattern(a) => arg_pat(a),
+        AST::Tuple(t) if t.len() == 1 => format!("{},", items(t)),
+        AST::Tuple(t)      => items(t),
+        AST::Keyword { name, value } => format!(
+            "{}: {}", name, operand(&value.item, Prec::Named.associate_left()),
+        ),
+        AST::List(l)       => format!("[{}]", items(l)),
+        AST::Assign { pattern: p, expression: e } => format!(
+            "{} = {}", pattern(&p.item), operand(&e.item, Prec::Assign),
+        ),
+        AST::Const { pattern: p, expression: e } => format!(
+            "const {} = {}", pattern(&p.item), operand(&e.item, Prec::Assign),
+        ),
+        // compound assignments are parsed into an update with an operator
+        AST::Mutate { pattern: p, expression: e } => match &e.item {
+            AST::FFI { name, expression: a } => match operator(name, &a.item) {
+                Some((symbol, _, _, right)) => format!(
+                    "{} {}= {}", pattern(&p.item), symbol, operand(right, Prec::Assign),
+                ),
+                None => unreachable!("Expected an update to apply an operator"),
+            },
+            _ => unreachable!("Expected an update to apply an operator"),
+        },
+        // builtins like `println` are parsed into lambdas that wrap an FFI call
+        AST::Lambda { pattern: p, expression: e } if p.span.is_empty() => match &e.item {
+            AST::FFI { name, .. } => name.clone(),
+            _ => unreachable!("Expected a builtin to wrap an FFI call"),
+        },
+        AST::Lambda { pattern: p, expression: e } => format!(
+            "{} -> {}", pattern(&p.item), operand(&e.item, Prec::Lambda),
+        ),
+        AST::Composition { argument, function } => format!(
+            "{} . {}",
+            operand(&argument.item, Prec::Compose),
+            operand(&function.item, Prec::Compose.associate_left()),
+        ),
+        AST::Label(name, e) => format!("{} {}", name, operand(&e.item, Prec::End)),
+        AST::Try(e)         => format!("try {}", operand(&e.item, Prec::End)),
+        AST::Return(e)      => format!("return {}", operand(&e.item, Prec::Pair)),
+        // `x?` would be read back as a single symbol
+        AST::Propagate(e)   => match e.item {
+            AST::Symbol(_) => format!("{} ?", operand(&e.item, Prec::Index)),
+            _              => format!("{}?",  operand(&e.item, Prec::Index)),
+        },
+        AST::Range { start, end } => format!(
+            "{}..{}",
+            operand(&start.item, Prec::Range),
+            operand(&end.item, Prec::Range.associate_left()),
+        ),
+        AST::Index { expression: e, index } => format!(
+            "{}[{}]", operand(&e.item, Prec::Index), expression(&index.item),
+        ),
+        AST::For { pattern: p, iterable, body } => format!(
+            "for {} in {} {}",
+            pattern(&p.item),
+            operand(&iterable.item, Prec::Pair),
+            expression(&body.item),
+        ),
+        AST::Where { expression: e, bindings } => format!(
+            "{} where {}",
+            operand(&e.item, Prec::Where),
+            bindings.iter()
+                .map(|(p, v)| format!("{} = {}", pattern(&p.item), operand(&v.item, Prec::Pair.associate_left())))
+                .collect::<Vec<String>>()
+                .join(", "),
+        ),
+        AST::Comprehension { element, pattern: p, iterable, filter } => format!(
+            "[{} for {} in {}{}]",
+            operand(&element.item, Prec::Pair.associate_left()),
+            pattern(&p.item),
+            operand(&iterable.item, Prec::Pair.associate_left()),
+            match filter {
+                Some(f) => format!(" if {}", operand(&f.item, Prec::Pair.associate_left())),
+                None    => "".to_string(),
+            },
+        ),
+        AST::Syntax { arg_pat: a, expression: e } => format!(
+            "syntax {} {}", arg_pat(&a.item), expression(&e.item),
+        ),
+        AST::FFI { name, expression: e } => ffi(name, &e.item),
+        AST::Import(path) => format!("import {}", literal(&Data::String(path.clone()))),
+        AST::Interpolation(fragments) => interpolation(fragments),
+    }
+}
+
+/// Formats an interpolated string,
+/// with each embedded expression between `${` and `}`.
+fn interpolation(fragments: &[Fragment]) -> String {
+    let mut formatted = "\"".to_string();
+    for fragment in fragments {
+        match fragment {
+            Fragment::Text(text) => formatted.push_str(&escape(text)),
+            Fragment::Expression(e) => formatted.push_str(&format!("${{{}}}", expression(&e.item))),
+        }
+    }
+    formatted.push('"');
+    formatted
+}
+
+/// Formats an FFI call, using the operator it was parsed from if possible.
+fn ffi(name: &str, argument: &AST) -> String {
+    if let Some((symbol, prec, left, right)) = operator(name, argument) {
+        // `**` associates right, everything else associates left
+        let (left_prec, right_prec) = if prec == Prec::Pow {
+            (prec.associate_left(), prec)
+        } else {
+            (prec, prec.associate_left())
+        };
+
+        return format!("{} {} {}", operand(left, left_prec), symbol, operand(right, right_prec));
+    }
+
+    if name == "neg" {
+        return format!("-{}", operand(argument, Prec::End));
+    }
+
+    format!("magic {} {}", literal(&Data::String(name.to_string())), operand(argument, Prec::End))
+}
+
+/// Formats a block, indenting each expression inside of it.
+fn block(expressions: &[Spanned<AST>]) -> String {
+    if expressions.is_empty() { return "{}".to_string(); }
+
+    let body = expressions.iter()
+        .map(|e| expression(&e.item)
+            .lines()
+            .map(|l| if l.is_empty() { l.to_string() } else { format!("{}{}", INDENT, l) })
+            .collect::<Vec<String>>()
+            .join("\n")
+        )
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    format!("{{\n{}\n}}", body)
+}
+
+/// Formats a literal so that it is lexed back into the same `Data`.
+fn literal(data: &Data) -> String {
+    match data {
+        Data::Real(n) => format!("{:?}", n),
+        Data::String(s) => format!("\"{}\"", escape(s)),
+        other => format!("{}", other),
+    }
+}
+
+/// Escapes the text of a string literal,
+/// including `${`, so it is not lexed as an embedded expression.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"'  => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '{' if escaped.ends_with('$') => escaped.push_str("${"),
+            c    => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Formats a pattern, such as the left side of an assignment.
+fn pattern(p: &ASTPattern) -> String {
+    match p {
+        ASTPattern::Symbol(s)   => s.clone(),
+        ASTPattern::Data(d)     => literal(d),
+        ASTPattern::Chain(c)    => c.iter()
+            .map(|p| nested_pattern(&p.item))
+            .collect::<Vec<String>>()
+            .join(" "),
+        ASTPattern::Label(n, p) => format!("{} {}", n, nested_pattern(&p.item)),
+        ASTPattern::Tuple(t)    => format!("({})", t.iter()
+            .map(|p| pattern(&p.item))
+            .collect::<Vec<String>>()
+            .join(", ")
+        ),
+    }
+}
+
+/// Formats a pattern nested inside of another,
+/// which must be wrapped in parenthesis if it is made up of more than one part.
+fn nested_pattern(nested: &ASTPattern) -> String {
+    match nested {
+          ASTPattern::Chain(_)
+        | ASTPattern::Label(_, _) => format!("({})", pattern(nested)),
+        _ => pattern(nested),
+    }
+}
+
+/// Formats the argument pattern of a macro.
+fn arg_pat(a: &ArgPattern) -> String {
+    match a {
+        ArgPattern::Keyword(k) => format!("'{}", k),
+        ArgPattern::Symbol(s)  => s.clone(),
+        ArgPattern::Group(g)   => g.iter()
+            .map(|a| match &a.item {
+                ArgPattern::Group(_) => format!("({})", arg_pat(&a.item)),
+                other => arg_pat(other),
+            })
+            .collect::<Vec<String>>()
+            .join(" "),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::source::Source;
+    use crate::compiler::{lex::lex, parse::parse};
+
+    fn format_source(source: &str) -> String {
+        let ast = parse(lex(Source::source(source)).unwrap()).unwrap();
+        format_ast(&ast.item)
+    }
+
+    #[test]
+    fn operator_spacing() {
+        assert_eq!(format_source("x=1+2*3"), "x = 1 + 2 * 3\n");
+        assert_eq!(format_source("(1+2)*3==9"), "(1 + 2) * 3 == 9\n");
+        assert_eq!(format_source("f=(a,b)->a-b"), "f = (a, b) -> a - b\n");
+        assert_eq!(format_source("magic \"add\" (1,2)"), "magic \"add\" (1, 2)\n");
+        assert_eq!(format_source("const pi=3.14"), "const pi = 3.14\n");
+        assert_eq!(format_source("a&b|c^^d<<1"), "a & b | c ^^ d << 1\n");
+        assert_eq!(format_source("(x in xs)==true"), "(x in xs) == true\n");
+        assert_eq!(format_source("[x*2 for (x,y) in xs if x!=y]"), "[x * 2 for (x, y) in xs if x != y]\n");
+        assert_eq!(format_source("z=x+y where x=1,y=(2,3)"), "z = x + y where x = 1, y = (2, 3)\n");
+        assert_eq!(format_source("y=(f x)?+x ?"), "y = (f x)? + x ?\n");
+        assert_eq!(format_source("f=x->return x+1"), "f = x -> return x + 1\n");
+        assert_eq!(format_source("(a or b and c==d)and e"), "(a or b and c == d) and e\n");
+        assert_eq!(format_source("magic \"f\" (1,x:2,y:(a,b))"), "magic \"f\" (1, x: 2, y: (a, b))\n");
+        assert_eq!(format_source("x=1;x*=2+3"), "x = 1\nx *= 2 + 3\n");
+    }
+
+    #[test]
+    fn block_indentation() {
+        let source = "f = x -> {\ny = x\n{ println y; z = () }\n}\nf [1,2]";
+        assert_eq!(format_source(source), "\
+            f = x -> {\n    \
+                y = x\n    \
+                {\n        \
+                    println y\n        \
+                    z = ()\n    \
+                }\n\
+            }\n\
+            f [1, 2]\n\
+        ");
+    }
+
+    #[test]
+    fn interpolation() {
+        assert_eq!(format_source("\"x is ${x+1}!\""), "\"x is ${x + 1}!\"\n");
+        assert_eq!(format_source("\"${ {y=x;y} }\""), "\"${{\n    y = x\n    y\n}}\"\n");
+        assert_eq!(format_source("\"$${x} costs $5\""), "\"$${x} costs $5\"\n");
+    }
+
+    #[test]
+    fn idempotent() {
+        let source = "\
+            syntax 'swap a b { (b, a) }\n\
+            fib = n -> magic \"if\" (n  ==  0, n, fib (n - 1) + fib (n - 2))\n\
+            for x in 0..10 { print (Some x . to_string) }\n\
+            a ** b ** c; b = -(a) ; xs[1]; try (1 / 0)\n\
+            \"a \\\"quoted\\\" string\"; 1.0; \"${a} and $${b}\"\n\
+        ";
+        let formatted = format_source(source);
+        assert_eq!(format_source(&formatted), formatted);
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::{HashMap, HashSet},
     mem,
     rc::Rc,
 };
@@ -17,7 +18,7 @@ use crate::common::{
 use crate::compiler::{
     sst::{UniqueSymbol, Scope, SST, SSTPattern},
     // TODO: pattern for where?
-    syntax::Syntax,
+    syntax::{GenError, Warning},
 };
 
 use crate::core::{
@@ -29,7 +30,12 @@ use crate::core::{
 
 /// Simple function that generates unoptimized bytecode from an `SST`.
 /// Exposes the functionality of the `Compiler`.
-pub fn gen(sst: (Spanned<SST>, Scope)) -> Result<Rc<Lambda>, Syntax> {
+/// Errors found while generating bytecode,
+/// such as calling an FFI function that does not exist,
+/// are reported as a `GenError` pointing at where they were found in the source.
+/// To chain `gen` after the other compiler passes,
+/// convert it into a `Syntax` error, e.g. `.and_then(|sst| Ok(gen(sst)?))`.
+pub fn gen(sst: (Spanned<SST>, Scope)) -> Result<Rc<Lambda>, GenError> {
     gen_with_ffi(sst, ffi_core())
 }
 
@@ -37,12 +43,41 @@ pub fn gen(sst: (Spanned<SST>, Scope)) -> Result<Rc<Lambda>, Syntax> {
 /// Given a specific FFI. Note that this doesn't even assume the core ffi,
 /// So it's required you generate a core ffi with `core::ffi_core()`,
 /// Then merge it with your ffi with `FFI::combine(...)`.
-pub fn gen_with_ffi(sst: (Spanned<SST>, Scope), ffi: FFI) -> Result<Rc<Lambda>, Syntax> {
+pub fn gen_with_ffi(sst: (Spanned<SST>, Scope), ffi: FFI) -> Result<Rc<Lambda>, GenError> {
     let mut compiler = Compiler::base(ffi, sst.1);
     compiler.walk(&sst.0)?;
     Ok(Rc::new(compiler.lambda))
 }
 
+/// Generates unoptimized bytecode from a `SST`, like `gen`,
+/// but variables in the root scope are stored as global variables, by name,
+/// rather than on the stack, so they outlive the run, see `VM::with_globals`.
+/// Functions use global variables directly, instead of capturing them.
+/// To use the globals of a previous run, hoist with `hoist::hoist_with_globals`.
+pub fn gen_with_globals(sst: (Spanned<SST>, Scope)) -> Result<Rc<Lambda>, GenError> {
+    let (sst, mut scope) = sst;
+    let globals = scope.locals.drain(..)
+        .zip(scope.names.drain(..))
+        .map(|(unique_symbol, local)| (unique_symbol, local.name().to_string()))
+        .collect();
+
+    let mut compiler = Compiler::base(ffi_core(), scope);
+    compiler.globals = globals;
+    compiler.walk(&sst)?;
+    Ok(Rc::new(compiler.lambda))
+}
+
+/// Generates unoptimized bytecode from a `SST`, like `gen`,
+/// but also returns any `Warning`s found along the way,
+/// such as variables that are assigned to but never read,
+/// so they can be shown to the user.
+pub fn gen_with_warnings(sst: (Spanned<SST>, Scope)) -> Result<(Rc<Lambda>, Vec<Warning>), GenError> {
+    let mut compiler = Compiler::base(ffi_core(), sst.1);
+    compiler.walk(&sst.0)?;
+    compiler.warn_unused();
+    Ok((Rc::new(compiler.lambda), compiler.warnings))
+}
+
 /// Compiler is a bytecode generator that walks an SST and produces (unoptimized) Bytecode.
 /// There are plans to add a bytecode optimizer in the future.
 /// Note that this struct should not be controlled manually,
@@ -58,6 +93,14 @@ pub struct Compiler {
     ffi: FFI,
     /// The FFI functions that have been bound in this scope.
     ffi_names: Vec<String>,
+    /// The locals that have been assigned to so far in this scope.
+    declared: Vec<UniqueSymbol>,
+    /// Every variable that has been read so far, in this or any other scope.
+    reads: HashSet<UniqueSymbol>,
+    /// Warnings found so far, in this or any other scope.
+    warnings: Vec<Warning>,
+    /// Variables stored as globals, by name, see `gen_with_globals`.
+    globals: HashMap<UniqueSymbol, String>,
     // determined in hoisting
     scope: Scope,
 }
@@ -65,44 +108,103 @@ pub struct Compiler {
 impl Compiler {
     /// Construct a new `Compiler`.
     pub fn base(ffi: FFI, scope: Scope) -> Compiler {
+        let mut lambda = Lambda::empty();
+        lambda.locals = scope.names.clone();
+
         Compiler {
             enclosing: None,
-            lambda:    Lambda::empty(),
+            lambda,
             ffi,
             ffi_names: vec![],
+            declared:  vec![],
+            reads:     HashSet::new(),
+            warnings:  vec![],
+            globals:   HashMap::new(),
             scope,
         }
     }
 
     /// Replace the current compiler with a fresh one,
     /// keeping a reference to the old one in `self.enclosing`,
-    /// and moving the FFI into the current compiler.
+    /// and moving the FFI (and warnings) into the current compiler.
     pub fn enter_scope(&mut self, scope: Scope) {
-        let ffi        = mem::replace(&mut self.ffi, FFI::new());
-        let nested     = Compiler::base(ffi, scope);
+        let ffi        = mem::take(&mut self.ffi);
+        let reads      = mem::take(&mut self.reads);
+        let warnings   = mem::take(&mut self.warnings);
+        let globals    = mem::take(&mut self.globals);
+        let mut nested = Compiler::base(ffi, scope);
+        nested.reads    = reads;
+        nested.warnings = warnings;
+        nested.globals  = globals;
         let enclosing  = mem::replace(self, nested);
         self.enclosing = Some(Box::new(enclosing));
     }
 
     /// Restore the enclosing compiler,
     /// returning the nested one for data (Lambda) extraction,
-    /// and moving the FFI mappings back into the enclosing compiler.
+    /// and moving the FFI mappings (and warnings) back into the enclosing compiler.
     pub fn exit_scope(&mut self) -> Compiler {
-        let ffi       = mem::replace(&mut self.ffi, FFI::new());
-        let enclosing = mem::replace(&mut self.enclosing, None);
+        let ffi       = mem::take(&mut self.ffi);
+        let reads     = mem::take(&mut self.reads);
+        let warnings  = mem::take(&mut self.warnings);
+        let globals   = mem::take(&mut self.globals);
+        let enclosing = self.enclosing.take();
         let nested = match enclosing {
             Some(compiler) => mem::replace(self, *compiler),
             None => unreachable!("Can not go back past root copiler"),
         };
-        self.ffi = ffi;
+        self.ffi      = ffi;
+        self.reads    = reads;
+        self.warnings = warnings;
+        self.globals  = globals;
         nested
     }
 
+    /// Warns about each local in this scope
+    /// that has the same name as a local of an enclosing scope,
+    /// pointing at both the local and the one it shadows.
+    /// Assigning to a name that is already in scope updates that variable,
+    /// even in a block, so only function parameters can shadow variables.
+    pub fn warn_shadowed(&mut self) {
+        for local in self.scope.names.iter() {
+            if local.name().starts_with('_') { continue; }
+
+            let mut enclosing = self.enclosing.as_deref();
+            while let Some(compiler) = enclosing {
+                if let Some(outer) = compiler.scope.names.iter().find(|o| o.name() == local.name()) {
+                    self.warnings.push(Warning::related(
+                        &format!("The variable '{}' shadows a variable in an enclosing function", local.name()),
+                        local.span(),
+                        outer.span(),
+                    ));
+                    break;
+                }
+                enclosing = compiler.enclosing.as_deref();
+            }
+        }
+    }
+
+    /// Warns about each local in this scope that is never read.
+    /// A local can only be read in the scope it's declared in, or a nested one,
+    /// so this must be called once the whole scope has been walked.
+    /// Variables starting with an underscore are meant to be unused,
+    /// so they are never warned about.
+    pub fn warn_unused(&mut self) {
+        for (local, unique_symbol) in self.scope.names.iter().zip(self.scope.locals.iter()) {
+            if !self.reads.contains(unique_symbol) && !local.name().starts_with('_') {
+                self.warnings.push(Warning::new(
+                    &format!("The variable '{}' is never used", local.name()),
+                    local.span(),
+                ));
+            }
+        }
+    }
+
     /// Walks an SST to generate bytecode.
     /// At this stage, the SST should've been verified, pruned, typechecked, etc.
     /// A malformed SST will cause a panic, as SSTs should be correct at this stage,
     /// and for them to be incorrect is an error in the compiler itself.
-    pub fn walk(&mut self, sst: &Spanned<SST>) -> Result<(), Syntax> {
+    pub fn walk(&mut self, sst: &Spanned<SST>) -> Result<(), GenError> {
         // TODO: move this to a better spot
         self.lambda.decls = self.scope.locals.len();
 
@@ -112,20 +214,28 @@ impl Compiler {
         // push left, push right, push center
         match sst.item.clone() {
             SST::Data(data) => {
-                self.data(data);
+                self.literal(data, &sst.span);
                 Ok(())
             },
             SST::Symbol(unique) => {
                 self.symbol(unique);
                 Ok(())
             },
-            SST::Block(block) => self.block(block),
+            SST::Block { expressions, locals } => self.block(expressions, locals),
             SST::Label(name, expression) => self.label(name, *expression),
+            SST::Try(expression) => self.try_catch(*expression, sst.span.clone()),
+            SST::Propagate(expression) => self.propagate(*expression, sst.span.clone()),
+            SST::Return(expression) => self.return_early(*expression, sst.span.clone()),
             SST::Tuple(tuple) => self.tuple(tuple),
-            SST::FFI    { name,    expression } => self.ffi(name, *expression, sst.span.clone()),
-            SST::Assign { pattern, expression } => self.assign(*pattern, *expression),
+            SST::List(list) => self.list(list),
+            SST::FFI    { name, expression, keywords } => self.ffi(name, *expression, keywords, sst.span.clone()),
+            SST::Assign { pattern, expression } => self.assign(*pattern, *expression, true),
+            SST::Mutate { pattern, expression } => self.mutate(*pattern, *expression, true),
             SST::Lambda { pattern, expression, scope } => self.lambda(*pattern, *expression, scope),
             SST::Call   { fun,     arg        } => self.call(*fun, *arg),
+            SST::For    { pattern, iterable, body } => self.for_loop(*pattern, *iterable, *body),
+            SST::Range  { start,   end        } => self.range(*start, *end, sst.span.clone()),
+            SST::Index  { expression, index   } => self.index(*expression, *index, sst.span.clone()),
         }
     }
 
@@ -134,7 +244,11 @@ impl Compiler {
 
     /// Resovles a symbol lookup, e.g. something like `x`.
     pub fn symbol(&mut self, unique_symbol: UniqueSymbol) {
-        let index = if let Some(i) = self.scope.local_index(unique_symbol) {
+        self.reads.insert(unique_symbol);
+        let index = if let Some(name) = self.globals.get(&unique_symbol) {
+            self.lambda.emit(Opcode::GetGlobal);
+            self.lambda.index_data(Data::String(name.clone()))
+        } else if let Some(i) = self.scope.local_index(unique_symbol) {
             self.lambda.emit(Opcode::Load); i
         } else if let Some(i) = self.scope.nonlocal_index(unique_symbol) {
             self.lambda.emit(Opcode::LoadCap); i
@@ -148,34 +262,89 @@ impl Compiler {
 
     /// Takes a `Data` leaf and and produces some code to load the constant
     pub fn data(&mut self, data: Data) {
-        self.lambda.emit(Opcode::Con);
-        let mut split = split_number(self.lambda.index_data(data));
-        self.lambda.emit_bytes(&mut split);
+        // common constants have their own opcodes
+        match data {
+            Data::Unit           => return self.lambda.emit(Opcode::LoadUnit),
+            Data::Boolean(true)  => return self.lambda.emit(Opcode::LoadTrue),
+            Data::Boolean(false) => return self.lambda.emit(Opcode::LoadFalse),
+            _ => (),
+        }
+
+        let index = self.lambda.index_data(data);
+        self.lambda.emit_constant(index);
+    }
+
+    /// Like `data`, but for a literal in source,
+    /// so the span of the literal is recorded alongside the constant.
+    pub fn literal(&mut self, data: Data, span: &Span) {
+        match data {
+            Data::Unit | Data::Boolean(_) => self.data(data),
+            _ => {
+                let index = self.lambda.index_spanned_data(data, span);
+                self.lambda.emit_constant(index);
+            },
+        }
     }
 
     /// A block is a series of expressions where the last is returned.
     /// Each sup-expression is walked, the last value is left on the stack.
-    pub fn block(&mut self, children: Vec<Spanned<SST>>) -> Result<(), Syntax> {
+    /// The locals of the block are then dropped with `DelLocals`,
+    /// as they are no longer in scope; they always take up a contiguous run of slots.
+    /// The locals of a function body are instead dropped all at once by `Return`.
+    pub fn block(&mut self, children: Vec<Spanned<SST>>, locals: Vec<UniqueSymbol>) -> Result<(), GenError> {
         if children.is_empty() {
             self.data(Data::Unit);
             return Ok(());
         }
 
-        for child in children {
-            self.walk(&child)?;
-            self.lambda.emit(Opcode::Del);
+        let last = children.len() - 1;
+        for (index, child) in children.into_iter().enumerate() {
+            if index == last {
+                self.walk(&child)?;
+            } else {
+                self.statement(&child)?;
+            }
+        }
+
+        // in a REPL, the locals of the root scope are globals instead
+        if let Some(first) = locals.first().and_then(|local| self.scope.local_index(*local)) {
+            self.lambda.emit(Opcode::DelLocals);
+            self.lambda.emit_bytes(&mut split_number(first));
+            self.lambda.emit_bytes(&mut split_number(locals.len()));
         }
 
-        // remove the last delete instruction
-        self.lambda.demit();
         Ok(())
     }
 
+    /// Generates an expression in a block whose value is not used.
+    /// Usually the value is deleted after it is computed,
+    /// but the value of an assignment is never pushed in the first place,
+    /// so a sequence of assignments doesn't copy and delete each assigned value.
+    pub fn statement(&mut self, sst: &Spanned<SST>) -> Result<(), GenError> {
+        match sst.item.clone() {
+            SST::Assign { pattern, expression } => {
+                self.lambda.decls = self.scope.locals.len();
+                self.lambda.emit_span(&sst.span);
+                self.assign(*pattern, *expression, false)
+            },
+            SST::Mutate { pattern, expression } => {
+                self.lambda.decls = self.scope.locals.len();
+                self.lambda.emit_span(&sst.span);
+                self.mutate(*pattern, *expression, false)
+            },
+            _ => {
+                self.walk(sst)?;
+                self.lambda.emit(Opcode::Del);
+                Ok(())
+            },
+        }
+    }
+
     /// Generates a print expression
     /// Note that currently printing is a baked-in language feature,
     /// but the second the FFI becomes a thing
     /// it'll no longer be one.
-    pub fn print(&mut self, expression: Spanned<SST>) -> Result<(), Syntax> {
+    pub fn print(&mut self, expression: Spanned<SST>) -> Result<(), GenError> {
         self.walk(&expression)?;
         self.lambda.emit(Opcode::Print);
         Ok(())
@@ -183,7 +352,7 @@ impl Compiler {
 
     /// Generates a Label construction
     /// that loads the variant, then wraps some data
-    pub fn label(&mut self, name: String, expression: Spanned<SST>) -> Result<(), Syntax> {
+    pub fn label(&mut self, name: String, expression: Spanned<SST>) -> Result<(), GenError> {
         self.walk(&expression)?;
         self.data(Data::Kind(name));
         self.lambda.emit(Opcode::Label);
@@ -193,7 +362,7 @@ impl Compiler {
     /// Generates a Tuple construction
     /// that loads all fields in the tuple
     /// then rips them off the stack into a vec.
-    pub fn tuple(&mut self, tuple: Vec<Spanned<SST>>) -> Result<(), Syntax> {
+    pub fn tuple(&mut self, tuple: Vec<Spanned<SST>>) -> Result<(), GenError> {
         let length = tuple.len();
 
         for item in tuple.into_iter() {
@@ -205,14 +374,96 @@ impl Compiler {
         Ok(())
     }
 
+    /// Generates a List construction,
+    /// which works the same way as a Tuple construction.
+    pub fn list(&mut self, list: Vec<Spanned<SST>>) -> Result<(), GenError> {
+        let length = list.len();
+
+        for item in list.into_iter() {
+            self.walk(&item)?;
+        }
+
+        self.lambda.emit(Opcode::List);
+        self.lambda.emit_bytes(&mut split_number(length));
+        Ok(())
+    }
+
+    /// Generates a Range construction,
+    /// loading the start and end of the range, then combining them.
+    pub fn range(&mut self, start: Spanned<SST>, end: Spanned<SST>, span: Span) -> Result<(), GenError> {
+        self.walk(&start)?;
+        self.walk(&end)?;
+
+        self.lambda.emit_span(&span);
+        self.lambda.emit(Opcode::BuildRange);
+        Ok(())
+    }
+
+    /// Generates an index into a value, i.e. `xs[i]`,
+    /// loading the value and the index, then indexing.
+    pub fn index(&mut self, expression: Spanned<SST>, index: Spanned<SST>, span: Span) -> Result<(), GenError> {
+        self.walk(&expression)?;
+        self.walk(&index)?;
+
+        self.lambda.emit_span(&span);
+        self.lambda.emit(Opcode::Index);
+        Ok(())
+    }
+
     // TODO: make a macro to map Passerine's data model to Rust's
     /// Makes a Rust function callable from Passerine,
     /// by keeping a reference to that function.
-    pub fn ffi(&mut self, name: String, expression: Spanned<SST>, span: Span) -> Result<(), Syntax> {
+    /// Note that some magic is handled by the VM directly rather than through FFI:
+    /// `magic "error"` raises a user error with the value passed to it,
+    /// `magic "map"`, `"filter"`, and `"fold"` call closures over a list,
+    /// `magic "equal"` and `"not_equal"` compare values,
+    /// as how strictly they do so is configured on the VM,
+    /// and `magic "random"` and `"random_int"` use the VM's seedable source of random numbers.
+    /// `magic "and"` and `"or"` short-circuit, see `Compiler::logic`.
+    /// None of these take keyword arguments.
+    /// Keyword arguments are collected into a tuple of name-value pairs,
+    /// which is passed to the FFI function separately, with `FFICallKeywords`.
+    pub fn ffi(
+        &mut self,
+        name: String,
+        expression: Spanned<SST>,
+        keywords: Vec<(String, Spanned<SST>)>,
+        span: Span,
+    ) -> Result<(), GenError> {
+        if let ("and" | "or", SST::Tuple(pair)) = (name.as_str(), &expression.item) {
+            if let [left, right] = pair.as_slice() {
+                if !keywords.is_empty() {
+                    return Err(GenError::UnexpectedKeywords { name, span });
+                }
+                return self.logic(name == "and", left, right, span);
+            }
+        }
+
         self.walk(&expression)?;
 
+        let opcode = match name.as_str() {
+            "error"  => Some(Opcode::Raise),
+            "map"    => Some(Opcode::Map),
+            "filter" => Some(Opcode::Filter),
+            "fold"   => Some(Opcode::Fold),
+            "equal"     => Some(Opcode::Equal),
+            "not_equal" => Some(Opcode::NotEqual),
+            "random"     => Some(Opcode::Random),
+            "random_int" => Some(Opcode::RandomInt),
+            _        => None,
+        };
+
+        if let Some(opcode) = opcode {
+            if !keywords.is_empty() {
+                return Err(GenError::UnexpectedKeywords { name, span });
+            }
+            self.lambda.emit_span(&span);
+            self.lambda.emit(opcode);
+            return Ok(());
+        }
+
         let function = self.ffi.get(&name)
-            .map_err(|s| Syntax::error(&s, &span))?;
+            .map_err(|_| GenError::UndefinedFFI { name: name.clone(), span: span.clone() })?;
 
         let index = match self.ffi_names.iter().position(|n| n == &name) {
             Some(p) => p,
@@ -230,17 +481,74 @@ impl Compiler {
             },
         };
 
+        if keywords.is_empty() {
+            self.lambda.emit_span(&span);
+            self.lambda.emit(Opcode::FFICall);
+            self.lambda.emit_bytes(&mut split_number(index));
+            return Ok(());
+        }
+
+        let count = keywords.len();
+        for (keyword, value) in keywords {
+            self.data(Data::String(keyword));
+            self.walk(&value)?;
+            self.lambda.emit(Opcode::Tuple);
+            self.lambda.emit_bytes(&mut split_number(2));
+        }
+        self.lambda.emit(Opcode::Tuple);
+        self.lambda.emit_bytes(&mut split_number(count));
+
         self.lambda.emit_span(&span);
-        self.lambda.emit(Opcode::FFICall);
+        self.lambda.emit(Opcode::FFICallKeywords);
         self.lambda.emit_bytes(&mut split_number(index));
         Ok(())
     }
 
-    /// Resolves the assignment of a variable
-    /// returns true if the variable was declared.
+    /// Generates a short-circuiting `and` or `or`.
+    /// The right side is only evaluated if the left side doesn't decide the result;
+    /// otherwise the left side is left on the stack as the result:
+    /// ```plain
+    ///     <left>
+    ///     JumpIfFalseNoPop end (or JumpIfTrueNoPop for or)
+    ///     Del
+    ///     <right>
+    /// end:
+    /// ```
+    pub fn logic(
+        &mut self,
+        and: bool,
+        left: &Spanned<SST>,
+        right: &Spanned<SST>,
+        span: Span,
+    ) -> Result<(), GenError> {
+        self.walk(left)?;
+        self.lambda.emit_span(&span);
+        self.lambda.emit(if and { Opcode::JumpIfFalseNoPop } else { Opcode::JumpIfTrueNoPop });
+        let end = self.lambda.emit_placeholder();
+
+        self.lambda.emit(Opcode::Del);
+        self.walk(right)?;
+
+        let after = self.lambda.code.len();
+        self.lambda.patch(end, after);
+        Ok(())
+    }
+
+    /// Resolves the assignment of a variable.
+    /// The first assignment to a local declares it,
+    /// any assignments that follow mutate it.
     pub fn resolve_assign(&mut self, unique_symbol: UniqueSymbol) {
-        let index = if let Some(i) = self.scope.local_index(unique_symbol) {
-            self.lambda.emit(Opcode::Save); i
+        let index = if let Some(name) = self.globals.get(&unique_symbol) {
+            self.lambda.emit(Opcode::SetGlobal);
+            self.lambda.index_data(Data::String(name.clone()))
+        } else if let Some(i) = self.scope.local_index(unique_symbol) {
+            if self.declared.contains(&unique_symbol) {
+                self.lambda.emit(Opcode::Mutate);
+            } else {
+                self.declared.push(unique_symbol);
+                self.lambda.emit(Opcode::Declare);
+            }
+            i
         } else if let Some(i) = self.scope.nonlocal_index(unique_symbol) {
             self.lambda.emit(Opcode::SaveCap); i
         } else {
@@ -255,7 +563,7 @@ impl Compiler {
     /// a series of unpack and assign instructions.
     /// Instructions match against the topmost stack item.
     /// Does delete the data that is matched against.
-    pub fn destructure(&mut self, pattern: Spanned<SSTPattern>, redeclare: bool) {
+    pub fn destructure(&mut self, pattern: Spanned<SSTPattern>) {
         self.lambda.emit_span(&pattern.span);
 
         match pattern.item {
@@ -269,13 +577,13 @@ impl Compiler {
             SSTPattern::Label(name, pattern) => {
                 self.data(Data::Kind(name));
                 self.lambda.emit(Opcode::UnLabel);
-                self.destructure(*pattern, redeclare);
+                self.destructure(*pattern);
             }
             SSTPattern::Tuple(tuple) => {
                 for (index, sub_pattern) in tuple.into_iter().enumerate() {
                     self.lambda.emit(Opcode::UnTuple);
                     self.lambda.emit_bytes(&mut split_number(index));
-                    self.destructure(sub_pattern, redeclare);
+                    self.destructure(sub_pattern);
                 }
                 // Delete the tuple moved to the top of the stack.
                 self.lambda.emit(Opcode::Del);
@@ -284,25 +592,156 @@ impl Compiler {
     }
 
     /// Assign a value to a variable.
+    /// An assignment is an expression that evaluates to the assigned value,
+    /// i.e. the whole value, not just the parts bound by the pattern,
+    /// so `y = (x = 1)` binds both `x` and `y` to `1`.
+    /// The value is duplicated before it is destructured,
+    /// leaving a copy on the stack as the result.
+    /// When the result isn't needed, i.e. `value` is false, no copy is made.
+    /// Assigning to a bare literal, rather than a pattern containing one, is an error.
     pub fn assign(
         &mut self,
         pattern: Spanned<SSTPattern>,
-        expression: Spanned<SST>
-    ) -> Result<(), Syntax> {
+        expression: Spanned<SST>,
+        value: bool,
+    ) -> Result<(), GenError> {
+        if let SSTPattern::Data(_) = pattern.item {
+            return Err(GenError::AssignToLiteral(pattern.span));
+        }
+
         // eval the expression
         self.walk(&expression)?;
-        self.destructure(pattern, false);
+        if value { self.lambda.emit(Opcode::Dup); }
+        self.destructure(pattern);
+        Ok(())
+    }
+
+    /// Updates a variable that has already been declared.
+    /// Updating a local before it has been assigned to is an error.
+    /// Like an assignment, this evaluates to the updated value.
+    pub fn mutate(
+        &mut self,
+        pattern: Spanned<SSTPattern>,
+        expression: Spanned<SST>,
+        value: bool,
+    ) -> Result<(), GenError> {
+        let unique_symbol = match pattern.item {
+            SSTPattern::Symbol(unique_symbol) => unique_symbol,
+            _ => unreachable!("Expected a variable to update"),
+        };
+
+        if self.scope.is_local(unique_symbol) && !self.declared.contains(&unique_symbol) {
+            return Err(GenError::UpdateBeforeDeclare(pattern.span));
+        }
+
+        self.walk(&expression)?;
+        if value { self.lambda.emit(Opcode::Dup); }
+        self.lambda.emit_span(&pattern.span);
+        self.resolve_assign(unique_symbol);
+        Ok(())
+    }
+
+    /// Generates a for loop.
+    /// The iterable and an index into it are kept on the stack as temporaries
+    /// while the loop runs. Each iteration, `Next` bounds-checks the index,
+    /// and either pushes the next item, which is destructured,
+    /// or jumps past the end of the loop:
+    /// ```plain
+    ///         <iterable>
+    ///         Con 0
+    /// start:  Next end
+    ///         <destructure pattern>
+    ///         <body>
+    ///         Del
+    ///         Jump start
+    /// end:    Del
+    ///         Del
+    ///         Con ()
+    /// ```
+    /// A for loop always evaluates to Unit.
+    pub fn for_loop(
+        &mut self,
+        pattern:  Spanned<SSTPattern>,
+        iterable: Spanned<SST>,
+        body:     Spanned<SST>,
+    ) -> Result<(), GenError> {
+        let span = Span::combine(&pattern.span, &iterable.span);
+
+        self.walk(&iterable)?;
+        self.data(Data::Integer(0));
+
+        let start = self.lambda.code.len();
+        self.lambda.emit_span(&span);
+        self.lambda.emit(Opcode::Next);
+        let end = self.lambda.emit_placeholder();
+
+        self.destructure(pattern);
+        self.walk(&body)?;
+        self.lambda.emit(Opcode::Del);
+        self.lambda.emit(Opcode::Jump);
+        self.lambda.emit_bytes(&mut split_number(start));
+
+        let after = self.lambda.code.len();
+        self.lambda.patch(end, after);
+        self.lambda.emit(Opcode::Del);
+        self.lambda.emit(Opcode::Del);
         self.data(Data::Unit);
         Ok(())
     }
 
+    /// Generates a try expression.
+    /// A handler is installed before the expression is run,
+    /// and removed after the expression completes.
+    /// If an error is raised in between, the VM unwinds to the end of the expression.
+    pub fn try_catch(&mut self, expression: Spanned<SST>, span: Span) -> Result<(), GenError> {
+        self.lambda.emit(Opcode::Try);
+        let end = self.lambda.emit_placeholder();
+
+        self.walk(&expression)?;
+        self.lambda.emit_span(&span);
+        self.lambda.emit(Opcode::EndTry);
+
+        let after = self.lambda.code.len();
+        self.lambda.patch(end, after);
+        Ok(())
+    }
+
+    /// Generates a nil propagation.
+    /// If the expression evaluates to `nil`,
+    /// the VM returns `nil` from the current function.
+    pub fn propagate(&mut self, expression: Spanned<SST>, span: Span) -> Result<(), GenError> {
+        self.walk(&expression)?;
+        self.lambda.emit_span(&span);
+        self.lambda.emit(Opcode::Propagate);
+        Ok(())
+    }
+
+    /// Generates an explicit return.
+    /// Unlike the implicit `Return` at the end of a function,
+    /// the stack may hold temporaries when returning from the middle of a body,
+    /// such as the iterable of a for loop, so `Exit` unwinds to the frame
+    /// instead of deleting a known number of locals.
+    pub fn return_early(&mut self, expression: Spanned<SST>, span: Span) -> Result<(), GenError> {
+        self.walk(&expression)?;
+        self.lambda.emit_span(&span);
+        self.lambda.emit(Opcode::Exit);
+        Ok(())
+    }
+
     /// Recursively compiles a lambda declaration in a new scope.
+    /// A function may refer to the variable it is being assigned to, i.e. `f = x -> f x`:
+    /// the hoister resolves the name to the enclosing local,
+    /// which is captured before the closure is constructed and assigned,
+    /// so the function sees the assignment once it is made.
     pub fn lambda(
         &mut self,
         pattern: Spanned<SSTPattern>,
         expression: Spanned<SST>,
-        scope: Scope,
-    ) -> Result<(), Syntax> {
+        mut scope: Scope,
+    ) -> Result<(), GenError> {
+        // globals are used directly, so they are never captured
+        scope.nonlocals.retain(|nonlocal| !self.globals.contains_key(nonlocal));
+
         // build a list of captures at the boundary
         let mut captures = vec![];
         for nonlocal in scope.nonlocals.iter() {
@@ -319,12 +758,13 @@ impl Compiler {
 
         // just so the parallel is visually apparent
         self.enter_scope(scope);
+        self.warn_shadowed();
         {
             // push locals and captures into lambda
             self.lambda.captures = captures;
 
             // match the argument against the pattern, binding variables
-            self.destructure(pattern, true);
+            self.destructure(pattern);
 
             // enter a new scope and walk the function body
             self.walk(&expression)?;
@@ -332,8 +772,18 @@ impl Compiler {
             // return the result
             self.lambda.emit(Opcode::Return);
             self.lambda.emit_bytes(&mut split_number(self.scope.locals.len()));
+            self.warn_unused();
+        }
+        let mut lambda = self.exit_scope().lambda;
+
+        // `a b -> c` is desugared into `a -> b -> c`,
+        // so each function directly returned counts as another argument
+        lambda.arity = 1;
+        let mut body = &expression;
+        while let SST::Lambda { expression, .. } = &body.item {
+            lambda.arity += 1;
+            body = expression;
         }
-        let lambda = self.exit_scope().lambda;
 
         // push the lambda object onto the callee's stack.
         let lambda_index = self.lambda.index_data(Data::Lambda(Rc::new(lambda)));
@@ -345,7 +795,7 @@ impl Compiler {
 
     /// When a function is called, the top two items are taken off the stack,
     /// The topmost item is expected to be a function.
-    pub fn call(&mut self, fun: Spanned<SST>, arg: Spanned<SST>) -> Result<(), Syntax> {
+    pub fn call(&mut self, fun: Spanned<SST>, arg: Spanned<SST>) -> Result<(), GenError> {
         self.walk(&arg)?;
         self.walk(&fun)?;
 
@@ -364,39 +814,294 @@ mod test {
         desugar::desugar,
         hoist::hoist,
     };
-    use crate::common::source::Source;
+    use crate::common::{source::Source, closure::Closure, number::build_number};
+    use crate::vm::VM;
 
     #[test]
     fn constants() {
         let source = Source::source("heck = true; lol = 0.0; lmao = false; eyy = \"GOod MoRNiNg, SiR\"");
         let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
 
+        // booleans and unit have their own opcodes,
+        // so they are not added to the constant pool
         let result = vec![
-            Data::Boolean(true),
-            Data::Unit, // from assignment
             Data::Real(0.0),
-            Data::Boolean(false),
             Data::String("GOod MoRNiNg, SiR".to_string()),
         ];
 
         assert_eq!(lambda.constants, result);
     }
 
+    #[test]
+    fn constant_spans() {
+        let source = Source::source("x = 37.201\ny = x + 1\nz = 37.201");
+        let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        // the span of the first occurence of a constant is kept
+        let span = lambda.constant_span(0).unwrap();
+        assert_eq!(span.contents(), "37.201");
+        assert_eq!(span.line_col(), Some((1, 5)));
+        assert_eq!(lambda.constant_span(1).unwrap().contents(), "1");
+
+        // the disassembly shows the literal the constant was loaded from
+        assert!(format!("{}", lambda).contains("0\tReal(37.201)\t37.201"));
+    }
+
+    #[test]
+    fn self_reference() {
+        let source = Source::source("fact = n -> fact n");
+        let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        // `fact` is moved to the heap and captured before the closure is made
+        assert_eq!(&lambda.code[..4], &[(Opcode::Capture as u8), 128, (Opcode::Closure as u8), 128]);
+        match &lambda.constants[0] {
+            Data::Lambda(fact) => assert_eq!(fact.captures, vec![Captured::Local(0)]),
+            other => panic!("Expected a lambda, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn forward_reference() {
+        let source = Source::source("is_even = n -> is_odd n; is_odd = n -> is_even n");
+        let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        // both functions capture the local the other is assigned to,
+        // even though `is_odd` is used before it is assigned
+        let captures = lambda.constants.iter()
+            .map(|constant| match constant {
+                Data::Lambda(function) => function.captures.clone(),
+                other => panic!("Expected a lambda, found {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(captures, vec![vec![Captured::Local(1)], vec![Captured::Local(0)]]);
+    }
+
+    #[test]
+    fn local_names() {
+        let source = Source::source("x = 1\n(a, b) = (x, 2)\nf = y -> { z = y; z }");
+        let lambda = gen(hoist(desugar(parse(lex(source.clone()).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        let names = lambda.locals.iter().map(|l| l.name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["x", "a", "b", "f"]);
+        assert_eq!(lambda.locals[1].span(), &Span::new(&source, 7, 1));
+
+        // locals of functions are kept with the function
+        match &lambda.constants[2] {
+            Data::Lambda(f) => {
+                let names = f.locals.iter().map(|l| l.name()).collect::<Vec<_>>();
+                assert_eq!(names, vec!["y", "z"]);
+                assert_eq!(f.locals[1].span().contents(), "z");
+            },
+            other => panic!("Expected a lambda, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn block_scope() {
+        let source = Source::source("x = 1; z = { a = x; b = a; b }; x");
+        let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        // `a` and `b` take up slots 2 and 3, and are dropped once the block's result is computed
+        let result = vec![
+            (Opcode::Load as u8), 128, (Opcode::Declare as u8), 130,  // declare a = x
+            (Opcode::Load as u8), 130, (Opcode::Declare as u8), 131,  // declare b = a
+            (Opcode::Load as u8), 131,                                // load b as the result
+            (Opcode::DelLocals as u8), 130, 130,                      // delete a and b
+            (Opcode::Declare as u8), 129,                             // declare z
+        ];
+        assert_eq!(&lambda.code[4..19], &result[..]);
+        assert_eq!(lambda.decls, 4);
+    }
+
+    #[test]
+    fn unused_warning() {
+        let source = Source::source("x = 1\ny = 2\nf = (a, _b) -> a\nf (x, 3)");
+        let (_, warnings) = gen_with_warnings(
+            hoist(desugar(parse(lex(source.clone()).unwrap()).unwrap()).unwrap()).unwrap()
+        ).unwrap();
+
+        // `_b` is meant to be unused, so only `y` is warned about
+        assert_eq!(warnings, vec![
+            Warning::new("The variable 'y' is never used", &Span::new(&source, 6, 1)),
+        ]);
+    }
+
+    #[test]
+    fn shadowed_warning() {
+        let source = Source::source("x = 1\nf = y -> { x = y; g = x -> x; g x }\nf x");
+        let (_, warnings) = gen_with_warnings(
+            hoist(desugar(parse(lex(source.clone()).unwrap()).unwrap()).unwrap()).unwrap()
+        ).unwrap();
+
+        // the `x` assigned to in `f` is the outer `x`,
+        // but the argument of `g` is a new variable that shadows it
+        assert_eq!(warnings, vec![
+            Warning::related(
+                "The variable 'x' shadows a variable in an enclosing function",
+                &Span::new(&source, 28, 1),
+                &Span::new(&source, 0, 1),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn short_circuit() {
+        let source = Source::source("true or (1 / 0)");
+        let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        // if the left side is true, jump past the right side, keeping it as the result
+        assert_eq!(&lambda.code[..2], &[(Opcode::LoadTrue as u8), (Opcode::JumpIfTrueNoPop as u8)]);
+        let (target, _) = build_number(&lambda.code[2..]);
+        assert_eq!(target, lambda.code.len());
+        assert_eq!(lambda.code[2 + Lambda::PLACEHOLDER_WIDTH], Opcode::Del as u8);
+
+        let mut vm = VM::init(Closure::wrap(lambda));
+        vm.run().unwrap();
+        assert_eq!(vm.stack.pop_data(), Data::Boolean(true));
+    }
+
     #[test]
     fn bytecode() {
         let source = Source::source("heck = true; lol = heck; lmao = false");
         let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
 
         let result = vec![
-            (Opcode::Con as u8), 128, (Opcode::Save as u8), 128,  // con true, save to heck,
-                (Opcode::Con as u8), 129, (Opcode::Del as u8),    // load unit, delete
-            (Opcode::Load as u8), 128, (Opcode::Save as u8), 129, // load heck, save to lol,
-                (Opcode::Con as u8), 129, (Opcode::Del as u8),    // load unit, delete
-            (Opcode::Con as u8), 130, (Opcode::Save as u8), 130,  // con false, save to lmao
-                (Opcode::Con as u8), 129,                         // load unit
+            (Opcode::LoadTrue as u8), (Opcode::Declare as u8), 128,  // declare heck = true
+            (Opcode::Load as u8), 128, (Opcode::Declare as u8), 129, // declare lol = heck
+            (Opcode::LoadFalse as u8), (Opcode::Dup as u8),          // load false, copy it,
+                (Opcode::Declare as u8), 130,                        // declare lmao, copy is result
+        ];
+
+        assert_eq!(result, lambda.code);
+    }
+
+    #[test]
+    fn declare_mutate() {
+        let source = Source::source("x = true; x = false; x += 1");
+        let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        let result = vec![
+            (Opcode::LoadTrue as u8), (Opcode::Declare as u8), 128, // declare x = true
+            (Opcode::LoadFalse as u8), (Opcode::Mutate as u8), 128, // mutate x = false
+            (Opcode::Load as u8), 128, (Opcode::Con as u8), 0,     // load x, load 1,
+                (Opcode::Tuple as u8), 130,                        // tuple them,
+                (Opcode::FFICall as u8), 128,                      // add them,
+                (Opcode::Dup as u8), (Opcode::Mutate as u8), 128,  // copy sum, mutate x
+        ];
+
+        assert_eq!(result, lambda.code);
+    }
+
+    #[test]
+    fn assignment_statements() {
+        let source = Source::source("a = 1; b = a; a = b; c = (d = a)");
+        let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        // statements don't copy the assigned value just to delete it,
+        // only the nested assignment and the final result are kept
+        let result = vec![
+            (Opcode::Con as u8), 0, (Opcode::Declare as u8), 128,    // declare a = 1
+            (Opcode::Load as u8), 128, (Opcode::Declare as u8), 129, // declare b = a
+            (Opcode::Load as u8), 129, (Opcode::Mutate as u8), 128,  // mutate a = b
+            (Opcode::Load as u8), 128, (Opcode::Dup as u8),          // load a, copy it,
+                (Opcode::Declare as u8), 131, (Opcode::Dup as u8),   // declare d, copy it,
+                (Opcode::Declare as u8), 130,                        // declare c, copy is result
+        ];
+
+        assert_eq!(result, lambda.code);
+    }
+
+    #[test]
+    fn mutate_undeclared() {
+        let source = Source::source("x += 1; x = 0");
+        let error = gen(hoist(desugar(parse(lex(source.clone()).unwrap()).unwrap()).unwrap()).unwrap())
+            .unwrap_err();
+
+        assert_eq!(error, GenError::UpdateBeforeDeclare(Span::new(&source, 0, 1)));
+        assert_eq!(error.message(), "A variable can not be updated before it is declared");
+
+        // a variable that is never declared can't be updated either
+        let source = Source::source("y += 1");
+        assert!(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn long_constants() {
+        // the 257th constant no longer fits in a byte
+        let reals = (0..257).map(|n| format!("{}.0", n)).collect::<Vec<_>>();
+        let source = Source::source(&format!("[{}]", reals.join(", ")));
+        let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        assert_eq!(lambda.code[..4], [(Opcode::Con as u8), 0, (Opcode::Con as u8), 1]);
+        assert_eq!(lambda.code[255 * 2..255 * 2 + 2], [(Opcode::Con as u8), 255]);
+        assert_eq!(lambda.code[256 * 2..256 * 2 + 3], [(Opcode::ConLong as u8), 2, 128]);
+
+        let mut vm = VM::init(Closure::wrap(lambda));
+        vm.run().unwrap();
+        match vm.stack.pop_data() {
+            Data::List(list) => {
+                assert_eq!(list[255], Data::Real(255.0));
+                assert_eq!(list[256], Data::Real(256.0));
+            },
+            other => panic!("Expected a list, found {}", other),
+        }
+    }
+
+    #[test]
+    fn fast_constants() {
+        let source = Source::source("(true, false, ())");
+        let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        let result = vec![
+            (Opcode::LoadTrue as u8),
+            (Opcode::LoadFalse as u8),
+            (Opcode::LoadUnit as u8),
+            (Opcode::Tuple as u8), 131,
         ];
 
         assert_eq!(result, lambda.code);
+        assert!(lambda.constants.is_empty());
+    }
+
+    #[test]
+    fn undefined_ffi() {
+        let source = Source::source("x = 1\ny = magic \"nope\" (x, 2)");
+        let error = gen(hoist(desugar(parse(lex(source.clone()).unwrap()).unwrap()).unwrap()).unwrap())
+            .unwrap_err();
+
+        assert_eq!(error.message(), "The ffi function 'nope' is not defined");
+        assert_eq!(error.span(), &Span::new(&source, 10, 19));
+        assert_eq!(error.span().contents(), "magic \"nope\" (x, 2)");
+        assert_eq!(error.span().line_col(), Some((2, 5)));
+    }
+
+    #[test]
+    fn unexpected_keywords() {
+        // magic handled by the VM directly doesn't go through an FFI function
+        for source in ["magic \"map\" (f, [1], by: 2)", "magic \"and\" (true, false, x: 1)"] {
+            let source = Source::source(&format!("f = x -> x\n{}", source));
+            let error = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap())
+                .unwrap_err();
+            assert!(error.message().ends_with("does not take keyword arguments"));
+            assert_eq!(error.span().line_col(), Some((2, 1)));
+        }
+    }
+
+    #[test]
+    fn assign_literal() {
+        // the parser rejects this, so the tree is built by hand
+        let source = Source::source("1 = 2");
+        let pattern = Spanned::new(SSTPattern::Data(Data::Integer(1)), Span::new(&source, 0, 1));
+        let expression = Spanned::new(SST::Data(Data::Integer(2)), Span::new(&source, 4, 1));
+        let sst = Spanned::new(SST::assign(pattern, expression), Span::new(&source, 0, 5));
+
+        let error = gen((sst, Scope::new())).unwrap_err();
+        assert_eq!(error, GenError::AssignToLiteral(Span::new(&source, 0, 1)));
+        assert_eq!(error.message(), "Can not assign to a literal");
+
+        // literals can still be matched against inside a pattern
+        let source = Source::source("(1, x) = (1, 2); x");
+        assert!(gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).is_ok());
     }
 
     // NOTE: instead of veryfying bytecode output,
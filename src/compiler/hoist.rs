@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 
-use crate::common::span::Spanned;
+use crate::common::{
+    span::{Span, Spanned},
+    lambda::Local,
+};
 use crate::compiler::{
     cst::{CST, CSTPattern},
     sst::{SST, SSTPattern, UniqueSymbol, Scope},
@@ -21,8 +24,20 @@ use crate::compiler::{
 /// symbols by the same name in different scopes will get different identifiers.
 /// Also resolves closure captures and closure hoisting.
 pub fn hoist(cst: Spanned<CST>) -> Result<(Spanned<SST>, Scope), Syntax> {
+    hoist_with_globals(cst, &[])
+}
+
+/// Like `hoist`, but with some variables already declared in the root scope,
+/// such as the globals defined by a previous run in a REPL.
+/// See `gen::gen_with_globals`.
+pub fn hoist_with_globals(cst: Spanned<CST>, globals: &[String]) -> Result<(Spanned<SST>, Scope), Syntax> {
     let mut hoister = Hoister::new();
-    let sst = hoister.walk(cst)?;
+    for name in globals {
+        let unique_symbol = hoister.new_symbol(name);
+        hoister.declare_local(unique_symbol, &Span::empty());
+    }
+
+    let sst = hoister.body(cst)?;
     let scope = hoister.scopes.pop().unwrap();
 
     if !hoister.unresolved_hoists.is_empty() {
@@ -53,7 +68,22 @@ pub struct Hoister {
     /// Maps integers (index in vector) to string representation of symbol.
     symbol_table: Vec<String>,
     /// Keeps track of variables that were referenced before assignment.
+    /// A later assignment in the same scope resolves the reference,
+    /// which is what lets sibling functions in a block call each other.
     unresolved_hoists: HashMap<String, UniqueSymbol>,
+    /// The most recently resolved name, the scope depth it was resolved at,
+    /// and the symbol it resolved to.
+    /// Repeatedly referencing the same variable, say, in a loop,
+    /// is common, so this skips walking the scopes again.
+    /// Cleared whenever a scope is entered or exited,
+    /// or a new local is declared, as the name may now resolve differently.
+    last_lookup: Option<(String, usize, UniqueSymbol)>,
+    /// Variables declared with `const`, which can not be reassigned.
+    constants: Vec<UniqueSymbol>,
+    /// Locals of blocks that have exited.
+    /// They are still locals of the function they were declared in,
+    /// but can no longer be referred to by name.
+    out_of_scope: Vec<UniqueSymbol>,
 }
 
 impl Hoister {
@@ -64,6 +94,9 @@ impl Hoister {
             scopes:            vec![Scope::new()],
             symbol_table:      vec![],
             unresolved_hoists: HashMap::new(),
+            last_lookup:       None,
+            constants:         vec![],
+            out_of_scope:      vec![],
         }
     }
 }
@@ -77,19 +110,37 @@ impl Default for Hoister {
 
 impl Hoister {
     /// Enters a new scope, called when entering a new function.
-    fn   enter_scope(&mut self) { self.scopes.push(Scope::new()); }
+    fn enter_scope(&mut self) {
+        self.last_lookup = None;
+        self.scopes.push(Scope::new());
+    }
+
     /// Enters an existing scope, called when resolving variables.
-    fn reenter_scope(&mut self, scope: Scope) { self.scopes.push(scope) }
+    fn reenter_scope(&mut self, scope: Scope) {
+        self.last_lookup = None;
+        self.scopes.push(scope)
+    }
 
     /// Exits the current scope, returning it.
     /// If there are no enclosing scopes, returns `None`.
     fn exit_scope(&mut self) -> Option<Scope> {
+        self.last_lookup = None;
         match self.scopes.len() {
             1 => None,
             _ => self.scopes.pop()
         }
     }
 
+    /// Declares a symbol as a local in the current scope,
+    /// remembering where it was declared.
+    fn declare_local(&mut self, unique_symbol: UniqueSymbol, span: &Span) {
+        self.last_lookup = None;
+        let local = Local::new(&self.symbol_table[unique_symbol.0], span);
+        let scope = self.local_scope();
+        scope.locals.push(unique_symbol);
+        scope.names.push(local);
+    }
+
     /// Returns the topmost, i.e. local, scope, mutably.
     fn local_scope(&mut self) -> &mut Scope {
         let last = self.scopes.len() - 1;
@@ -111,11 +162,20 @@ impl Hoister {
             CST::Symbol(name) => self.symbol(&name),
             CST::Block(block) => self.block(block)?,
             CST::Label(name, expression) => SST::Label(name, Box::new(self.walk(*expression)?)),
+            CST::Try(expression) => SST::Try(Box::new(self.walk(*expression)?)),
+            CST::Propagate(expression) => SST::Propagate(Box::new(self.walk(*expression)?)),
+            CST::Return(expression) => SST::Return(Box::new(self.walk(*expression)?)),
             CST::Tuple(tuple) => self.tuple(tuple)?,
-            CST::FFI    { name,    expression } => SST::ffi(&name, self.walk(*expression)?),
+            CST::List(list) => self.list(list)?,
+            CST::FFI    { name, expression, keywords } => self.ffi(name, *expression, keywords)?,
             CST::Assign { pattern, expression } => self.assign(*pattern, *expression)?,
+            CST::Const  { pattern, expression } => self.constant(*pattern, *expression)?,
+            CST::Mutate { pattern, expression } => self.mutate(*pattern, *expression)?,
             CST::Lambda { pattern, expression } => self.lambda(*pattern, *expression)?,
             CST::Call   { fun,     arg        } => self.call(*fun, *arg)?,
+            CST::For    { pattern, iterable, body } => self.for_loop(*pattern, *iterable, *body)?,
+            CST::Range  { start,   end        } => SST::range(self.walk(*start)?, self.walk(*end)?),
+            CST::Index  { expression, index   } => SST::index(self.walk(*expression)?, self.walk(*index)?),
         };
 
         Ok(Spanned::new(sst, cst.span))
@@ -126,7 +186,7 @@ impl Hoister {
     pub fn walk_pattern(&mut self, pattern: Spanned<CSTPattern>, declare: bool) -> Spanned<SSTPattern> {
         let item = match pattern.item {
             CSTPattern::Symbol(name) => {
-                SSTPattern::Symbol(self.resolve_assign(&name, declare))
+                SSTPattern::Symbol(self.resolve_assign(&name, declare, &pattern.span))
             },
             CSTPattern::Data(d)     => SSTPattern::Data(d),
             CSTPattern::Label(n, p) => SSTPattern::Label(n, Box::new(self.walk_pattern(*p, declare))),
@@ -138,6 +198,29 @@ impl Hoister {
         Spanned::new(item, pattern.span)
     }
 
+    /// Raises an error if a pattern would reassign a constant.
+    fn check_constants(&self, pattern: &Spanned<SSTPattern>) -> Result<(), Syntax> {
+        match &pattern.item {
+            SSTPattern::Symbol(unique_symbol) if self.constants.contains(unique_symbol) => Err(Syntax::error(
+                &format!("Can not reassign the constant '{}'", self.symbol_table[unique_symbol.0]),
+                &pattern.span,
+            )),
+            SSTPattern::Symbol(_) | SSTPattern::Data(_) => Ok(()),
+            SSTPattern::Label(_, p) => self.check_constants(p),
+            SSTPattern::Tuple(t) => t.iter().try_for_each(|p| self.check_constants(p)),
+        }
+    }
+
+    /// Marks every variable bound by a pattern as a constant.
+    fn declare_constants(&mut self, pattern: &SSTPattern) {
+        match pattern {
+            SSTPattern::Symbol(unique_symbol) => self.constants.push(*unique_symbol),
+            SSTPattern::Data(_) => (),
+            SSTPattern::Label(_, p) => self.declare_constants(&p.item),
+            SSTPattern::Tuple(t) => for p in t { self.declare_constants(&p.item) },
+        }
+    }
+
     /// Creates a new symbol that is guaranteed to be unique.
     fn new_symbol(&mut self, name: &str) -> UniqueSymbol {
         let index = self.symbol_table.len();
@@ -146,10 +229,11 @@ impl Hoister {
     }
 
     /// Looks to see whether a name is defined as a local in the current scope.
+    /// Locals of blocks that have exited are skipped.
     fn local_symbol(&self, name: &str) -> Option<UniqueSymbol> {
         for local in self.borrow_local_scope().locals.iter() {
             let local_name = &self.symbol_table[local.0];
-            if local_name == name { return Some(*local); }
+            if local_name == name && !self.out_of_scope.contains(local) { return Some(*local); }
         }
 
         None
@@ -187,7 +271,23 @@ impl Hoister {
     /// 1. If this variable is local or nonlocal to this scope, use it.
     /// 2. If this variable is defined in an enclosing scope, capture it and use it.
     /// 3. If this variable is not defined, return `None`.
+    ///
+    /// The most recent successful lookup is cached, see `Hoister.last_lookup`.
     fn try_resolve(&mut self, name: &str) -> Option<UniqueSymbol> {
+        let depth = self.scopes.len();
+        if let Some((last_name, last_depth, unique_symbol)) = &self.last_lookup {
+            if last_name == name && *last_depth == depth { return Some(*unique_symbol); }
+        }
+
+        let resolved = self.try_resolve_uncached(name);
+        if let Some(unique_symbol) = resolved {
+            self.last_lookup = Some((name.to_string(), depth, unique_symbol));
+        }
+        resolved
+    }
+
+    /// Resolves a variable lookup by walking the scopes, see `try_resolve`.
+    fn try_resolve_uncached(&mut self, name: &str) -> Option<UniqueSymbol> {
         if let Some(unique_symbol) = self.local_symbol(name)    { return Some(unique_symbol); }
         if let Some(unique_symbol) = self.nonlocal_symbol(name) { return Some(unique_symbol); }
 
@@ -209,14 +309,14 @@ impl Hoister {
     /// this function will define it in all lexical scopes
     /// once this variable is discovered, we remove the definitions
     /// in all scopes below this one.
-    fn resolve_assign(&mut self, name: &str, redeclare: bool) -> UniqueSymbol {
+    fn resolve_assign(&mut self, name: &str, redeclare: bool, span: &Span) -> UniqueSymbol {
         // if we've seen the symbol before but don't know where it's defined
         if let Some(unique_symbol) = self.unresolved_hoists.get(name) {
             // this is a definition; we've resolved it!
             let unique_symbol = *unique_symbol;
             self.uncapture_all(unique_symbol);
             self.unresolved_hoists.remove(name);
-            self.local_scope().locals.push(unique_symbol);
+            self.declare_local(unique_symbol, span);
             return unique_symbol;
         }
 
@@ -228,7 +328,7 @@ impl Hoister {
 
         // if we didn't find it by searching backwards, we declare it in the current scope
         let unique_symbol = self.new_symbol(name);
-        self.local_scope().locals.push(unique_symbol);
+        self.declare_local(unique_symbol, span);
         unique_symbol
     }
 
@@ -258,14 +358,44 @@ impl Hoister {
         SST::Symbol(self.resolve_symbol(name))
     }
 
-    /// Walks a block, nothing fancy here.
+    /// Walks the body of a function, or the program itself.
+    /// Variables assigned in the body are visible until the function returns,
+    /// so unlike other blocks, a body does not introduce a scope.
+    pub fn body(&mut self, cst: Spanned<CST>) -> Result<Spanned<SST>, Syntax> {
+        match cst.item {
+            CST::Block(block) => {
+                let expressions = self.expressions(block)?;
+                Ok(Spanned::new(SST::block(expressions, vec![]), cst.span))
+            },
+            _ => self.walk(cst),
+        }
+    }
+
+    /// Walks a block, which introduces a new scope:
+    /// variables first assigned in a block are not visible after it.
+    /// Variables that already exist are still updated in place.
+    /// Block locals are declared as locals of the enclosing function,
+    /// so they take up the same contiguous run of slots,
+    /// which is recorded so that `gen` can drop them when the block exits.
     pub fn block(&mut self, block: Vec<Spanned<CST>>) -> Result<SST, Syntax> {
+        let start = self.borrow_local_scope().locals.len();
+        let expressions = self.expressions(block)?;
+
+        let locals = self.borrow_local_scope().locals[start..].to_vec();
+        self.last_lookup = None;
+        self.out_of_scope.extend(locals.iter().copied());
+
+        Ok(SST::block(expressions, locals))
+    }
+
+    /// Walks each expression in a block, nothing fancy here.
+    fn expressions(&mut self, block: Vec<Spanned<CST>>) -> Result<Vec<Spanned<SST>>, Syntax> {
         let mut expressions = vec![];
         for expression in block {
             expressions.push(self.walk(expression)?)
         }
 
-        Ok(SST::Block(expressions))
+        Ok(expressions)
     }
 
     /// Walks a tuple, nothing fancy here.
@@ -278,11 +408,61 @@ impl Hoister {
         Ok(SST::Tuple(expressions))
     }
 
+    /// Walks an FFI call, along with the value of each keyword argument passed to it.
+    pub fn ffi(
+        &mut self,
+        name: String,
+        expression: Spanned<CST>,
+        keywords: Vec<(String, Spanned<CST>)>,
+    ) -> Result<SST, Syntax> {
+        let expression = Box::new(self.walk(expression)?);
+        let mut walked = vec![];
+        for (keyword, value) in keywords {
+            walked.push((keyword, self.walk(value)?));
+        }
+
+        Ok(SST::FFI { name, expression, keywords: walked })
+    }
+
+    /// Walks a list, nothing fancy here.
+    pub fn list(&mut self, list: Vec<Spanned<CST>>) -> Result<SST, Syntax> {
+        let mut expressions = vec![];
+        for expression in list {
+            expressions.push(self.walk(expression)?)
+        }
+
+        Ok(SST::List(expressions))
+    }
+
+    /// Walks a for loop.
+    /// The iterable is walked before the pattern, so that `for x in x`
+    /// iterates over the previous value of `x`.
+    /// Like an assignment, variables bound by the pattern live in the current scope.
+    pub fn for_loop(
+        &mut self,
+        pattern:  Spanned<CSTPattern>,
+        iterable: Spanned<CST>,
+        body:     Spanned<CST>,
+    ) -> Result<SST, Syntax> {
+        let sst_iterable = self.walk(iterable)?;
+        let sst_pattern  = self.walk_pattern(pattern, false);
+        self.check_constants(&sst_pattern)?;
+        let sst_body     = self.walk(body)?;
+
+        Ok(SST::for_loop(
+            sst_pattern,
+            sst_iterable,
+            sst_body,
+        ))
+    }
+
     /// Walks an assignment.
     /// Delegates to `walk_pattern` for capturing.
-    /// Assignments can capture existing variables
+    /// Assignments can capture existing variables,
+    /// but can not reassign constants.
     pub fn assign(&mut self, pattern: Spanned<CSTPattern>, expression: Spanned<CST>) -> Result<SST, Syntax> {
         let sst_pattern = self.walk_pattern(pattern, false);
+        self.check_constants(&sst_pattern)?;
         let sst_expression = self.walk(expression)?;
 
         Ok(SST::assign(
@@ -291,13 +471,42 @@ impl Hoister {
         ))
     }
 
+    /// Walks a constant, which is an assignment
+    /// whose variables can not be reassigned afterwards.
+    pub fn constant(&mut self, pattern: Spanned<CSTPattern>, expression: Spanned<CST>) -> Result<SST, Syntax> {
+        let sst = self.assign(pattern, expression)?;
+        if let SST::Assign { pattern, .. } = &sst {
+            self.declare_constants(&pattern.item);
+        }
+        Ok(sst)
+    }
+
+    /// Walks an update of an existing variable.
+    /// Unlike an assignment, the variable is never declared;
+    /// it's looked up like any other reference to a variable,
+    /// so updating a variable that is never declared is an error.
+    pub fn mutate(&mut self, pattern: Spanned<CSTPattern>, expression: Spanned<CST>) -> Result<SST, Syntax> {
+        let unique_symbol = match pattern.item {
+            CSTPattern::Symbol(name) => self.resolve_symbol(&name),
+            _ => unreachable!("Expected a variable to update"),
+        };
+        let sst_pattern = Spanned::new(SSTPattern::Symbol(unique_symbol), pattern.span);
+        self.check_constants(&sst_pattern)?;
+        let sst_expression = self.walk(expression)?;
+
+        Ok(SST::mutate(
+            sst_pattern,
+            sst_expression,
+        ))
+    }
+
     /// Walks a function definition.
     /// Like `assign`, delegates to `walk_pattern` for capturing.
     /// But any paramaters will shadow those in outer scopes.
     pub fn lambda(&mut self, pattern: Spanned<CSTPattern>, expression: Spanned<CST>) -> Result<SST, Syntax> {
         self.enter_scope();
         let sst_pattern = self.walk_pattern(pattern, true);
-        let sst_expression = self.walk(expression)?;
+        let sst_expression = self.body(expression)?;
         let scope = self.exit_scope().unwrap();
 
         Ok(SST::lambda(
@@ -0,0 +1,454 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+    fs,
+    mem,
+};
+
+use crate::common::{
+    source::Source,
+    span::{Span, Spanned},
+    data::Data,
+};
+
+use crate::compiler::{
+    lex,
+    parse,
+    desugar,
+    hoist,
+    ast::{AST, ASTPattern},
+    syntax::Syntax,
+};
+
+/// Finds the source code of a module, given the path it was imported by.
+/// The compiler does not assume modules live on disk,
+/// so that, for instance, tests can import modules held in memory.
+pub trait Resolver {
+    /// Returns the source of the module at `path`,
+    /// as imported from the module `from`.
+    fn resolve(&mut self, path: &str, from: Option<&Source>) -> Result<Rc<Source>, String>;
+}
+
+/// Resolves modules from the filesystem.
+/// Paths are relative to the directory of the module importing them.
+pub struct FileResolver;
+
+impl Resolver for FileResolver {
+    fn resolve(&mut self, path: &str, from: Option<&Source>) -> Result<Rc<Source>, String> {
+        let directory = from
+            .and_then(|s| s.path.parent())
+            .unwrap_or_else(|| Path::new("."));
+        let full = directory.join(path);
+        // so that a module is recognized no matter how its path is written
+        let full = fs::canonicalize(&full).unwrap_or(full);
+
+        Source::path(&full)
+            .map_err(|e| format!("Could not read the module '{}': {}", path, e))
+    }
+}
+
+/// Resolves modules from a set of sources held in memory,
+/// keyed by the exact path used to import them.
+pub struct MemoryResolver(HashMap<String, String>);
+
+impl MemoryResolver {
+    /// Creates a new resolver with no modules.
+    pub fn new() -> MemoryResolver {
+        MemoryResolver(HashMap::new())
+    }
+
+    /// Adds a module that can be imported with a path.
+    pub fn add(&mut self, path: &str, contents: &str) {
+        self.0.insert(path.to_string(), contents.to_string());
+    }
+}
+
+impl Default for MemoryResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver for MemoryResolver {
+    fn resolve(&mut self, path: &str, _from: Option<&Source>) -> Result<Rc<Source>, String> {
+        match self.0.get(path) {
+            Some(contents) => Ok(Source::new(contents, &PathBuf::from(path))),
+            None => Err(format!("The module '{}' does not exist", path)),
+        }
+    }
+}
+
+/// Resolves every `import` in an `AST`, using a `Resolver` to find modules.
+/// An import evaluates to a map from the names bound at the top level of the module
+/// to their values, i.e. the module's namespace.
+/// A module is run in its own scope, so it can not refer to the bindings of the importer;
+/// nor can modules import each other in a cycle.
+///
+/// Every name bound at the top level of a module is exported.
+/// When a module is bound to a name at the top level, i.e. `m = import "m"`,
+/// its bindings can be accessed with `m.name`,
+/// which is an error at compile time if the module does not export `name`.
+pub fn import(ast: Spanned<AST>, resolver: &mut dyn Resolver) -> Result<Spanned<AST>, Syntax> {
+    let mut importer = Importer {
+        resolver,
+        importing: ast.span.source.iter().map(|s| s.path.clone()).collect(),
+        modules:   HashMap::new(),
+    };
+    importer.walk(ast)
+}
+
+/// Keeps track of the modules currently being imported,
+/// i.e. the chain of imports that lead to the current module.
+struct Importer<'a> {
+    resolver:  &'a mut dyn Resolver,
+    importing: Vec<PathBuf>,
+    /// The names modules are bound to in the current module,
+    /// mapped to the names those modules export.
+    modules:   HashMap<String, Vec<String>>,
+}
+
+impl<'a> Importer<'a> {
+    /// Walks an `AST`, replacing each import with the module it refers to.
+    fn walk(&mut self, ast: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        let walk_all = |importer: &mut Self, items: Vec<Spanned<AST>>| items.into_iter()
+            .map(|i| importer.walk(i))
+            .collect::<Result<Vec<_>, _>>();
+
+        let item = match ast.item {
+            AST::Import(path) => return Ok(self.module(&path, ast.span)?.0),
+
+              AST::Symbol(_)
+            | AST::Data(_)
+            | AST::CSTPattern(_)
+            | AST::ArgPattern(_) => return Ok(ast),
+
+            AST::Block(b) => AST::Block(walk_all(self, b)?),
+            AST::Form(f)  => AST::Form(walk_all(self, f)?),
+            AST::Tuple(t) => AST::Tuple(walk_all(self, t)?),
+            AST::List(l)  => AST::List(walk_all(self, l)?),
+            AST::Group(e) => AST::group(self.walk(*e)?),
+            AST::Label(n, e) => AST::label(&n, self.walk(*e)?),
+            AST::Try(e) => AST::Try(Box::new(self.walk(*e)?)),
+            AST::Propagate(e) => AST::Propagate(Box::new(self.walk(*e)?)),
+            AST::Keyword { name, value } => AST::Keyword { name, value: Box::new(self.walk(*value)?) },
+            AST::Return(e) => AST::Return(Box::new(self.walk(*e)?)),
+            AST::Assign { pattern, expression } => self.assign(*pattern, *expression)?,
+            AST::Const { pattern, expression } => match self.assign(*pattern, *expression)? {
+                AST::Assign { pattern, expression } => AST::Const { pattern, expression },
+                _ => unreachable!(),
+            },
+            AST::Mutate { pattern, expression } => {
+                let expression = self.walk(*expression)?;
+                for name in self.shadow(&pattern.item).keys() {
+                    self.modules.remove(name);
+                }
+                AST::mutate(*pattern, expression)
+            },
+            AST::Lambda { pattern, expression } => {
+                let shadowed = self.shadow(&pattern.item);
+                let expression = self.walk(*expression);
+                self.modules.extend(shadowed);
+                AST::lambda(*pattern, expression?)
+            },
+            AST::Composition { argument, function } => {
+                if let Some(access) = self.access(&argument, &function)? {
+                    return Ok(Spanned::new(access, ast.span));
+                }
+                AST::composition(self.walk(*argument)?, self.walk(*function)?)
+            },
+            AST::Range { start, end } => AST::range(self.walk(*start)?, self.walk(*end)?),
+            AST::Index { expression, index } => AST::index(self.walk(*expression)?, self.walk(*index)?),
+            AST::For { pattern, iterable, body } => {
+                let iterable = self.walk(*iterable)?;
+                let shadowed = self.shadow(&pattern.item);
+                let body = self.walk(*body);
+                self.modules.extend(shadowed);
+                AST::for_loop(*pattern, iterable, body?)
+            },
+            AST::Where { expression, bindings } => {
+                let mut shadowed = vec![];
+                let bindings = bindings.into_iter()
+                    .map(|(pattern, value)| {
+                        let value = self.walk(value);
+                        shadowed.push(self.shadow(&pattern.item));
+                        Ok((pattern, value?))
+                    })
+                    .collect::<Result<Vec<_>, Syntax>>();
+                let expression = self.walk(*expression);
+                for modules in shadowed.into_iter().rev() { self.modules.extend(modules); }
+                AST::where_clause(expression?, bindings?)
+            },
+            AST::Comprehension { element, pattern, iterable, filter } => {
+                let iterable = self.walk(*iterable)?;
+                let shadowed = self.shadow(&pattern.item);
+                let element = self.walk(*element);
+                let filter = filter.map(|f| self.walk(*f)).transpose();
+                self.modules.extend(shadowed);
+                AST::comprehension(element?, *pattern, iterable, filter?)
+            },
+            AST::Syntax { arg_pat, expression } => AST::syntax(*arg_pat, self.walk(*expression)?),
+            AST::FFI { name, expression } => AST::ffi(&name, self.walk(*expression)?),
+            AST::Interpolation(fragments) => AST::Interpolation(
+                fragments.into_iter()
+                    .map(|f| f.map_expression(|e| self.walk(e)))
+                    .collect::<Result<Vec<_>, _>>()?
+            ),
+        };
+
+        Ok(Spanned::new(item, ast.span))
+    }
+
+    /// Walks an assignment.
+    /// If a module is assigned to a single name, remembers what it exports,
+    /// so that its bindings can be accessed through that name.
+    fn assign(&mut self, pattern: Spanned<ASTPattern>, expression: Spanned<AST>) -> Result<AST, Syntax> {
+        let module = match (&pattern.item, expression.item) {
+            (ASTPattern::Symbol(name), AST::Import(path)) => {
+                let (module, exports) = self.module(&path, expression.span)?;
+                self.modules.insert(name.clone(), exports);
+                module
+            },
+            (_, item) => {
+                let expression = self.walk(Spanned::new(item, expression.span))?;
+                // a module rebound to something else is no longer a module
+                for name in self.shadow(&pattern.item).keys() {
+                    self.modules.remove(name);
+                }
+                expression
+            },
+        };
+
+        Ok(AST::assign(pattern, module))
+    }
+
+    /// Removes the modules whose names are bound by a pattern,
+    /// returning them so that they can be restored once the pattern goes out of scope.
+    fn shadow(&mut self, pattern: &ASTPattern) -> HashMap<String, Vec<String>> {
+        let mut names = vec![];
+        bindings(pattern, &mut names);
+        names.into_iter()
+            .filter_map(|n| self.modules.remove_entry(&n))
+            .collect()
+    }
+
+    /// If a composition accesses a binding of a module, i.e. `m.name`,
+    /// produces a lookup of that name in the module's namespace:
+    /// ```ignore
+    /// magic "get" (m, "name")
+    /// ```
+    /// Because `m.name x` is parsed as `m.(name x)`,
+    /// a binding may also be accessed at the head of a call,
+    /// in which case the lookup is called instead.
+    /// Accessing a name that the module does not export is an error.
+    fn access(&mut self, argument: &Spanned<AST>, function: &Spanned<AST>) -> Result<Option<AST>, Syntax> {
+        let (module, name, args) = match (&argument.item, &function.item) {
+            (AST::Symbol(module), AST::Symbol(_)) => (module, function, vec![]),
+            (AST::Symbol(module), AST::Form(f)) => match f.split_first() {
+                Some((name @ Spanned { item: AST::Symbol(_), .. }, args)) => (module, name, args.to_vec()),
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        let key = match &name.item { AST::Symbol(key) => key, _ => unreachable!() };
+
+        let exports = match self.modules.get(module) {
+            Some(exports) => exports,
+            None => return Ok(None),
+        };
+
+        if !exports.contains(key) {
+            return Err(Syntax::error(
+                &format!("The module '{}' does not export '{}'", module, key),
+                &name.span,
+            ));
+        }
+
+        let span = Span::combine(&argument.span, &name.span);
+        let key = Spanned::new(AST::Data(Data::String(key.clone())), name.span.clone());
+        let lookup = AST::ffi("get", Spanned::new(AST::Tuple(vec![argument.clone(), key]), span.clone()));
+
+        if args.is_empty() { return Ok(Some(lookup)); }
+        let mut form = vec![Spanned::new(lookup, span)];
+        for arg in args { form.push(self.walk(arg)?); }
+        Ok(Some(AST::Form(form)))
+    }
+
+    /// Compiles the module at a path, producing an expression that evaluates to its namespace,
+    /// along with the names the module exports.
+    /// The body of the module is wrapped in a function which is called immediately,
+    /// so that its bindings do not leak into the importer:
+    /// ```ignore
+    /// (() -> { <module>; magic "map_from" [("name", name), ...] }) ()
+    /// ```
+    fn module(&mut self, path: &str, span: Span) -> Result<(Spanned<AST>, Vec<String>), Syntax> {
+        let from = span.source.clone();
+        let source = self.resolver.resolve(path, from.as_deref())
+            .map_err(|e| Syntax::error(&e, &span))?;
+
+        if let Some(index) = self.importing.iter().position(|p| p == &source.path) {
+            let cycle = self.importing[index..].iter()
+                .chain(std::iter::once(&source.path))
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(Syntax::error(&format!("Import cycle detected: {}", cycle), &span));
+        }
+
+        // each module refers to the modules it imports by its own names
+        let modules = mem::take(&mut self.modules);
+        self.importing.push(source.path.clone());
+        let module = self.walk(parse(lex(source)?)?);
+        self.importing.pop();
+        self.modules = modules;
+        let module = module?;
+
+        // check the module on its own, so it can't refer to the bindings of the importer
+        hoist(desugar(module.clone())?)?;
+
+        let mut expressions = match module.item {
+            AST::Block(b) => b,
+            _ => unreachable!("Expected a module to be parsed as a block"),
+        };
+
+        let mut names = vec![];
+        for expression in expressions.iter() {
+            match &expression.item {
+                  AST::Assign { pattern, .. }
+                | AST::Const  { pattern, .. } => bindings(&pattern.item, &mut names),
+                _ => (),
+            }
+        }
+
+        let spanned = |item| Spanned::new(item, span.clone());
+        let namespace = names.iter().cloned()
+            .map(|name| spanned(AST::Tuple(vec![
+                spanned(AST::Data(Data::String(name.clone()))),
+                spanned(AST::Symbol(name)),
+            ])))
+            .collect::<Vec<_>>();
+        expressions.push(spanned(AST::ffi("map_from", spanned(AST::List(namespace)))));
+
+        let body = AST::lambda(
+            Spanned::new(ASTPattern::Data(Data::Unit), span.clone()),
+            spanned(AST::Block(expressions)),
+        );
+
+        let module = spanned(AST::Form(vec![
+            spanned(AST::group(spanned(body))),
+            spanned(AST::Data(Data::Unit)),
+        ]));
+        Ok((module, names))
+    }
+}
+
+/// Collects the names bound by a pattern, in order, without duplicates.
+fn bindings(pattern: &ASTPattern, names: &mut Vec<String>) {
+    match pattern {
+        ASTPattern::Symbol(name) => if !names.contains(name) { names.push(name.clone()) },
+        ASTPattern::Data(_) => (),
+        ASTPattern::Label(_, p) => bindings(&p.item, names),
+          ASTPattern::Chain(ps)
+        | ASTPattern::Tuple(ps) => for p in ps { bindings(&p.item, names) },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compiler::gen::gen;
+    use crate::common::closure::Closure;
+    use crate::vm::VM;
+
+    fn run(source: &str, resolver: &mut dyn Resolver) -> Result<Data, String> {
+        let ast = parse(lex(Source::source(source)).unwrap()).unwrap();
+        let lambda = import(ast, resolver)
+            .and_then(desugar)
+            .and_then(hoist)
+            .and_then(|sst| Ok(gen(sst)?))
+            .map_err(|e| e.message)?;
+
+        let mut vm = VM::init(Closure::wrap(lambda));
+        vm.run().map_err(|e| e.message().to_string())?;
+        Ok(vm.stack.pop_data())
+    }
+
+    #[test]
+    fn import_module() {
+        let mut resolver = MemoryResolver::new();
+        resolver.add("math", "double = x -> x + x\ntwo = double 1");
+
+        let result = run("
+            math = import \"math\"
+            two = magic \"get\" (math, \"two\")
+            double = magic \"get\" (math, \"double\")
+            (two, double 21)
+        ", &mut resolver);
+
+        assert_eq!(result, Ok(Data::Tuple(vec![Data::Integer(2), Data::Integer(42)])));
+    }
+
+    #[test]
+    fn import_scope() {
+        // a module can't refer to the bindings of its importer
+        let mut resolver = MemoryResolver::new();
+        resolver.add("leaky", "y = x + 1");
+
+        let result = run("x = 1; import \"leaky\"", &mut resolver);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn import_missing() {
+        let result = run("import \"nowhere\"", &mut MemoryResolver::new());
+        assert_eq!(result, Err("The module 'nowhere' does not exist".to_string()));
+    }
+
+    #[test]
+    fn import_access() {
+        let mut resolver = MemoryResolver::new();
+        resolver.add("math", "double = x -> x + x\ntwo = double 1");
+
+        let result = run("
+            math = import \"math\"
+            (math.two, math.double 21)
+        ", &mut resolver);
+
+        assert_eq!(result, Ok(Data::Tuple(vec![Data::Integer(2), Data::Integer(42)])));
+    }
+
+    #[test]
+    fn import_access_missing() {
+        let mut resolver = MemoryResolver::new();
+        resolver.add("math", "double = x -> x + x");
+
+        let result = run("math = import \"math\"; math.triple 3", &mut resolver);
+        assert_eq!(result, Err("The module 'math' does not export 'triple'".to_string()));
+    }
+
+    #[test]
+    fn import_access_shadowed() {
+        // a parameter with the same name as a module is not the module
+        let mut resolver = MemoryResolver::new();
+        resolver.add("math", "double = x -> x + x");
+
+        let result = run("
+            math = import \"math\"
+            triple = x -> x * 3
+            f = math -> math.triple
+            f 2
+        ", &mut resolver);
+
+        assert_eq!(result, Ok(Data::Integer(6)));
+    }
+
+    #[test]
+    fn import_cycle() {
+        let mut resolver = MemoryResolver::new();
+        resolver.add("a", "b = import \"b\"");
+        resolver.add("b", "a = import \"a\"");
+
+        let result = run("import \"a\"", &mut resolver);
+        assert_eq!(result, Err("Import cycle detected: a -> b -> a".to_string()));
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    ops::Range,
     str::FromStr,
     f64,
     rc::Rc,
@@ -11,7 +12,7 @@ use crate::common::{
 };
 
 use crate::compiler::{
-    token::Token,
+    token::{Token, Piece},
     syntax::Syntax,
 };
 
@@ -21,23 +22,48 @@ type Bite = (Token, usize);
 /// This _must_ be sorted by length.
 pub const STATIC_TOKENS: &[(&str, Token)] = &[
     ("syntax", Token::Syntax),
+    ("import", Token::Import),
+    ("return", Token::Return),
     ("magic", Token::Magic),
+    ("const", Token::Const),
+    ("where", Token::Where),
+    ("for", Token::For),
+    ("try", Token::Try),
+    ("nil", Token::Nil),
     ("()", Token::Unit),
     ("->", Token::Lambda),
     ("==", Token::Equal),
+    ("!=", Token::NotEqual),
+    ("+=", Token::AddAssign),
+    ("-=", Token::SubAssign),
+    ("*=", Token::MulAssign),
+    ("/=", Token::DivAssign),
+    ("%=", Token::RemAssign),
     ("**", Token::Pow),
+    ("^^", Token::BitXor),
+    ("<<", Token::ShiftLeft),
+    (">>", Token::ShiftRight),
+    ("|>", Token::Pipe),
+    ("..", Token::Range),
+    ("in", Token::In),
     ("{", Token::OpenBracket),
     ("}", Token::CloseBracket),
     ("(", Token::OpenParen),
     (")", Token::CloseParen),
+    ("[", Token::OpenSquare),
+    ("]", Token::CloseSquare),
     ("=", Token::Assign),
     (".", Token::Compose),
     (",", Token::Pair),
+    (":", Token::Colon),
     ("+", Token::Add),
     ("-", Token::Sub),
     ("*", Token::Mul),
     ("/", Token::Div),
     ("%", Token::Rem),
+    ("&", Token::BitAnd),
+    ("|", Token::BitOr),
+    ("?", Token::Question),
 ];
 
 /// Simple function that lexes a source file into a token stream.
@@ -47,6 +73,126 @@ pub fn lex(source: Rc<Source>) -> Result<Vec<Spanned<Token>>, Syntax> {
     lexer.all()
 }
 
+/// Lexes a source file like `lex`, but keeps comments as `Token::Comment`s.
+/// Each comment holds the text between its delimiters,
+/// and is spanned over the whole comment, delimiters included.
+/// This is meant for tooling, like formatters and doc extractors;
+/// the parser does not expect comments, so don't parse the result.
+pub fn lex_with_comments(source: Rc<Source>) -> Result<Vec<Spanned<Token>>, Syntax> {
+    let mut lexer = Lexer::with_comments(&source);
+    lexer.all()
+}
+
+/// Lexes an edited source file, reusing the tokens lexed before the edit where possible.
+/// `tokens` are the tokens of the source before the edit,
+/// and `changed` is the range of the edited source that was inserted or modified
+/// (an empty range for a deletion).
+/// Lexing resumes from a token just before the change,
+/// and stops as soon as a token lines up with a token lexed before the edit,
+/// as lexing from the start of a token only depends on the text after it;
+/// the remaining tokens are reused, shifted by the change in length.
+/// The result is the same as lexing the edited source from scratch.
+pub fn relex(
+    source: Rc<Source>,
+    tokens: &[Spanned<Token>],
+    changed: Range<usize>,
+) -> Result<Vec<Spanned<Token>>, Syntax> {
+    let old_source = match tokens.iter().find_map(|t| t.span.source.clone()) {
+        Some(old_source) => old_source,
+        None => return lex(source),
+    };
+
+    // the end token points at the end of the source, so it is added back at the new end
+    let tokens = match tokens.last() {
+        Some(Spanned { item: Token::End, .. }) => &tokens[..tokens.len() - 1],
+        _ => tokens,
+    };
+    let delta = source.contents.len() as isize - old_source.contents.len() as isize;
+
+    // a token ending right where the change starts may be extended by it,
+    // and the token before it may depend on it, so resume one token earlier
+    let first_changed = tokens.iter()
+        .position(|t| t.span.end() >= changed.start)
+        .unwrap_or(tokens.len());
+    let resume = first_changed.saturating_sub(1);
+
+    let mut relexed = tokens[..resume].iter()
+        .map(|t| moved(t, &source, 0))
+        .collect::<Vec<_>>();
+
+    let mut lexer = Lexer::new(&source);
+    lexer.offset = tokens.get(resume).map_or_else(
+        || relexed.last().map_or(0, |t| t.span.end()),
+        |t| t.span.offset,
+    );
+
+    let mut old = resume;
+    while !lexer.remaining().is_empty() {
+        let token = lexer.token()?;
+
+        if token.span.offset >= changed.end {
+            let old_offset = token.span.offset as isize - delta;
+            while old < tokens.len() && (tokens[old].span.offset as isize) < old_offset { old += 1; }
+
+            // lexing is back in sync, so the rest of the tokens are unchanged
+            if old < tokens.len() && tokens[old].span.offset as isize == old_offset {
+                relexed.extend(tokens[old..].iter().map(|t| moved(t, &source, delta)));
+                relexed.push(Spanned::new(Token::End, Span::point(&source, source.contents.len())));
+                return Ok(relexed);
+            }
+        }
+
+        relexed.push(token);
+    }
+
+    relexed.push(Spanned::new(Token::End, Span::point(&source, source.contents.len())));
+    Ok(relexed)
+}
+
+/// Moves a token lexed from a source before an edit into the edited source,
+/// shifting it by `delta`, along with the tokens of any expressions embedded in it.
+fn moved(token: &Spanned<Token>, source: &Rc<Source>, delta: isize) -> Spanned<Token> {
+    let item = match &token.item {
+        Token::Interpolation(pieces) => Token::Interpolation(pieces.iter().map(|piece| match piece {
+            Piece::Expression(tokens) => Piece::Expression(
+                tokens.iter().map(|t| moved(t, source, delta)).collect()
+            ),
+            text => text.clone(),
+        }).collect()),
+        other => other.clone(),
+    };
+
+    let offset = (token.span.offset as isize + delta) as usize;
+    Spanned::new(item, Span::new(source, offset, token.span.length))
+}
+
+/// Lexes a source file, then lists each token on its own line,
+/// along with the line and column it starts at and the text it was lexed from:
+/// ```plain
+/// 1:1 a symbol "x"
+/// 1:3 an assignment "="
+/// 1:5 a number "1"
+/// 1:6 end of source
+/// ```
+/// This is useful for debugging the lexer, or seeing how source is split into tokens.
+pub fn dump_tokens(source: Rc<Source>) -> Result<String, Syntax> {
+    let mut dump = String::new();
+
+    for token in lex(source)? {
+        match token.span.line_col() {
+            Some((line, col)) if token.item == Token::End => dump.push_str(&format!(
+                "{}:{} {}\n", line, col, token.item,
+            )),
+            Some((line, col)) => dump.push_str(&format!(
+                "{}:{} {} {:?}\n", line, col, token.item, token.span.contents(),
+            )),
+            None => dump.push_str(&format!("{}\n", token.item)),
+        }
+    }
+
+    Ok(dump)
+}
+
 /// This represents a lexer object.
 /// A lexer takes a source file and lexes it into tokens.
 /// Note that this struct should not be controlled manually,
@@ -56,12 +202,19 @@ pub struct Lexer {
     source: Rc<Source>,
     /// The current lexing offset.
     offset: usize,
+    /// Whether comments are emitted as tokens, rather than skipped.
+    comments: bool,
 }
 
 impl Lexer {
     /// Create a new empty lexer.
     pub fn new(source: &Rc<Source>) -> Lexer {
-        Lexer { source: Rc::clone(source), offset: 0 }
+        Lexer { source: Rc::clone(source), offset: 0, comments: false }
+    }
+
+    /// Create a new empty lexer that emits comments as tokens.
+    pub fn with_comments(source: &Rc<Source>) -> Lexer {
+        Lexer { comments: true, ..Lexer::new(source) }
     }
 
     /// Run the lexer, generating the entire token stream.
@@ -69,41 +222,94 @@ impl Lexer {
         let mut tokens = vec![];
 
         while !self.remaining().is_empty() {
-            // strip preceeding whitespace
-            self.strip();
+            tokens.push(self.token()?);
+        }
 
-            // clear out comments
-            self.offset += Lexer::comment(self.remaining());
-            self.offset += Lexer::multi_comment(self.remaining());
+        tokens.push(Spanned::new(Token::End, Span::point(&self.source, self.source.contents.len())));
 
-            // strip trailing whitespace
-            self.strip();
+        Ok(tokens)
+    }
 
-            // get next token kind, build token
-            let (kind, consumed) = match self.step() {
-                Ok(k)  => k,
-                Err(e) => return Err(
-                    Syntax::error(&e, &Span::point(&self.source, self.offset))
-                ),
-            };
-
-            // annotate it
-            tokens.push(Spanned::new(
-                kind,
-                Span::new(&self.source, self.offset, consumed),
-            ));
-            self.offset += consumed;
+    /// Lexes the next token, skipping any whitespace and comments before it.
+    pub fn token(&mut self) -> Result<Spanned<Token>, Syntax> {
+        // strip preceeding whitespace
+        self.strip();
+
+        // keep comments around if asked to
+        if self.comments {
+            if let Some(comment) = self.comment_token() { return Ok(comment); }
         }
 
-        tokens.push(Spanned::new(Token::End, Span::empty()));
+        // clear out comments
+        self.offset += Lexer::comment(self.remaining());
+        self.offset += Lexer::multi_comment(self.remaining());
 
-        Ok(tokens)
+        // strip trailing whitespace
+        self.strip();
+
+        // get next token kind, build token
+        // errors in string literals point at the opening quote,
+        // rather than at the end of the source where lexing failed,
+        // unless they are inside an embedded expression, see `Lexer::interpolated`
+        let (kind, consumed) = if self.remaining().starts_with('"') {
+            self.interpolated()?
+        } else {
+            match self.step() {
+                Ok(k)  => k,
+                Err(e) => {
+                    let quote = if self.remaining().starts_with("b\"") { 2 } else { 0 };
+                    return Err(Syntax::error(&e, &Span::new(&self.source, self.offset, quote)));
+                },
+            }
+        };
+
+        // annotate it
+        let token = Spanned::new(kind, Span::new(&self.source, self.offset, consumed));
+        self.offset += consumed;
+        Ok(token)
+    }
+
+    /// Lexes a single or multi-line comment into a `Token::Comment`,
+    /// if the remaining source starts with one.
+    fn comment_token(&mut self) -> Option<Spanned<Token>> {
+        let source = self.remaining();
+
+        let (len, text) = match Lexer::comment(source) {
+            0 => {
+                let len = Lexer::multi_comment(source);
+                if len == 0 { return None; }
+                let text = &source[2..len];
+                (len, text.strip_suffix("}-").unwrap_or(text))
+            },
+            len => (len, &source[2..len]),
+        };
+
+        let token = Spanned::new(
+            Token::Comment(text.to_string()),
+            Span::new(&self.source, self.offset, len),
+        );
+        self.offset += len;
+        Some(token)
     }
 
     /// Step the lexer, returning the next token.
     pub fn step(&self) -> Result<Bite, String> {
         let source = self.remaining();
 
+        // only numbers start with a digit,
+        // so errors in number literals are reported directly
+        if source.starts_with(|c: char| c.is_ascii_digit()) {
+            return Lexer::number(source);
+        }
+
+        // likewise, only strings start with a quote,
+        // so that unterminated strings are reported as such
+        if source.starts_with('"') {
+            return self.interpolated().map_err(|e| e.message);
+        } else if source.starts_with("b\"") {
+            return Lexer::bytes(source);
+        }
+
         type Rule = Box<dyn Fn(&str) -> Result<Bite, String>>;
         let rules: Vec<Rule> = vec![
             // higher up in order = higher precedence
@@ -117,9 +323,8 @@ impl Lexer {
             Box::new(Lexer::boolean),
 
             // dynamic
-            Box::new(Lexer::real),
-            Box::new(Lexer::integer),
             Box::new(Lexer::string),
+            Box::new(Lexer::bytes),
 
             // keep this @ the bottom, lmao
             Box::new(Lexer::keyword),
@@ -186,7 +391,7 @@ impl Lexer {
 
         for char in source.chars() {
             match char {
-                n if n.is_digit(10) => len += n.len_utf8(),
+                n if n.is_ascii_digit() => len += n.len_utf8(),
                 _                   => break,
             }
         }
@@ -237,7 +442,9 @@ impl Lexer {
             Err(_) => { return 0; },
         };
 
-        for char in source[len..].chars() {
+        // step through character by character,
+        // so `len` always lands on a character boundary
+        while let Some(char) = source[len..].chars().next() {
             if Lexer::expect(&source[len..], "-{").is_ok() {
                 len += Lexer::multi_comment(&source[len..]);
             } else if let Ok(end) = Lexer::expect(&source[len..], "}-") {
@@ -280,14 +487,32 @@ impl Lexer {
     }
 
     /// Classifies a symbol (i.e. variable name).
+    /// A symbol may end in a single `?` or `!`, as in `empty?` or `reset!`,
+    /// which must directly follow the rest of the symbol.
+    /// So that `x!=y` is still an inequality, a suffix can not be followed by `=`.
+    /// So that `x?` at the end of a line propagates `nil`,
+    /// a `?` followed by a separator, a comment, or the end of the source is not a suffix.
+    /// Note that there is no standalone `!` operator, so `!x` and `x ! y` do not lex.
     pub fn symbol(source: &str) -> Result<Bite, String> {
-        if let symbol @ (Token::Symbol, _) = Lexer::identifier(source)? {
-            Ok(symbol)
-        } else {
-            Err("Expected a symbol".to_string())
+        let len = if let (Token::Symbol, len) = Lexer::identifier(source)? { len } else {
+            return Err("Expected a symbol".to_string());
+        };
+
+        let mut suffix = source[len..].chars();
+        match (suffix.next(), suffix.next()) {
+            (Some('?'), _) if Lexer::ends_line(&source[len + 1..]) => Ok((Token::Symbol, len)),
+            (Some('?'), next) | (Some('!'), next) if next != Some('=') => Ok((Token::Symbol, len + 1)),
+            _ => Ok((Token::Symbol, len)),
         }
     }
 
+    /// Whether nothing but whitespace is left before a separator,
+    /// a comment, or the end of the source.
+    fn ends_line(source: &str) -> bool {
+        let rest = source.trim_start_matches(|c: char| c.is_whitespace() && c != '\n');
+        rest.is_empty() || Lexer::sep(rest).is_ok() || Lexer::comment(rest) > 0
+    }
+
     /// Classifies a label (i.e. data wrapper).
     /// Must start with an uppercase character.
     pub fn label(source: &str) -> Result<Bite, String> {
@@ -344,40 +569,226 @@ impl Lexer {
         Ok((Token::Number(Data::Integer(number)), len))
     }
 
+    /// Matches a number, which is either a real or an integer.
+    /// By default, a number with a decimal point is a real,
+    /// but a suffix can be used to explicitly give the type of a number:
+    /// `3i` is an integer, and `3f` and `3.5f` are reals.
+    /// An integer suffix can not be used on a number with a decimal point,
+    /// and any other suffix, like `3z`, is an error.
+    pub fn number(source: &str) -> Result<Bite, String> {
+        let (data, len) = match Lexer::real(source) {
+            Ok((Token::Number(real), len)) => (real, len),
+            _ => match Lexer::integer(source)? {
+                (Token::Number(integer), len) => (integer, len),
+                _ => unreachable!("Expected an integer"),
+            },
+        };
+
+        let suffix = match Lexer::identifier(&source[len..]) {
+            Ok((_, suffix_len)) => &source[len..len + suffix_len],
+            Err(_) => return Ok((Token::Number(data), len)),
+        };
+
+        let data = match (suffix, data) {
+            ("i", Data::Integer(n)) => Data::Integer(n),
+            ("f", Data::Integer(n)) => Data::Real(n as f64),
+            ("f", Data::Real(n))    => Data::Real(n),
+            ("i", Data::Real(_))    => return Err(format!(
+                "The number '{}' has a decimal point, so it can not be an integer",
+                &source[..len],
+            )),
+            (other, _) => return Err(format!(
+                "Invalid suffix '{}' on the number '{}', expected 'i' for an integer or 'f' for a real",
+                other, &source[..len],
+            )),
+        };
+
+        Ok((Token::Number(data), len + suffix.len()))
+    }
+
+    /// Converts an escape code in a string to the character it stands for,
+    /// given the source right after the `\`.
+    /// Returns the character, and the length of the escape code, not including the `\`.
+    /// A unicode escape gives the codepoint of a character in hex, like `\u{1F426}`.
+    pub fn escape(source: &str) -> Result<(char, usize), String> {
+        // TODO: add more escape codes
+        let c = source.chars().next()
+            .ok_or("Unterminated string literal, expected a closing quote")?;
+
+        let escaped = match c {
+            '"'  => '"',
+            '\\' => '\\',
+            'n'  => '\n',
+            't'  => '\t',
+            'r'  => '\r',
+            'u'  => return Lexer::unicode_escape(source),
+            o    => return Err(format!("Unknown escape code '\\{}'", o)),
+        };
+
+        Ok((escaped, c.len_utf8()))
+    }
+
+    /// Converts a unicode escape, like `u{1F426}`, to a character,
+    /// see `Lexer::escape`.
+    /// The codepoint must be between 1 and 6 hex digits,
+    /// and be a valid unicode scalar value.
+    fn unicode_escape(source: &str) -> Result<(char, usize), String> {
+        let len = Lexer::expect(source, "u{")
+            .map_err(|_| "Expected a '{' after '\\u' in a unicode escape".to_string())?;
+
+        let digits = source[len..].find(|c: char| !c.is_ascii_hexdigit())
+            .map_or(&source[len..], |end| &source[len..len + end]);
+
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(format!(
+                "Expected between 1 and 6 hex digits in a unicode escape, found '\\u{{{}}}'",
+                digits,
+            ));
+        }
+
+        Lexer::expect(&source[len + digits.len()..], "}")
+            .map_err(|_| "Expected a closing '}' in a unicode escape".to_string())?;
+
+        let codepoint = u32::from_str_radix(digits, 16).unwrap();
+        let c = char::from_u32(codepoint)
+            .ok_or_else(|| format!("'\\u{{{}}}' is not a valid unicode codepoint", digits))?;
+
+        Ok((c, len + digits.len() + 1))
+    }
+
+    /// Matches a string that may embed expressions, like `"x is ${x + 1}"`,
+    /// converting escapes.
+    /// Each embedded expression is lexed in place, up to the matching `}`,
+    /// so it may itself contain braces and strings.
+    /// A literal `${` is written as `$${`.
+    /// Strings without embedded expressions are plain `Token::String`s,
+    /// otherwise the string is split into a `Token::Interpolation`.
+    /// Errors in the string itself point at the opening quote,
+    /// errors in an embedded expression point at where they were found.
+    pub fn interpolated(&self) -> Result<Bite, Syntax> {
+        let source = self.remaining();
+        let quote  = Span::new(&self.source, self.offset, 1);
+        let error  = |message: String| Syntax::error(&message, &quote);
+        let mut len    = Lexer::expect(source, "\"").map_err(error)?;
+        let mut text   = "".to_string();
+        let mut pieces = vec![];
+
+        while let Some(c) = source[len..].chars().next() {
+            let rest = &source[len..];
+            len += c.len_utf8();
+
+            match c {
+                '\\' => {
+                    let (escaped, consumed) = Lexer::escape(&source[len..]).map_err(error)?;
+                    text.push(escaped);
+                    len += consumed;
+                },
+                '$' if rest.starts_with("$${") => {
+                    text.push_str("${");
+                    len += 2;
+                },
+                '$' if rest.starts_with("${") => {
+                    len += 1;
+                    let (tokens, consumed) = self.embedded(self.offset + len)?;
+                    len += consumed;
+
+                    if !text.is_empty() { pieces.push(Piece::Text(std::mem::take(&mut text))); }
+                    pieces.push(Piece::Expression(tokens));
+                },
+                '\"' if pieces.is_empty() => return Ok((Token::String(Data::String(text)), len)),
+                '\"' => {
+                    if !text.is_empty() { pieces.push(Piece::Text(text)); }
+                    return Ok((Token::Interpolation(pieces), len));
+                },
+                c => text.push(c),
+            }
+        }
+
+        Err(error("Unterminated string literal, expected a closing quote".to_string()))
+    }
+
+    /// Lexes the tokens of an expression embedded in a string, starting at an offset,
+    /// up to and including the `}` that closes it.
+    /// Returns the tokens, followed by a `Token::End`,
+    /// and the length of source consumed.
+    /// Errors point at the opening quote of the string,
+    /// unless they are found while lexing a token of the expression.
+    fn embedded(&self, offset: usize) -> Result<(Vec<Spanned<Token>>, usize), Syntax> {
+        let quote = Span::new(&self.source, self.offset, 1);
+        let mut lexer = Lexer::new(&self.source);
+        lexer.offset = offset;
+
+        let mut tokens = vec![];
+        let mut depth = 0;
+
+        loop {
+            lexer.strip();
+            if lexer.remaining().is_empty() {
+                return Err(Syntax::error(
+                    "Unterminated interpolation in string literal, expected a closing '}'",
+                    &quote,
+                ));
+            }
+
+            let token = lexer.token()?;
+            match token.item {
+                Token::OpenBracket => depth += 1,
+                Token::CloseBracket if depth == 0 => break,
+                Token::CloseBracket => depth -= 1,
+                _ => (),
+            }
+            tokens.push(token);
+        }
+
+        if tokens.is_empty() {
+            return Err(Syntax::error("Expected an expression between '${' and '}' in a string", &quote));
+        }
+
+        // the end of the expression is the closing `}`
+        tokens.push(Spanned::new(Token::End, Span::point(&self.source, lexer.offset - 1)));
+        Ok((tokens, lexer.offset - offset))
+    }
+
     /// Matches a string, converting escapes.
+    /// This does not handle embedded expressions, see `Lexer::interpolated`.
     pub fn string(source: &str) -> Result<Bite, String> {
         // TODO: read through the rust compiler and figure our how they do this
         // look into parse_str_lit
 
         let mut len    = 0;
-        let mut escape = false;
         let mut string = "".to_string();
 
         len += Lexer::expect(source, "\"")?;
 
-        for c in source[len..].chars() {
+        while let Some(c) = source[len..].chars().next() {
             len += c.len_utf8();
-            if escape {
-                escape = false;
-                // TODO: add more escape codes
-                string.push(match c {
-                    '"'  => '"',
-                    '\\' => '\\',
-                    'n'  => '\n',
-                    't'  => '\t',
-                    'r'  => '\r',
-                    o    => return Err(format!("Unknown escape code '\\{}'", o)),
-                })
-            } else {
-                match c {
-                    '\\' => escape = true,
-                    '\"' => return Ok((Token::String(Data::String(string)), len)),
-                    c    => string.push(c),
-                }
+            match c {
+                '\\' => {
+                    let (escaped, consumed) = Lexer::escape(&source[len..])?;
+                    string.push(escaped);
+                    len += consumed;
+                },
+                '\"' => return Ok((Token::String(Data::String(string)), len)),
+                c    => string.push(c),
             }
         }
 
-        Err("Unexpected EOF while parsing string literal".to_string())
+        Err("Unterminated string literal, expected a closing quote".to_string())
+    }
+
+    /// Matches a byte string, i.e. a string prefixed with a `b`: `b"..."`.
+    /// Byte strings use the same escape codes as strings,
+    /// and hold the UTF-8 encoding of their contents.
+    pub fn bytes(source: &str) -> Result<Bite, String> {
+        let len = Lexer::expect(source, "b")?;
+
+        match Lexer::string(&source[len..])? {
+            (Token::String(Data::String(s)), consumed) => Ok((
+                Token::Bytes(Data::Bytes(s.into_bytes())),
+                len + consumed,
+            )),
+            _ => unreachable!("Expected a string"),
+        }
     }
 
     /// Matches a literal boolean.
@@ -435,9 +846,10 @@ mod test {
     #[test]
     fn empty() {
         // no source code? no tokens!
-        let result = lex(Source::source(""));
+        let source = Source::source("");
+        let result = lex(source.clone());
         let target: Result<Vec<Spanned<Token>>, Syntax> =
-            Ok(vec![Spanned::new(Token::End, Span::empty())]);
+            Ok(vec![Spanned::new(Token::End, Span::point(&source, 0))]);
 
         assert_eq!(result, target);
     }
@@ -450,7 +862,7 @@ mod test {
             Spanned::new(Token::Symbol,                       Span::new(&source, 0, 4)),
             Spanned::new(Token::Assign,                       Span::new(&source, 5, 1)),
             Spanned::new(Token::Boolean(Data::Boolean(true)), Span::new(&source, 7, 4)),
-            Spanned::new(Token::End,                          Span::empty()),
+            Spanned::new(Token::End,                          Span::point(&source, 11)),
         ];
 
         assert_eq!(lex(source), Ok(result));
@@ -463,7 +875,7 @@ mod test {
         let result = vec![
             Spanned::new(Token::Boolean(Data::Boolean(true)), Span::new(&source, 2, 4)),
             Spanned::new(Token::Sep,                          Span::new(&source, 8, 3)),
-            Spanned::new(Token::End,                          Span::empty()),
+            Spanned::new(Token::End,                          Span::point(&source, 11)),
 
         ];
 
@@ -486,7 +898,7 @@ mod test {
             Spanned::new(Token::Symbol,                       Span::new(&source, 17, 5)),
             Spanned::new(Token::Sep,                          Span::new(&source, 22, 1)),
             Spanned::new(Token::CloseBracket,                 Span::new(&source, 23, 1)),
-            Spanned::new(Token::End,                          Span::empty()),
+            Spanned::new(Token::End,                          Span::point(&source, 24)),
         ];
 
         assert_eq!(lex(source), Ok(result));
@@ -507,7 +919,7 @@ mod test {
             Spanned::new(Token::Symbol,                                   Span::new(&source, 28, 8)),
             Spanned::new(Token::String(Data::String("heck".to_string())), Span::new(&source, 37, 6)),
             Spanned::new(Token::CloseParen,                               Span::new(&source, 43, 1)),
-            Spanned::new(Token::End,                          Span::empty()),
+            Spanned::new(Token::End,                          Span::point(&source, 44)),
         ];
 
         assert_eq!(lex(source), Ok(result));
@@ -540,6 +952,39 @@ mod test {
     #[test]
     fn symbol() {
         if !test_literal("orchard", Token::Symbol, 7) { panic!() }
+        // keywords can start symbols
+        if !test_literal("done", Token::Symbol, 4) { panic!() }
+    }
+
+    #[test]
+    fn symbol_suffix() {
+        if !test_literal("empty? xs", Token::Symbol, 6) { panic!() }
+        if !test_literal("reset! x", Token::Symbol, 6) { panic!() }
+        // a `?` ending a line propagates `nil` instead
+        if !test_literal("x?", Token::Symbol, 1) { panic!() }
+        if !test_literal("x? \n", Token::Symbol, 1) { panic!() }
+        if !test_literal("x?; y", Token::Symbol, 1) { panic!() }
+        if !test_literal("x? -- note", Token::Symbol, 1) { panic!() }
+        // only a single suffix is allowed
+        if !test_literal("what?!", Token::Symbol, 5) { panic!() }
+        // `!=` is still an inequality
+        if !test_literal("x!=y", Token::Symbol, 1) { panic!() }
+    }
+
+    #[test]
+    fn bang_not_operator() {
+        // a `!` has to be the suffix of a symbol
+        let unary = Source::source("!x");
+        assert!(lex(unary).is_err());
+
+        let binary = Source::source("x ! y");
+        assert!(lex(binary).is_err());
+
+        let source = Source::source("x!=y");
+        let tokens = lex(source).unwrap().into_iter()
+            .map(|t| t.item)
+            .collect::<Vec<Token>>();
+        assert_eq!(tokens, vec![Token::Symbol, Token::NotEqual, Token::Symbol, Token::End]);
     }
 
     #[test]
@@ -572,6 +1017,69 @@ mod test {
         ) { panic!() }
     }
 
+    #[test]
+    fn number_suffix() {
+        if !test_literal("3i",   Token::Number(Data::Integer(3)), 2) { panic!() }
+        if !test_literal("3f",   Token::Number(Data::Real(3.0)),  2) { panic!() }
+        if !test_literal("3.5f", Token::Number(Data::Real(3.5)),  4) { panic!() }
+        // a suffix must directly follow the number
+        if !test_literal("3 f",  Token::Number(Data::Integer(3)), 1) { panic!() }
+        // ranges are not mistaken for suffixes
+        if !test_literal("1..2", Token::Number(Data::Integer(1)), 1) { panic!() }
+    }
+
+    #[test]
+    fn invalid_number_suffix() {
+        let result = Lexer::new(&Source::source("3z")).step();
+        assert_eq!(
+            result,
+            Err("Invalid suffix 'z' on the number '3', expected 'i' for an integer or 'f' for a real".to_string()),
+        );
+
+        let result = Lexer::new(&Source::source("3.5i")).step();
+        assert_eq!(
+            result,
+            Err("The number '3.5' has a decimal point, so it can not be an integer".to_string()),
+        );
+
+        let error = lex(Source::source("x = 12zz")).unwrap_err();
+        assert_eq!(error.span.line_col(), Some((1, 5)));
+    }
+
+    #[test]
+    fn dump() {
+        let source = Source::source("x = \"hi\"\nf [x, 2.5]");
+        assert_eq!(dump_tokens(source).unwrap(), "\
+            1:1 a symbol \"x\"\n\
+            1:3 an assignment \"=\"\n\
+            1:5 a string \"\\\"hi\\\"\"\n\
+            1:9 a separator \"\\n\"\n\
+            2:1 a symbol \"f\"\n\
+            2:3 an opening square bracket \"[\"\n\
+            2:4 a symbol \"x\"\n\
+            2:5 a tuple \",\"\n\
+            2:7 a number \"2.5\"\n\
+            2:10 a closing square bracket \"]\"\n\
+            2:11 end of source\n\
+        ");
+
+        assert!(dump_tokens(Source::source("\"open")).is_err());
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let source = Source::source("x = \"abc");
+        let error = lex(source.clone()).unwrap_err();
+        assert_eq!(error.message, "Unterminated string literal, expected a closing quote");
+        assert_eq!(error.span, Span::new(&source, 4, 1));
+        assert_eq!(error.span.contents(), "\"");
+
+        let source = Source::source("b\"abc\nx");
+        let error = lex(source.clone()).unwrap_err();
+        assert_eq!(error.message, "Unterminated string literal, expected a closing quote");
+        assert_eq!(error.span, Span::new(&source, 0, 2));
+    }
+
     #[test]
     fn string() {
         let source = "\"heck\"";
@@ -596,10 +1104,162 @@ mod test {
         ) { panic!() }
     }
 
+    #[test]
+    fn bytes() {
+        let source = "b\"hi\\n\"";
+        if !test_literal(
+            source,
+            Token::Bytes(Data::Bytes(vec![b'h', b'i', b'\n'])),
+            source.len(),
+        ) { panic!() }
+
+        // a symbol named b followed by a string is not a byte string
+        let source = Source::source("b \"hi\"");
+        let tokens = lex(source).unwrap();
+        assert_eq!(tokens[0].item, Token::Symbol);
+    }
+
+    #[test]
+    fn unicode() {
+        let source = Source::source("café = \"🐦 tweet\"\n-{ -{ ü }- ö }- café");
+        let tokens = lex(source.clone()).unwrap();
+
+        let expected = vec![
+            (Token::Symbol, "café"),
+            (Token::Assign, "="),
+            (Token::String(Data::String("🐦 tweet".to_string())), "\"🐦 tweet\""),
+            (Token::Sep, "\n"),
+            (Token::Symbol, "café"),
+        ];
+
+        for ((token, contents), spanned) in expected.into_iter().zip(tokens.iter()) {
+            assert_eq!(spanned.item, token);
+            assert_eq!(spanned.span.contents(), contents);
+        }
+
+        // columns are counted in characters
+        assert_eq!(tokens[1].span.line_col(), Some((1, 6)));
+        assert_eq!(tokens[4].span.line_col(), Some((2, 17)));
+    }
+
+    #[test]
+    fn range() {
+        let source = Source::source("1..10");
+        let result = vec![
+            Spanned::new(Token::Number(Data::Integer(1)),  Span::new(&source, 0, 1)),
+            Spanned::new(Token::Range,                     Span::new(&source, 1, 2)),
+            Spanned::new(Token::Number(Data::Integer(10)), Span::new(&source, 3, 2)),
+            Spanned::new(Token::End,                       Span::point(&source, 5)),
+        ];
+
+        assert_eq!(lex(source), Ok(result));
+    }
+
     #[test]
     fn comma() {
         let source = Source::source("heck\\ man");
         let tokens = lex(source.clone());
         assert_eq!(tokens, Err(Syntax::error("Unexpected token", &Span::new(&source, 4, 0))));
     }
+
+    #[test]
+    fn relex_matches_full() {
+        // (before, after, range of after that changed)
+        let edits = [
+            ("x = 1\ny = x + 2\n",         "x = 1\ny = x + 20 * 3\n",     (14, 20)),
+            ("x = 1\ny = x + 2\n",         "z = 1\ny = x + 2\n",          (0, 1)),
+            ("x = 1\ny = x + 2\n",         "x = 1\n",                      (6, 6)),
+            ("hello = 1\n",                 "hello_there = 1\n",            (5, 11)),
+            ("a = \"b\"\nc = d\n",       "a = \"b\nc = d\"\n",        (6, 6)),
+            ("a = 1 -- note\nb = 2\n",     "a = 1 -- a note\nb = 2\n",    (9, 11)),
+            ("a = 1\n-- b = 2\nc = 3\n",  "a = 1\nb = 2\nc = 3\n",      (6, 6)),
+            ("f = x -> x\n",                "f = x - x\n",                  (7, 7)),
+            ("a = 1\nb = \"x${a}y\"\n",  "zz = 0\na = 1\nb = \"x${a}y\"\n", (0, 7)),
+            ("b = \"x${a}y\"\nc = 1\n",  "b = \"x${a}y\"\nc = 12\n",   (18, 19)),
+        ];
+
+        for (before, after, (start, end)) in edits.iter() {
+            let tokens = lex(Source::source(before)).unwrap();
+            let source = Source::source(after);
+            let relexed = relex(source.clone(), &tokens, *start..*end);
+            assert_eq!(relexed, lex(source), "relexing {:?} as {:?}", before, after);
+        }
+    }
+
+    #[test]
+    fn comments_kept() {
+        let source = Source::source("x = 1 -- one\n-{ two -{ three }- }-y\n");
+        let result = vec![
+            Spanned::new(Token::Symbol,                                  Span::new(&source, 0, 1)),
+            Spanned::new(Token::Assign,                                  Span::new(&source, 2, 1)),
+            Spanned::new(Token::Number(Data::Integer(1)),                Span::new(&source, 4, 1)),
+            Spanned::new(Token::Comment(" one".to_string()),             Span::new(&source, 6, 6)),
+            Spanned::new(Token::Sep,                                     Span::new(&source, 12, 1)),
+            Spanned::new(Token::Comment(" two -{ three }- ".to_string()), Span::new(&source, 13, 21)),
+            Spanned::new(Token::Symbol,                                  Span::new(&source, 34, 1)),
+            Spanned::new(Token::Sep,                                     Span::new(&source, 35, 1)),
+            Spanned::new(Token::End,                                     Span::point(&source, 36)),
+        ];
+
+        assert_eq!(lex_with_comments(source.clone()), Ok(result));
+
+        // comments are still skipped when lexing normally
+        assert!(lex(source).unwrap().iter().all(|t| !matches!(t.item, Token::Comment(_))));
+    }
+
+    #[test]
+    fn interpolation() {
+        let source = Source::source("\"x is ${x + {1}}!\"");
+        let pieces = vec![
+            Piece::Text("x is ".to_string()),
+            Piece::Expression(vec![
+                Spanned::new(Token::Symbol,                   Span::new(&source, 8, 1)),
+                Spanned::new(Token::Add,                      Span::new(&source, 10, 1)),
+                Spanned::new(Token::OpenBracket,              Span::new(&source, 12, 1)),
+                Spanned::new(Token::Number(Data::Integer(1)), Span::new(&source, 13, 1)),
+                Spanned::new(Token::CloseBracket,             Span::new(&source, 14, 1)),
+                Spanned::new(Token::End,                      Span::point(&source, 15)),
+            ]),
+            Piece::Text("!".to_string()),
+        ];
+
+        assert_eq!(lex(source.clone()), Ok(vec![
+            Spanned::new(Token::Interpolation(pieces), Span::new(&source, 0, 18)),
+            Spanned::new(Token::End,                   Span::point(&source, 18)),
+        ]));
+
+        // an escaped interpolation is just a string
+        let source = Source::source("\"$${x} costs $5\"");
+        assert_eq!(lex(source.clone()), Ok(vec![
+            Spanned::new(Token::String(Data::String("${x} costs $5".to_string())), Span::new(&source, 0, 16)),
+            Spanned::new(Token::End, Span::point(&source, 16)),
+        ]));
+
+        // errors in an embedded expression point at where they were found
+        let source = Source::source("\"x is ${x + 3z}\"");
+        let error = lex(source.clone()).unwrap_err();
+        assert!(error.message.starts_with("Invalid suffix 'z' on the number '3'"));
+        assert_eq!(error.span, Span::new(&source, 12, 0));
+
+        // errors in the string itself point at the opening quote
+        let source = Source::source("x = \"x is ${}\"");
+        let error = lex(source.clone()).unwrap_err();
+        assert_eq!(error.message, "Expected an expression between '${' and '}' in a string");
+        assert_eq!(error.span, Span::new(&source, 4, 1));
+    }
+
+    #[test]
+    fn unicode_escape() {
+        assert_eq!(
+            Lexer::string("\"\\u{1F600} and \\u{e9}\""),
+            Ok((Token::String(Data::String("😀 and é".to_string())), 22)),
+        );
+
+        let error = |source: &str| lex(Source::source(source)).unwrap_err().message;
+        assert_eq!(error("\"\\u{110000}\""), "'\\u{110000}' is not a valid unicode codepoint");
+        assert_eq!(error("\"\\u{D800}\""), "'\\u{D800}' is not a valid unicode codepoint");
+        assert_eq!(error("\"\\u{}\""), "Expected between 1 and 6 hex digits in a unicode escape, found '\\u{}'");
+        assert_eq!(error("\"\\u{1F600\""), "Expected a closing '}' in a unicode escape");
+        assert_eq!(error("\"\\u1F600\""), "Expected a '{' after '\\u' in a unicode escape");
+    }
 }
@@ -7,18 +7,22 @@
 //!
 //! 1. Tokens:   `lex.rs`
 //! 2. Absrtact ST: `parse.rs`
+//!    (Imports are then resolved, in `import.rs`)
 //! 3. Concrete ST: `desugar.rs`
 //! 4. Scoped ST:  `hoist.rs`
 //! 5. Bytecode: `gen.rs`
+//!    (Bytecode may then optionally be optimized, in `peephole.rs`)
 //!
-//! Note that more steps (e.g. ones applying typechecking operations, optimization passes, etc.)
+//! Note that more steps (e.g. ones applying typechecking operations, more optimization passes, etc.)
 //! may be implemented in the future.
 
 pub mod lex;
 pub mod parse;
+pub mod import;
 pub mod desugar;
 pub mod hoist;
 pub mod gen;
+pub mod peephole;
 
 pub mod token;
 pub mod ast; // high level pre-macro IR
@@ -27,9 +31,13 @@ pub mod cst; // post-macro IR
 pub mod sst; // hoisted IR
 
 pub mod syntax;
+pub mod format;
 
-pub use lex::lex;
+pub use lex::{lex, lex_with_comments, relex, dump_tokens};
 pub use parse::parse;
+pub use import::import;
 pub use desugar::desugar;
-pub use hoist::hoist;
-pub use gen::gen;
+pub use hoist::{hoist, hoist_with_globals};
+pub use gen::{gen, gen_with_globals, gen_with_warnings};
+pub use peephole::peephole;
+pub use format::format_ast;
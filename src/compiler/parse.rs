@@ -10,8 +10,8 @@ use crate::common::{
 
 use crate::compiler::{
     syntax::Syntax,
-    token::Token,
-    ast::{AST, ASTPattern, ArgPattern},
+    token::{Token, Piece},
+    ast::{AST, ASTPattern, ArgPattern, Fragment},
 };
 
 /// Simple function that parses a token stream into an AST.
@@ -32,10 +32,20 @@ pub fn parse(tokens: Vec<Spanned<Token>>) -> Result<Spanned<AST>, Syntax> {
 pub enum Prec {
     None = 0,
     Assign,
+    Where,
     Pair,
+    Named,
     Lambda,
+    Pipe,
 
+    Or,
+    And,
     Logic,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Range,
+    Shift,
 
     AddSub,
     MulDiv,
@@ -43,6 +53,7 @@ pub enum Prec {
 
     Compose, // TODO: where should this be, precedence-wise?
     Call,
+    Index,
     End,
 }
 
@@ -68,12 +79,35 @@ impl Prec {
 pub struct Parser {
     tokens: Vec<Spanned<Token>>,
     index:  usize,
+    /// Whether a block may be used as an argument to a function call.
+    /// This is disabled while parsing the head of a `for` loop,
+    /// so that `for x in xs { ... }` isn't parsed as the call `xs { ... }`.
+    block_args: bool,
+    /// Whether `in` may be used as the membership operator, i.e. `x in xs`.
+    /// This is disabled while parsing the pattern of a `for` loop,
+    /// so that the `in` in `for x in xs { ... }` ends the pattern.
+    in_operator: bool,
+    /// Whether `for` and `if` end the current expression.
+    /// This is enabled while parsing a list,
+    /// so that `[x for x in xs if x > 1]` is parsed as a list comprehension.
+    comprehension: bool,
+    /// Whether `do` followed by a block starts a do block.
+    /// This is disabled while parsing the pattern of a syntax definition,
+    /// so that `do` is bound like any other name in `syntax 'loop do { ... }`.
+    do_blocks: bool,
 }
 
 impl Parser {
     /// Create a new `parser`.
     pub fn new(tokens: Vec<Spanned<Token>>) -> Parser {
-        Parser { tokens, index: 0 }
+        Parser {
+            tokens,
+            index:         0,
+            block_args:    true,
+            in_operator:   true,
+            comprehension: false,
+            do_blocks:     true,
+        }
     }
 
     // Cookie Monster's Helper Functions:
@@ -113,6 +147,13 @@ impl Parser {
         &self.tokens[self.index]
     }
 
+    /// Returns whether the current token directly follows the previous one,
+    /// i.e. whether there is no whitespace between them.
+    pub fn adjacent(&self) -> bool {
+        self.index > 0
+        && self.tokens[self.index - 1].span.end() == self.tokens[self.index].span.offset
+    }
+
     /// Returns the first non-Sep token.
     pub fn skip(&mut self) -> &Spanned<Token> {
         self.sep();
@@ -147,12 +188,20 @@ impl Parser {
 
     /// Looks at the current token and parses an infix expression
     pub fn rule_prefix(&mut self) -> Result<Spanned<AST>, Syntax> {
-        match self.skip().item {
+        self.sep();
+        match self.current().item {
             Token::End         => Ok(Spanned::new(AST::Block(vec![]), Span::empty())),
 
             Token::Syntax      => self.syntax(),
             Token::OpenParen   => self.group(),
             Token::OpenBracket => self.block(),
+            Token::OpenSquare  => self.list(),
+            Token::For         => self.for_loop(),
+            Token::Try         => self.try_catch(),
+            Token::Import      => self.import(),
+            Token::Const       => self.constant(),
+            Token::Return      => self.return_expr(),
+            Token::Symbol if self.is_do_block() => self.do_block(),
             Token::Symbol      => self.symbol(),
             Token::Magic       => self.magic(),
             Token::Label       => self.label(),
@@ -160,9 +209,12 @@ impl Parser {
             Token::Sub         => self.neg(),
 
             Token::Unit
+            | Token::Nil
             | Token::Number(_)
             | Token::String(_)
+            | Token::Bytes(_)
             | Token::Boolean(_) => self.literal(),
+            Token::Interpolation(_) => self.interpolation(),
 
             Token::Sep => unreachable!(),
             _          => Err(Syntax::error("Expected an expression", &self.current().span)),
@@ -171,11 +223,27 @@ impl Parser {
 
     /// Looks at the current token and parses the right side of any infix expressions.
     pub fn rule_infix(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
-        match self.skip().item {
+        self.skip();
+        match self.logic_word() {
+            Some("and") => return self.and(left),
+            Some("or")  => return self.or(left),
+            _ => (),
+        }
+
+        match self.current().item {
             Token::Assign  => self.assign(left),
+
+              Token::AddAssign
+            | Token::SubAssign
+            | Token::MulAssign
+            | Token::DivAssign
+            | Token::RemAssign => self.compound_assign(left),
             Token::Lambda  => self.lambda(left),
+            Token::Where   => self.where_clause(left),
             Token::Pair    => self.pair(left),
+            Token::Colon   => self.named_argument(left),
             Token::Compose => self.compose(left),
+            Token::Pipe    => self.pipe(left),
 
             Token::Add => self.add(left),
             Token::Sub => self.sub(left),
@@ -184,7 +252,19 @@ impl Parser {
             Token::Rem => self.rem(left),
             Token::Pow => self.pow(left),
 
+            Token::BitAnd => self.bit_and(left),
+            Token::BitOr  => self.bit_or(left),
+            Token::BitXor => self.bit_xor(left),
+            Token::ShiftLeft  => self.shift_left(left),
+            Token::ShiftRight => self.shift_right(left),
+
             Token::Equal => self.equal(left),
+            Token::NotEqual => self.not_equal(left),
+            Token::Range => self.range(left),
+            Token::In    => self.contains(left),
+
+            Token::OpenSquare => if self.adjacent() { self.index(left) } else { self.call(left) },
+            Token::Question   => self.propagate(left),
 
             Token::End => Err(self.unexpected()),
             Token::Sep => unreachable!(),
@@ -200,12 +280,29 @@ impl Parser {
 
         let prec = match next {
             // infix
-            Token::Assign  => Prec::Assign,
+              Token::Assign
+            | Token::AddAssign
+            | Token::SubAssign
+            | Token::MulAssign
+            | Token::DivAssign
+            | Token::RemAssign => Prec::Assign,
             Token::Lambda  => Prec::Lambda,
+            Token::Where   => Prec::Where,
             Token::Pair    => Prec::Pair,
+            Token::Colon   => Prec::Named,
             Token::Compose => Prec::Compose,
+            Token::Pipe    => Prec::Pipe,
+
+              Token::Equal
+            | Token::NotEqual => Prec::Logic,
+            Token::In if self.in_operator => Prec::Logic,
+            Token::Range => Prec::Range,
 
-            Token::Equal => Prec::Logic,
+            Token::BitOr  => Prec::BitOr,
+            Token::BitXor => Prec::BitXor,
+            Token::BitAnd => Prec::BitAnd,
+              Token::ShiftLeft
+            | Token::ShiftRight => Prec::Shift,
 
               Token::Add
             | Token::Sub => Prec::AddSub,
@@ -219,12 +316,40 @@ impl Parser {
             // postfix
               Token::End
             | Token::CloseParen
-            | Token::CloseBracket => Prec::End,
+            | Token::CloseBracket
+            | Token::CloseSquare
+            | Token::In => Prec::End,
+
+            // comments are only lexed for tooling, never parsed
+            Token::Comment(_) => Prec::End,
+
+            // `xs[i]` is an index, `xs [i]` is a call
+            Token::OpenSquare if !sep && self.adjacent() => Prec::Index,
+            Token::Question => Prec::Index,
+
+            // a block can't be passed to a call in the head of a for loop
+            Token::OpenBracket if !self.block_args => Prec::End,
+
+            // `and` and `or` are only operators between two expressions,
+            // elsewhere they're symbols like any other
+            Token::Symbol if !sep && self.logic_word() == Some("and") => Prec::And,
+            Token::Symbol if !sep && self.logic_word() == Some("or")  => Prec::Or,
+
+            // the parts of a list comprehension are separated by `for` and `if`
+            Token::For if self.comprehension => Prec::End,
+            Token::Symbol if self.comprehension && self.is_filter() => Prec::End,
 
             // prefix
               Token::OpenParen
             | Token::OpenBracket
+            | Token::OpenSquare
+            | Token::For
+            | Token::Try
+            | Token::Import
+            | Token::Const
+            | Token::Return
             | Token::Unit
+            | Token::Nil
             | Token::Syntax
             | Token::Magic
             | Token::Symbol
@@ -232,6 +357,8 @@ impl Parser {
             | Token::Label
             | Token::Number(_)
             | Token::String(_)
+            | Token::Interpolation(_)
+            | Token::Bytes(_)
             | Token::Boolean(_) => Prec::Call,
 
             Token::Sep => unreachable!(),
@@ -274,14 +401,14 @@ impl Parser {
         // nothing is more permanant, but
         // temporary workaround until prelude
         let name = symbol.span.contents();
-        if name == "println" {
+        if name == "println" || name == "error" {
             return Ok(Spanned::new(
                 AST::lambda(
                     Spanned::new(ASTPattern::Symbol("n".into()), Span::empty()),
                     Spanned::new(AST::ffi(
-                        "println",
+                        &name,
                         Spanned::new(AST::Symbol("n".into()), Span::empty()),
-                    ), Span::empty()),
+                    ), symbol.span.clone()),
                 ),
                 symbol.span.clone(),
             ));
@@ -307,8 +434,10 @@ impl Parser {
 
         let leaf = match token {
             Token::Unit       => AST::Data(Data::Unit),
+            Token::Nil        => AST::Data(Data::Nil),
             Token::Number(n)  => AST::Data(n.clone()),
             Token::String(s)  => AST::Data(s.clone()),
+            Token::Bytes(b)   => AST::Data(b.clone()),
             Token::Boolean(b) => AST::Data(b.clone()),
             unexpected => return Err(Syntax::error(
                 &format!("Expected a literal, found {}", unexpected),
@@ -319,15 +448,132 @@ impl Parser {
         Ok(Spanned::new(leaf, span.clone()))
     }
 
+    /// Constructs the AST for an interpolated string, like `"x is ${x + 1}"`.
+    /// Each embedded expression is parsed on its own,
+    /// the string is lowered into concatenations when desugaring.
+    pub fn interpolation(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let Spanned { item: token, span } = self.advance().clone();
+        let pieces = match token {
+            Token::Interpolation(pieces) => pieces,
+            _ => unreachable!("Expected an interpolated string"),
+        };
+
+        let mut fragments = vec![];
+        for piece in pieces {
+            fragments.push(match piece {
+                Piece::Text(text) => Fragment::Text(text),
+                Piece::Expression(tokens) => {
+                    let inner = Span::combine(&tokens[0].span, &tokens[tokens.len() - 2].span);
+                    let mut parser = Parser::new(tokens);
+                    let expression = match parser.body(Token::End)? {
+                        // a single expression is not wrapped in a block
+                        AST::Block(mut expressions) if expressions.len() == 1 => expressions.pop().unwrap(),
+                        expression => Spanned::new(expression, inner),
+                    };
+                    parser.consume(Token::End)?;
+                    Fragment::Expression(expression)
+                },
+            });
+        }
+
+        Ok(Spanned::new(AST::Interpolation(fragments), span))
+    }
+
     /// Constructs the ast for a group,
     /// i.e. an expression between parenthesis.
     pub fn group(&mut self) -> Result<Spanned<AST>, Syntax> {
         let start = self.consume(Token::OpenParen)?.span.clone();
+        let block_args    = mem::replace(&mut self.block_args, true);
+        let comprehension = mem::replace(&mut self.comprehension, false);
         let ast   = self.expression(Prec::None.associate_left(), true)?;
+        self.block_args    = block_args;
+        self.comprehension = comprehension;
         let end   = self.consume(Token::CloseParen)?.span.clone();
         Ok(Spanned::new(AST::group(ast), Span::combine(&start, &end)))
     }
 
+    /// Constructs the ast for a list,
+    /// i.e. comma-separated expressions between square brackets,
+    /// or a list comprehension, see `Parser::comprehension`.
+    pub fn list(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::OpenSquare)?.span.clone();
+        let block_args    = mem::replace(&mut self.block_args, true);
+        let comprehension = mem::replace(&mut self.comprehension, true);
+
+        let items = if self.skip().item == Token::CloseSquare { vec![] } else {
+            let ast = self.expression(Prec::None.associate_left(), true)?;
+            if self.skip().item == Token::For {
+                let list = self.comprehension(start, ast);
+                self.block_args    = block_args;
+                self.comprehension = comprehension;
+                return list;
+            }
+
+            match ast.item {
+                AST::Tuple(t) => t,
+                _ => vec![ast],
+            }
+        };
+
+        self.block_args    = block_args;
+        self.comprehension = comprehension;
+        let end = self.consume(Token::CloseSquare)?.span.clone();
+        Ok(Spanned::new(AST::List(items), Span::combine(&start, &end)))
+    }
+
+    /// Returns `and` or `or` if the current token is that word, as a symbol.
+    fn logic_word(&self) -> Option<&'static str> {
+        let token = self.draw();
+        if token.item != Token::Symbol { return None; }
+        ["and", "or"].iter().copied().find(|w| token.span.contents() == *w)
+    }
+
+    /// Returns whether the current token is the `if` of a list comprehension's filter.
+    fn is_filter(&self) -> bool {
+        let token = self.draw();
+        token.item == Token::Symbol && token.span.contents() == "if"
+    }
+
+    /// Parses the rest of a list comprehension, after the element:
+    /// ```ignore
+    /// [x * 2 for x in 0..5 if x != 3]
+    /// ```
+    /// Like a for loop, the pattern is followed by `in` and the iterable.
+    /// A filter, introduced by `if`, is optional.
+    pub fn comprehension(&mut self, start: Span, element: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        if let AST::Tuple(_) = element.item {
+            return Err(Syntax::error(
+                "A list comprehension must have a single element, wrap tuples in parenthesis",
+                &element.span,
+            ));
+        }
+        self.consume(Token::For)?;
+
+        let in_operator = mem::replace(&mut self.in_operator, false);
+        let left = self.expression(Prec::Pair, true);
+        self.in_operator = in_operator;
+        let left = left?;
+        let left_span = left.span.clone();
+        let pattern = left.map(ASTPattern::try_from)
+            .map_err(|e| Syntax::error(&e, &left_span))?;
+        self.skip();
+        self.consume(Token::In)?;
+
+        let iterable = self.expression(Prec::Pair, true)?;
+        let filter = if self.is_filter() {
+            self.skip();
+            self.advance();
+            Some(self.expression(Prec::Pair, true)?)
+        } else {
+            None
+        };
+
+        self.skip();
+        let end = self.consume(Token::CloseSquare)?.span.clone();
+        let ast = AST::comprehension(element, pattern, iterable, filter);
+        Ok(Spanned::new(ast, Span::combine(&start, &end)))
+    }
+
     /// Parses the body of a block.
     /// A block is one or more expressions, separated by separators.
     /// This is more of a helper function, as it serves as both the
@@ -351,17 +597,56 @@ impl Parser {
     /// Just a body between curlies.
     pub fn block(&mut self) -> Result<Spanned<AST>, Syntax> {
         let start = self.consume(Token::OpenBracket)?.span.clone();
-        let ast = self.body(Token::CloseBracket)?;
+        let block_args    = mem::replace(&mut self.block_args, true);
+        let comprehension = mem::replace(&mut self.comprehension, false);
+        let do_blocks     = mem::replace(&mut self.do_blocks, true);
+        let ast = self.body(Token::CloseBracket);
+        self.block_args    = block_args;
+        self.comprehension = comprehension;
+        self.do_blocks     = do_blocks;
+        let ast = ast?;
         let end = self.consume(Token::CloseBracket)?.span.clone();
         Ok(Spanned::new(ast, Span::combine(&start, &end)))
     }
 
+    /// Parse a for loop.
+    /// `for`, followed by a pattern, `in`, an expression to iterate over, and a block:
+    /// ```ignore
+    /// for x in xs { ... }
+    /// ```
+    pub fn for_loop(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::For)?.span.clone();
+
+        let in_operator = mem::replace(&mut self.in_operator, false);
+        let left = self.expression(Prec::Pair, false);
+        self.in_operator = in_operator;
+        let left = left?;
+        let left_span = left.span.clone();
+        let pattern = left.map(ASTPattern::try_from)
+            .map_err(|e| Syntax::error(&e, &left_span))?;
+        self.skip();
+        self.consume(Token::In)?;
+
+        let block_args = mem::replace(&mut self.block_args, false);
+        let iterable = self.expression(Prec::Pair, false);
+        self.block_args = block_args;
+        let iterable = iterable?;
+
+        self.skip();
+        let body = self.block()?;
+        let span = Span::combine(&start, &body.span);
+        Ok(Spanned::new(AST::for_loop(pattern, iterable, body), span))
+    }
+
     // TODO: unwrap from outside in to prevent nesting
     /// Parse a macro definition.
     /// `syntax`, followed by a pattern, followed by a `block`
     pub fn syntax(&mut self) -> Result<Spanned<AST>, Syntax> {
         let start = self.consume(Token::Syntax)?.span.clone();
-        let mut after = self.expression(Prec::Call, false)?;
+        let do_blocks = mem::replace(&mut self.do_blocks, false);
+        let after = self.expression(Prec::Call, false);
+        self.do_blocks = do_blocks;
+        let mut after = after?;
 
         let mut form = match after.item {
             AST::Form(p) => p,
@@ -432,6 +717,113 @@ impl Parser {
         ))
     }
 
+    /// Parse a try expression.
+    /// `try` catches any runtime error raised while evaluating the expression after it,
+    /// evaluating to `Ok value` on success, and an error value on failure,
+    /// which matches the pattern `Error message`:
+    /// ```ignore
+    /// Error message = try (1 / 0)
+    /// ```
+    pub fn try_catch(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Try)?.span.clone();
+        let ast = self.expression(Prec::End, false)?;
+        let end = ast.span.clone();
+        Ok(Spanned::new(
+            AST::Try(Box::new(ast)),
+            Span::combine(&start, &end),
+        ))
+    }
+
+    /// Parse an explicit return.
+    /// `return` is followed by an expression,
+    /// which the enclosing function evaluates to immediately,
+    /// skipping the rest of its body:
+    /// ```ignore
+    /// first = xs -> { for x in xs { return x }; () }
+    /// ```
+    /// Without a `return`, a function evaluates to the last expression of its body.
+    pub fn return_expr(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Return)?.span.clone();
+        let ast = self.expression(Prec::Pair, false)?;
+        let end = ast.span.clone();
+        Ok(Spanned::new(
+            AST::Return(Box::new(ast)),
+            Span::combine(&start, &end),
+        ))
+    }
+
+    /// Returns whether the current token is the `do` of a do block.
+    /// `do` is not reserved: it's only a keyword when followed by a block,
+    /// elsewhere it's a symbol like any other.
+    fn is_do_block(&self) -> bool {
+        let token = self.draw();
+        self.do_blocks
+        && token.item == Token::Symbol
+        && token.span.contents() == "do"
+        && self.tokens.get(self.index + 1).map(|t| &t.item) == Some(&Token::OpenBracket)
+    }
+
+    /// Parse a do block.
+    /// `do` is followed by a block, which is evaluated for its side effects:
+    /// ```ignore
+    /// do { println "a"; println "b" }
+    /// ```
+    /// Unlike a normal block, a do block evaluates to `()`,
+    /// so it is parsed as a block with a trailing `()`.
+    pub fn do_block(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Symbol)?.span.clone();
+        let block = self.block()?;
+        let span = Span::combine(&start, &block.span);
+
+        let mut expressions = match block.item {
+            AST::Block(b) => b,
+            _ => unreachable!("Expected a block"),
+        };
+
+        let end = Span::point(block.span.source.as_ref().unwrap(), block.span.end());
+        expressions.push(Spanned::new(AST::Data(Data::Unit), end));
+        Ok(Spanned::new(AST::Block(expressions), span))
+    }
+
+    /// Parse an import.
+    /// `import` is followed by a string, the path of the module to import:
+    /// ```ignore
+    /// utils = import "utils.pn"
+    /// ```
+    /// The imported module is compiled later on, see `compiler::import`.
+    pub fn import(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Import)?.span.clone();
+
+        let Spanned { item: token, span } = self.advance();
+        let path = match token {
+            Token::String(Data::String(s)) => s.clone(),
+            unexpected => return Err(Syntax::error(
+                &format!("Expected the path of the module to import as a string, found {}", unexpected),
+                span
+            )),
+        };
+
+        let end = span.clone();
+        Ok(Spanned::new(AST::Import(path), Span::combine(&start, &end)))
+    }
+
+    /// Parse a constant.
+    /// `const` is followed by an assignment:
+    /// ```ignore
+    /// const pi = 3.14
+    /// ```
+    /// Unlike a normal assignment, the bound variables can not be reassigned.
+    pub fn constant(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Const)?.span.clone();
+        let assign = self.expression(Prec::Assign, false)?;
+        let span = Span::combine(&start, &assign.span);
+
+        match assign.item {
+            AST::Assign { pattern, expression } => Ok(Spanned::new(AST::constant(*pattern, *expression), span)),
+            _ => Err(Syntax::error("Expected an assignment after 'const'", &assign.span)),
+        }
+    }
+
     pub fn neg(&mut self) -> Result<Spanned<AST>, Syntax> {
         let start = self.consume(Token::Sub)?.span.clone();
         let ast = self.expression(Prec::End, false)?;
@@ -468,10 +860,20 @@ impl Parser {
     // TODO: assign and lambda are similar... combine?
 
     /// Parses an assignment, associates right.
+    /// The left side must be a variable, or a pattern to destructure;
+    /// assigning to a literal, like `1 = 2`, binds nothing, so it is rejected.
     pub fn assign(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
         let left_span = left.span.clone();
+        let hint = "the left side of an assignment must be a variable or a pattern";
         let pattern = left.map(ASTPattern::try_from)
-            .map_err(|e| Syntax::error(&e, &left_span))?;
+            .map_err(|e| Syntax::error(&format!("{}, {}", e, hint), &left_span))?;
+
+        if let ASTPattern::Data(data) = &pattern.item {
+            return Err(Syntax::error(
+                &format!("Can not assign to the literal '{}', {}", data, hint),
+                &left_span,
+            ));
+        }
 
         self.consume(Token::Assign)?;
         let expression = self.expression(Prec::Assign, false)?;
@@ -479,6 +881,38 @@ impl Parser {
         Ok(Spanned::new(AST::assign(pattern, expression), combined))
     }
 
+    /// Parses a compound assignment, such as `x += 1`, associates right.
+    /// `x += 1` is like `x = x + 1`, so the left side must be a variable.
+    /// Unlike an assignment, it can only update a variable that has already been declared.
+    /// This rebinds the variable rather than mutating its value,
+    /// although closures that captured the variable will see the new value.
+    pub fn compound_assign(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        let name = match self.advance().item {
+            Token::AddAssign => "add",
+            Token::SubAssign => "sub",
+            Token::MulAssign => "mul",
+            Token::DivAssign => "div",
+            Token::RemAssign => "rem",
+            _ => unreachable!("Expected a compound assignment"),
+        };
+
+        if let AST::Symbol(_) = left.item {} else {
+            return Err(Syntax::error(
+                "Expected a variable on the left side of a compound assignment",
+                &left.span,
+            ));
+        }
+
+        let pattern = left.clone().map(ASTPattern::try_from)
+            .map_err(|e| Syntax::error(&e, &left.span))?;
+
+        let expression = self.expression(Prec::Assign, false)?;
+        let combined   = Span::combine(&left.span, &expression.span);
+        let arguments  = Spanned::new(AST::Tuple(vec![left, expression]), combined.clone());
+        let operation  = Spanned::new(AST::ffi(name, arguments), combined.clone());
+        Ok(Spanned::new(AST::mutate(pattern, operation), combined))
+    }
+
     /// Parses a lambda definition, associates right.
     pub fn lambda(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
         let left_span = left.span.clone();
@@ -516,6 +950,33 @@ impl Parser {
         Ok(Spanned::new(AST::Tuple(tuple), span))
     }
 
+    /// Parses local bindings for an expression,
+    /// i.e. comma-separated assignments after `where`:
+    /// ```ignore
+    /// x + y where x = 1, y = 2
+    /// ```
+    /// Each binding can refer to the ones before it.
+    pub fn where_clause(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.consume(Token::Where)?;
+        let mut bindings = vec![];
+
+        loop {
+            let binding = self.expression(Prec::Pair.associate_left(), false)?;
+            let binding_span = binding.span.clone();
+            let pattern = binding.map(ASTPattern::try_from)
+                .map_err(|e| Syntax::error(&e, &binding_span))?;
+            self.consume(Token::Assign)?;
+            let value = self.expression(Prec::Pair.associate_left(), false)?;
+            bindings.push((pattern, value));
+
+            if self.current().item != Token::Pair { break; }
+            self.consume(Token::Pair)?;
+        }
+
+        let span = Span::combine(&left.span, &bindings.last().unwrap().1.span);
+        Ok(Spanned::new(AST::where_clause(left, bindings), span))
+    }
+
     /// Parses a function composition, i.e. `a . b`
     pub fn compose(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
         self.consume(Token::Compose)?;
@@ -524,6 +985,25 @@ impl Parser {
         Ok(Spanned::new(AST::composition(left, right), combined))
     }
 
+    /// Parses a pipe, i.e. `x |> f |> g`, which is the same as `g (f x)`.
+    /// A pipe is a composition with a lower precedence,
+    /// so `a + b |> f` passes the sum to `f`.
+    /// The right side must be a function, so literals are rejected.
+    pub fn pipe(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.consume(Token::Pipe)?;
+        let right = self.expression(Prec::Pipe.associate_left(), false)?;
+
+        if let AST::Data(data) = &right.item {
+            return Err(Syntax::error(
+                &format!("Expected a function on the right side of the pipe, found '{}'", data),
+                &right.span,
+            ));
+        }
+
+        let combined = Span::combine(&left.span, &right.span);
+        Ok(Spanned::new(AST::composition(left, right), combined))
+    }
+
     // TODO: names must be full qualified paths.
 
     /// Parses a left-associative binary operator.
@@ -567,6 +1047,105 @@ impl Parser {
         self.binop(Token::Equal, Prec::Logic.associate_left(), "equal", left)
     }
 
+    /// Parses an inequality, calls out to FFI.
+    pub fn not_equal(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.binop(Token::NotEqual, Prec::Logic.associate_left(), "not_equal", left)
+    }
+
+    /// Parses a membership check, i.e. `x in xs`, calls out to FFI.
+    pub fn contains(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.binop(Token::In, Prec::Logic.associate_left(), "contains", left)
+    }
+
+    /// Parses a keyword argument, i.e. `name: value`.
+    /// Keyword arguments can only be passed to FFI functions that take them,
+    /// see `core::ffi::FFIFunction::with_keywords`.
+    pub fn named_argument(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        let name = match left.item {
+            AST::Symbol(name) => name,
+            _ => return Err(Syntax::error(
+                "Expected the name of a keyword argument before the colon",
+                &left.span,
+            )),
+        };
+
+        self.consume(Token::Colon)?;
+        let value = self.expression(Prec::Named.associate_left(), false)?;
+        let combined = Span::combine(&left.span, &value.span);
+        Ok(Spanned::new(AST::Keyword { name, value: Box::new(value) }, combined))
+    }
+
+    /// Parses a logical and, i.e. `a and b`.
+    /// Although this is parsed as an FFI call, the right side is only evaluated if needed,
+    /// see `gen::Compiler::logic`.
+    pub fn and(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.binop(Token::Symbol, Prec::And.associate_left(), "and", left)
+    }
+
+    /// Parses a logical or, i.e. `a or b`, which short-circuits like `and`.
+    pub fn or(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.binop(Token::Symbol, Prec::Or.associate_left(), "or", left)
+    }
+
+    /// Parses a bitwise and, calls out to FFI.
+    pub fn bit_and(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.binop(Token::BitAnd, Prec::BitAnd.associate_left(), "bit_and", left)
+    }
+
+    /// Parses a bitwise or, calls out to FFI.
+    pub fn bit_or(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.binop(Token::BitOr, Prec::BitOr.associate_left(), "bit_or", left)
+    }
+
+    /// Parses a bitwise exclusive or, i.e. `a ^^ b`, calls out to FFI.
+    pub fn bit_xor(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.binop(Token::BitXor, Prec::BitXor.associate_left(), "bit_xor", left)
+    }
+
+    /// Parses a left shift, calls out to FFI.
+    pub fn shift_left(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.binop(Token::ShiftLeft, Prec::Shift.associate_left(), "shift_left", left)
+    }
+
+    /// Parses a right shift, calls out to FFI.
+    pub fn shift_right(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.binop(Token::ShiftRight, Prec::Shift.associate_left(), "shift_right", left)
+    }
+
+    /// Parses a range, i.e. `start..end`.
+    pub fn range(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.consume(Token::Range)?;
+        let right = self.expression(Prec::Range.associate_left(), false)?;
+        let combined = Span::combine(&left.span, &right.span);
+        Ok(Spanned::new(AST::range(left, right), combined))
+    }
+
+    /// Parses an index, i.e. `xs[i]`.
+    /// Note that the opening square bracket must directly follow the expression
+    /// being indexed, otherwise `xs [i]` is parsed as a call with a list.
+    pub fn index(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        self.consume(Token::OpenSquare)?;
+        let block_args = mem::replace(&mut self.block_args, true);
+        let index = self.expression(Prec::None.associate_left(), true);
+        self.block_args = block_args;
+        let index = index?;
+        let end = self.consume(Token::CloseSquare)?.span.clone();
+        let combined = Span::combine(&left.span, &end);
+        Ok(Spanned::new(AST::index(left, index), combined))
+    }
+
+    /// Parses a nil propagation, i.e. `expression?`.
+    /// If the expression is `nil`, the enclosing function returns `nil` early;
+    /// otherwise the value passes through unchanged.
+    /// Note that symbols may end in a question mark,
+    /// so `x? y` calls the symbol `x?`, whereas `x ? y`, `(x)? y`,
+    /// and `x?` at the end of a line propagate.
+    pub fn propagate(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        let end = self.consume(Token::Question)?.span.clone();
+        let combined = Span::combine(&left.span, &end);
+        Ok(Spanned::new(AST::Propagate(Box::new(left)), combined))
+    }
+
     /// Parses an remainder, calls out to FFI.
     pub fn rem(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
         self.binop(Token::Rem, Prec::MulDiv.associate_left(), "rem", left)
@@ -641,6 +1220,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     pub fn lambda() {
         let source = Source::source("x = y -> 3.141592");
         let ast = parse(lex(source.clone()).unwrap()).unwrap();
@@ -672,4 +1252,130 @@ mod test {
             )
         );
     }
+
+    #[test]
+    pub fn assign_literal() {
+        let source = Source::source("x = 1\n1 = 2");
+        let error = parse(lex(source.clone()).unwrap()).unwrap_err();
+        assert_eq!(
+            error,
+            Syntax::error(
+                "Can not assign to the literal '1', the left side of an assignment must be a variable or a pattern",
+                &Span::new(&source, 6, 1),
+            ),
+        );
+    }
+
+    #[test]
+    pub fn import() {
+        let source = Source::source("m = import \"m.pn\"");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        assert_eq!(
+            ast.item,
+            AST::Block(vec![
+                Spanned::new(
+                    AST::assign(
+                        Spanned::new(ASTPattern::Symbol("m".to_string()), Span::new(&source, 0, 1)),
+                        Spanned::new(AST::Import("m.pn".to_string()), Span::new(&source, 4, 13)),
+                    ),
+                    Span::new(&source, 0, 17),
+                ),
+            ]),
+        );
+
+        let source = Source::source("import m");
+        assert!(parse(lex(source).unwrap()).is_err());
+    }
+
+    #[test]
+    pub fn constant() {
+        let source = Source::source("const x = 1");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        assert_eq!(
+            ast.item,
+            AST::Block(vec![
+                Spanned::new(
+                    AST::constant(
+                        Spanned::new(ASTPattern::Symbol("x".to_string()), Span::new(&source, 6, 1)),
+                        Spanned::new(AST::Data(Data::Integer(1)), Span::new(&source, 10, 1)),
+                    ),
+                    Span::new(&source, 0, 11),
+                ),
+            ]),
+        );
+
+        let source = Source::source("const x");
+        assert!(parse(lex(source).unwrap()).is_err());
+    }
+
+    #[test]
+    pub fn assign_expression() {
+        let source = Source::source("(a + b) = 3");
+        let error = parse(lex(source.clone()).unwrap()).unwrap_err();
+        assert_eq!(error.span, Span::new(&source, 0, 7));
+    }
+
+    #[test]
+    pub fn index() {
+        let source = Source::source("xs[0]\nf [0]");
+        let ast = parse(lex(source).unwrap()).unwrap();
+        let block = if let AST::Block(b) = ast.item { b } else { panic!() };
+
+        // indexing requires the bracket to be right after the expression
+        assert!(matches!(block[0].item, AST::Index { .. }));
+        assert!(matches!(block[1].item, AST::Form(_)));
+    }
+
+    #[test]
+    pub fn comprehension() {
+        let source = Source::source("[f x for x in xs if x in ys]\n[f for (x) in xs]\n[f, g for x in xs]");
+        let ast = parse(lex(source.clone()).unwrap());
+        let error = ast.unwrap_err();
+        assert_eq!(error.span, Span::new(&source, 48, 4));
+
+        let source = Source::source("[f x for x in xs if x in ys]\n[f (for x in xs {})]");
+        let ast = parse(lex(source).unwrap()).unwrap();
+        let block = if let AST::Block(b) = ast.item { b } else { panic!() };
+
+        match &block[0].item {
+            AST::Comprehension { element, iterable, filter: Some(filter), .. } => {
+                assert!(matches!(element.item, AST::Form(_)));
+                assert_eq!(iterable.item, AST::Symbol("xs".to_string()));
+                assert!(matches!(filter.item, AST::FFI { .. }));
+            },
+            other => panic!("Expected a list comprehension, found {:?}", other),
+        }
+
+        // a for loop can still be passed to a function in a list
+        assert!(matches!(&block[1].item, AST::List(l) if l.len() == 1));
+    }
+
+    #[test]
+    pub fn where_clause() {
+        let source = Source::source("z = x where x = 1, y = 2");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        let symbol = |name: &str, offset| Spanned::new(ASTPattern::Symbol(name.to_string()), Span::new(&source, offset, 1));
+        let integer = |n, offset| Spanned::new(AST::Data(Data::Integer(n)), Span::new(&source, offset, 1));
+        assert_eq!(
+            ast.item,
+            AST::Block(vec![
+                Spanned::new(
+                    AST::assign(
+                        symbol("z", 0),
+                        Spanned::new(
+                            AST::where_clause(
+                                Spanned::new(AST::Symbol("x".to_string()), Span::new(&source, 4, 1)),
+                                vec![(symbol("x", 12), integer(1, 16)), (symbol("y", 19), integer(2, 23))],
+                            ),
+                            Span::new(&source, 4, 20),
+                        ),
+                    ),
+                    Span::new(&source, 0, 24),
+                ),
+            ]),
+        );
+
+        let source = Source::source("x where x");
+        assert!(parse(lex(source).unwrap()).is_err());
+    }
 }
@@ -0,0 +1,418 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::common::{
+    lambda::Lambda,
+    opcode::Opcode,
+    data::Data,
+    number::{build_number, split_number},
+};
+
+/// A single op decoded from bytecode,
+/// along with the index it starts at and its operands, if it has any.
+struct Op {
+    index:    usize,
+    opcode:   Opcode,
+    operands: Vec<usize>,
+}
+
+impl Op {
+    /// The first operand, i.e. the target of a jump.
+    fn operand(&self) -> usize {
+        self.operands[0]
+    }
+}
+
+/// Simple peephole optimizer over bytecode generated by `gen`.
+/// This pass is optional: the bytecode it produces evaluates to the same result,
+/// but may execute fewer ops.
+/// Looks at adjacent ops and removes ones that cancel out:
+///
+/// - A value pushed and then immediately deleted, like `Con; Del`.
+/// - A `Jump` to the op right after it.
+/// - Two `Swap`s in a row.
+///
+/// Jumps to other jumps are also threaded,
+/// i.e. rewritten to jump straight to where the chain of jumps ends up.
+///
+/// Ops are never removed if doing so would change what happens
+/// when another op jumps into the middle of a pattern.
+/// Jump targets and spans are recalculated after ops are removed,
+/// and functions defined in the chunk are optimized too.
+/// Returns an error if the chunk contains a byte that is not a valid opcode.
+pub fn peephole(lambda: &mut Lambda) -> Result<(), String> {
+    for constant in lambda.constants.iter_mut() {
+        if let Data::Lambda(inner) = constant {
+            let mut optimized = (**inner).clone();
+            peephole(&mut optimized)?;
+            *inner = Rc::new(optimized);
+        }
+    }
+
+    // removing ops may bring new patterns together,
+    // e.g. the `Jump` in `Con; Jump; Del`
+    loop {
+        let mut ops = decode(lambda)?;
+        let threaded = thread(&mut ops);
+        let removed = removable(&ops, lambda.code.len());
+        if !threaded && removed.is_empty() { return Ok(()); }
+        rebuild(lambda, &ops, &removed)?;
+    }
+}
+
+/// Splits bytecode into ops.
+fn decode(lambda: &Lambda) -> Result<Vec<Op>, String> {
+    let mut ops = vec![];
+    let mut index = 0;
+
+    while index < lambda.code.len() {
+        let start = index;
+        let opcode = Opcode::from_byte(lambda.code[index])
+            .ok_or_else(|| format!("The byte {} at index {} is not a valid opcode", lambda.code[index], index))?;
+        index += 1;
+
+        let operands = if opcode == Opcode::Con {
+            index += 1;
+            vec![lambda.code[index - 1] as usize]
+        } else {
+            (0..opcode.operand_count()).map(|_| {
+                let (operand, consumed) = build_number(&lambda.code[index..]);
+                index += consumed;
+                operand
+            }).collect()
+        };
+
+        ops.push(Op { index: start, opcode, operands });
+    }
+
+    Ok(ops)
+}
+
+/// Rewrites jumps that land on another jump to go straight to the final target.
+/// Any jump can pass through an unconditional `Jump`;
+/// conditional jumps can also pass through a conditional jump of the same kind,
+/// as they leave the condition on the stack, e.g. in `a and b and c`.
+/// The jumps that were skipped over are left in place,
+/// as other ops may still fall through to them.
+/// Returns whether any jump was rewritten.
+fn thread(ops: &mut [Op]) -> bool {
+    let starts = ops.iter()
+        .enumerate()
+        .map(|(i, op)| (op.index, i))
+        .collect::<HashMap<usize, usize>>();
+
+    let mut threaded = false;
+
+    for i in 0..ops.len() {
+        let opcode = ops[i].opcode;
+        if !opcode.is_jump() { continue; }
+
+        let passes = |through: Opcode| through == Opcode::Jump
+            || (through == opcode && matches!(opcode, Opcode::JumpIfFalseNoPop | Opcode::JumpIfTrueNoPop));
+
+        // jumps that loop forever are left alone
+        let mut target = ops[i].operand();
+        let mut seen = HashSet::new();
+        seen.insert(ops[i].index);
+        while let Some(&j) = starts.get(&target) {
+            if !passes(ops[j].opcode) { break; }
+            if !seen.insert(target) {
+                target = ops[i].operand();
+                break;
+            }
+            target = ops[j].operand();
+        }
+
+        if target != ops[i].operand() {
+            ops[i].operands[0] = target;
+            threaded = true;
+        }
+    }
+
+    threaded
+}
+
+/// Returns the indices (into `ops`) of the ops that can be removed.
+fn removable(ops: &[Op], end: usize) -> HashSet<usize> {
+    let targets = ops.iter()
+        .filter(|op| op.opcode.is_jump())
+        .map(|op| op.operand())
+        .collect::<HashSet<usize>>();
+
+    let mut removed = HashSet::new();
+    let mut i = 0;
+
+    while i < ops.len() {
+        let next = ops.get(i + 1);
+        // the second op of a pair can only be removed if nothing jumps to it
+        let pair = |opcode| next.is_some_and(|n| n.opcode == opcode && !targets.contains(&n.index));
+
+        let cancels = match ops[i].opcode {
+              Opcode::Con
+            | Opcode::ConLong
+            | Opcode::LoadUnit
+            | Opcode::LoadTrue
+            | Opcode::LoadFalse
+            | Opcode::Dup  => pair(Opcode::Del),
+            Opcode::Swap   => pair(Opcode::Swap),
+            Opcode::Jump   => {
+                if ops[i].operand() == next.map(|n| n.index).unwrap_or(end) {
+                    removed.insert(i);
+                }
+                false
+            },
+            _ => false,
+        };
+
+        if cancels {
+            removed.insert(i);
+            removed.insert(i + 1);
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    removed
+}
+
+/// Re-emits the bytecode of a chunk without the removed ops,
+/// recalculating jump targets and spans.
+/// Anything that pointed to a removed op now points to the next op that was kept.
+fn rebuild(lambda: &mut Lambda, ops: &[Op], removed: &HashSet<usize>) -> Result<(), String> {
+    let old_len = lambda.code.len();
+    let old_spans = std::mem::take(&mut lambda.spans);
+    lambda.code = vec![];
+
+    let mut positions = vec![None; old_len + 1];
+    let mut targets = vec![];
+
+    for (i, op) in ops.iter().enumerate() {
+        positions[op.index] = Some(lambda.code.len());
+        if removed.contains(&i) { continue; }
+
+        lambda.emit(op.opcode);
+        if op.opcode == Opcode::Con {
+            lambda.emit_bytes(&mut vec![op.operand() as u8]);
+        } else if op.opcode.is_jump() {
+            targets.push((lambda.emit_placeholder(), op.operand()));
+        } else {
+            for operand in op.operands.iter() {
+                lambda.emit_bytes(&mut split_number(*operand));
+            }
+        }
+    }
+    positions[old_len] = Some(lambda.code.len());
+
+    let position = |old: usize| positions.get(old).copied().flatten()
+        .ok_or_else(|| format!("The index {} is not the start of an op", old));
+
+    for (placeholder, target) in targets {
+        lambda.patch(placeholder, position(target)?);
+    }
+
+    for (old, span) in old_spans {
+        lambda.spans.push((position(old)?, span));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{source::Source, closure::Closure, span::Span};
+    use crate::compiler::{lex, parse, desugar, hoist, gen};
+    use crate::vm::VM;
+
+    fn compile(source: &str) -> Lambda {
+        let lambda = lex(Source::source(source))
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(hoist)
+            .and_then(|sst| Ok(gen(sst)?))
+            .unwrap();
+        (*lambda).clone()
+    }
+
+    fn run(lambda: Lambda) -> Data {
+        let mut vm = VM::init(Closure::wrap(Rc::new(lambda)));
+        vm.run().unwrap();
+        vm.stack.pop_data()
+    }
+
+    fn optimize(lambda: &Lambda) -> Lambda {
+        let mut optimized = lambda.clone();
+        peephole(&mut optimized).unwrap();
+        assert_eq!(run(optimized.clone()), run(lambda.clone()));
+        optimized
+    }
+
+    #[test]
+    fn push_then_delete() {
+        let mut lambda = Lambda::empty();
+        let one = lambda.index_data(Data::Integer(1));
+        lambda.emit_constant(one);
+        lambda.emit(Opcode::Del);
+        lambda.emit(Opcode::LoadUnit);
+        lambda.emit(Opcode::Del);
+        lambda.emit(Opcode::LoadTrue);
+        lambda.emit(Opcode::Dup);
+        lambda.emit(Opcode::Del);
+
+        assert_eq!(optimize(&lambda).code, vec![Opcode::LoadTrue as u8]);
+    }
+
+    #[test]
+    fn jump_to_next() {
+        let mut lambda = Lambda::empty();
+        lambda.emit(Opcode::LoadTrue);
+        lambda.emit(Opcode::Jump);
+        lambda.emit_bytes(&mut split_number(3));
+        lambda.emit(Opcode::LoadFalse);
+
+        assert_eq!(
+            optimize(&lambda).code,
+            vec![Opcode::LoadTrue as u8, Opcode::LoadFalse as u8],
+        );
+
+        // removing the jump brings the push and delete together
+        let mut lambda = Lambda::empty();
+        lambda.emit(Opcode::LoadFalse);
+        lambda.emit(Opcode::LoadTrue);
+        lambda.emit(Opcode::Jump);
+        lambda.emit_bytes(&mut split_number(4));
+        lambda.emit(Opcode::Del);
+
+        assert_eq!(optimize(&lambda).code, vec![Opcode::LoadFalse as u8]);
+    }
+
+    #[test]
+    fn double_swap() {
+        let mut lambda = Lambda::empty();
+        lambda.emit(Opcode::LoadTrue);
+        lambda.emit(Opcode::LoadFalse);
+        lambda.emit(Opcode::Swap);
+        lambda.emit(Opcode::Swap);
+        lambda.emit(Opcode::Swap);
+
+        assert_eq!(
+            optimize(&lambda).code,
+            vec![Opcode::LoadTrue as u8, Opcode::LoadFalse as u8, Opcode::Swap as u8],
+        );
+    }
+
+    #[test]
+    fn chained_jumps() {
+        // `true`, then a jump to a jump to the end
+        let mut lambda = Lambda::empty();
+        lambda.emit(Opcode::LoadTrue);
+        lambda.emit(Opcode::Jump);
+        lambda.emit_bytes(&mut split_number(4));
+        lambda.emit(Opcode::LoadFalse);
+        lambda.emit(Opcode::Jump);
+        lambda.emit_bytes(&mut split_number(7));
+        lambda.emit(Opcode::LoadUnit);
+
+        let optimized = optimize(&lambda);
+        let (first, _) = build_number(&optimized.code[2..]);
+        assert_eq!(first, optimized.code.len());
+
+        // each `and` jumps to the next when the left side is false
+        let lambda = compile("false and true and true and true");
+        let optimized = optimize(&lambda);
+        assert_eq!(optimized.code[1], Opcode::JumpIfFalseNoPop as u8);
+        let (first, _) = build_number(&optimized.code[2..]);
+        assert_eq!(first, optimized.code.len());
+        assert_eq!(run(optimized), Data::Boolean(false));
+    }
+
+    #[test]
+    fn jump_loop_kept() {
+        let mut lambda = Lambda::empty();
+        lambda.emit(Opcode::Jump);
+        lambda.emit_bytes(&mut split_number(3));
+        lambda.emit(Opcode::LoadUnit);
+        lambda.emit(Opcode::Jump);
+        lambda.emit_bytes(&mut split_number(0));
+
+        let mut optimized = lambda.clone();
+        peephole(&mut optimized).unwrap();
+        assert_eq!(optimized, lambda);
+    }
+
+    #[test]
+    fn targets_and_spans() {
+        let source = Source::source("a b c");
+        let span = |offset| Span::new(&source, offset, 1);
+
+        // `true or (1; false)`, with a span for each op
+        let mut lambda = Lambda::empty();
+        lambda.emit_span(&span(0));
+        lambda.emit(Opcode::LoadTrue);
+        lambda.emit(Opcode::JumpIfTrueNoPop);
+        let end = lambda.emit_placeholder();
+        lambda.emit(Opcode::Del);
+        lambda.emit_span(&span(2));
+        let one = lambda.index_data(Data::Integer(1));
+        lambda.emit_constant(one);
+        lambda.emit(Opcode::Del);
+        lambda.emit_span(&span(4));
+        lambda.emit(Opcode::LoadFalse);
+        let len = lambda.code.len();
+        lambda.patch(end, len);
+
+        let optimized = optimize(&lambda);
+        assert_eq!(optimized.code.len(), lambda.code.len() - 3);
+        let (target, _) = build_number(&optimized.code[2..]);
+        assert_eq!(target, optimized.code.len());
+
+        // the span of the removed ops now points to the op after them
+        let load_false = optimized.code.len() - 1;
+        assert_eq!(optimized.code[load_false], Opcode::LoadFalse as u8);
+        assert_eq!(optimized.index_span(load_false), span(4));
+        assert_eq!(optimized.index_span(load_false - 1), span(0));
+    }
+
+    #[test]
+    fn jumped_to_delete_kept() {
+        // the `Del` is reached without the `LoadUnit` before it
+        let mut lambda = Lambda::empty();
+        lambda.emit(Opcode::LoadTrue);
+        lambda.emit(Opcode::LoadFalse);
+        lambda.emit(Opcode::Jump);
+        lambda.emit_bytes(&mut split_number(5));
+        lambda.emit(Opcode::LoadUnit);
+        lambda.emit(Opcode::Del);
+
+        assert_eq!(optimize(&lambda), lambda);
+    }
+
+    #[test]
+    fn no_change() {
+        let lambda = compile("x = 1\nf = y -> y + x\nf 2");
+        let optimized = optimize(&lambda);
+        assert_eq!(optimized.code, lambda.code);
+        assert_eq!(optimized.spans, lambda.spans);
+    }
+
+    #[test]
+    fn loops_and_functions() {
+        let lambda = compile("
+            total = 0
+            add = x -> { (); x + 1 }
+            for x in [1, 2, 3] { true; total = total + add x }
+            total
+        ");
+        let optimized = optimize(&lambda);
+        assert!(optimized.code.len() < lambda.code.len());
+        assert_eq!(run(optimized.clone()), Data::Integer(9));
+
+        let function = |lambda: &Lambda| lambda.constants.iter()
+            .find_map(|c| match c { Data::Lambda(l) => Some(l.code.len()), _ => None })
+            .unwrap();
+        assert!(function(&optimized) < function(&lambda));
+    }
+}
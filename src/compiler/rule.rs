@@ -94,7 +94,7 @@ impl Rule {
     /// Note that this function takes the form unwrapped and in reverse -
     /// This is to make processing the bindings more efficient,
     /// As this function works with the head of the form.
-    pub fn bind(arg_pat: &Spanned<ArgPattern>, mut reversed_form: &mut Vec<Spanned<AST>>)
+    pub fn bind(arg_pat: &Spanned<ArgPattern>, reversed_form: &mut Vec<Spanned<AST>>)
     -> Option<Result<Bindings, Syntax>> {
         match &arg_pat.item {
             // TODO: right now, if a macro is invoked from another macro,
@@ -118,7 +118,7 @@ impl Rule {
                 let mut bindings = HashMap::new();
                 for pat in pats {
                     let span = pat.span.clone();
-                    let new = match Rule::bind(pat, &mut reversed_form)? {
+                    let new = match Rule::bind(pat, reversed_form)? {
                         Ok(matched) => matched,
                         mismatch @ Err(_) => return Some(mismatch),
                     };
@@ -245,7 +245,7 @@ impl Rule {
     // TODO: break expand out into functions
 
     /// Takes a macro's tree and a set of bindings and produces a new hygenic tree.
-    pub fn expand(tree: Spanned<AST>, mut bindings: &mut Bindings)
+    pub fn expand(tree: Spanned<AST>, bindings: &mut Bindings)
     -> Result<Spanned<AST>, Syntax> {
         // TODO: should macros evaluate arguments as thunks before insertions?
         // TODO: allow macros to reference external definitions
@@ -256,8 +256,9 @@ impl Rule {
             // and replaced with a random symbol that does not collide with any other bindings
             // so that the next time the symbol is located,
             // it's consistently replaced, hygenically.
-            AST::Symbol(name) => return Ok(Rule::resolve_symbol(name, tree.span.clone(), &mut bindings)),
+            AST::Symbol(name) => return Ok(Rule::resolve_symbol(name, tree.span.clone(), bindings)),
             AST::Data(_) => return Ok(tree),
+            AST::Import(_) => return Ok(tree),
 
             // Apply the transformation to each form
             AST::Block(forms) => AST::Block(
@@ -298,6 +299,16 @@ impl Rule {
                 let e = Rule::expand(*expression, bindings)?;
                 AST::assign(p, e)
             },
+            AST::Const { pattern, expression } => {
+                let p = Rule::expand_pattern(*pattern, bindings)?;
+                let e = Rule::expand(*expression, bindings)?;
+                AST::constant(p, e)
+            },
+            AST::Mutate { pattern, expression } => {
+                let p = Rule::expand_pattern(*pattern, bindings)?;
+                let e = Rule::expand(*expression, bindings)?;
+                AST::mutate(p, e)
+            },
             AST::Lambda { pattern, expression } => {
                 let p = Rule::expand_pattern(*pattern, bindings)?;
                 let e = Rule::expand(*expression, bindings)?;
@@ -309,12 +320,72 @@ impl Rule {
                 kind, Box::new(Rule::expand(*expression, bindings)?)
             ),
 
+            AST::Try(expression) => AST::Try(
+                Box::new(Rule::expand(*expression, bindings)?)
+            ),
+
+            AST::Propagate(expression) => AST::Propagate(
+                Box::new(Rule::expand(*expression, bindings)?)
+            ),
+
+            AST::Keyword { name, value } => AST::Keyword {
+                name, value: Box::new(Rule::expand(*value, bindings)?)
+            },
+
+            AST::Return(expression) => AST::Return(
+                Box::new(Rule::expand(*expression, bindings)?)
+            ),
+
             AST::Tuple(tuple) => AST::Tuple(
                 tuple.into_iter()
                     .map(|b| Rule::expand(b, bindings))
                     .collect::<Result<Vec<_>, _>>()?
             ),
 
+            AST::List(list) => AST::List(
+                list.into_iter()
+                    .map(|b| Rule::expand(b, bindings))
+                    .collect::<Result<Vec<_>, _>>()?
+            ),
+
+            AST::For { pattern, iterable, body } => {
+                let p = Rule::expand_pattern(*pattern, bindings)?;
+                let i = Rule::expand(*iterable, bindings)?;
+                let b = Rule::expand(*body, bindings)?;
+                AST::for_loop(p, i, b)
+            },
+
+            AST::Where { expression, bindings: locals } => {
+                let e = Rule::expand(*expression, bindings)?;
+                let l = locals.into_iter()
+                    .map(|(p, v)| Ok((
+                        Rule::expand_pattern(p, bindings)?,
+                        Rule::expand(v, bindings)?,
+                    )))
+                    .collect::<Result<Vec<_>, Syntax>>()?;
+                AST::where_clause(e, l)
+            },
+
+            AST::Comprehension { element, pattern, iterable, filter } => {
+                let e = Rule::expand(*element, bindings)?;
+                let p = Rule::expand_pattern(*pattern, bindings)?;
+                let i = Rule::expand(*iterable, bindings)?;
+                let f = filter.map(|f| Rule::expand(*f, bindings)).transpose()?;
+                AST::comprehension(e, p, i, f)
+            },
+
+            AST::Range { start, end } => {
+                let s = Rule::expand(*start, bindings)?;
+                let e = Rule::expand(*end, bindings)?;
+                AST::range(s, e)
+            },
+
+            AST::Index { expression, index } => {
+                let e = Rule::expand(*expression, bindings)?;
+                let i = Rule::expand(*index, bindings)?;
+                AST::index(e, i)
+            },
+
             // a macro inside a macro. not sure how this should work yet
             AST::Syntax { arg_pat, expression } => {
                 let ap = Rule::expand_arg_pat(*arg_pat, bindings)?;
@@ -330,6 +401,12 @@ impl Rule {
                 &name,
                 Rule::expand(*expression, bindings)?
             ),
+
+            AST::Interpolation(fragments) => AST::Interpolation(
+                fragments.into_iter()
+                    .map(|f| f.map_expression(|e| Rule::expand(e, bindings)))
+                    .collect::<Result<Vec<_>, _>>()?
+            ),
         };
 
         Ok(Spanned::new(item, tree.span))
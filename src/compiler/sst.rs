@@ -1,9 +1,10 @@
 use crate::common::{
     span::Spanned,
     data::Data,
+    lambda::Local,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct UniqueSymbol(pub usize);
 
 /// A pattern that mirrors the structure of some Data.
@@ -23,6 +24,8 @@ pub enum SSTPattern {
 pub struct Scope {
     pub locals:    Vec<UniqueSymbol>,
     pub nonlocals: Vec<UniqueSymbol>,
+    /// The name and declaration of each local, in the same order as `locals`.
+    pub names:     Vec<Local>,
 }
 
 impl Scope {
@@ -30,6 +33,7 @@ impl Scope {
         Scope {
             locals:    vec![],
             nonlocals: vec![],
+            names:     vec![],
         }
     }
 
@@ -64,11 +68,18 @@ impl Default for Scope {
 pub enum SST {
     Symbol(UniqueSymbol),
     Data(Data),
-    Block(Vec<Spanned<SST>>),
+    Block {
+        expressions: Vec<Spanned<SST>>,
+        locals:      Vec<UniqueSymbol>,
+    },
     Assign {
         pattern:    Box<Spanned<SSTPattern>>,
         expression: Box<Spanned<SST>>,
     },
+    Mutate {
+        pattern:    Box<Spanned<SSTPattern>>,
+        expression: Box<Spanned<SST>>,
+    },
     Lambda {
         pattern:    Box<Spanned<SSTPattern>>,
         expression: Box<Spanned<SST>>,
@@ -79,14 +90,37 @@ pub enum SST {
         arg: Box<Spanned<SST>>,
     },
     Label(String, Box<Spanned<SST>>),
+    Try(Box<Spanned<SST>>),
+    Propagate(Box<Spanned<SST>>),
+    Return(Box<Spanned<SST>>),
     Tuple(Vec<Spanned<SST>>),
+    List(Vec<Spanned<SST>>),
+    Range {
+        start: Box<Spanned<SST>>,
+        end:   Box<Spanned<SST>>,
+    },
+    Index {
+        expression: Box<Spanned<SST>>,
+        index:      Box<Spanned<SST>>,
+    },
+    For {
+        pattern:  Box<Spanned<SSTPattern>>,
+        iterable: Box<Spanned<SST>>,
+        body:     Box<Spanned<SST>>,
+    },
     FFI {
         name:       String,
         expression: Box<Spanned<SST>>,
+        keywords:   Vec<(String, Spanned<SST>)>,
     },
 }
 
 impl SST {
+    /// Shortcut for creating an `SST::Block` variant.
+    pub fn block(expressions: Vec<Spanned<SST>>, locals: Vec<UniqueSymbol>) -> SST {
+        SST::Block { expressions, locals }
+    }
+
     /// Shortcut for creating an `SST::Assign` variant.
     pub fn assign(
         pattern:    Spanned<SSTPattern>,
@@ -98,6 +132,17 @@ impl SST {
         }
     }
 
+    /// Shortcut for creating an `SST::Mutate` variant.
+    pub fn mutate(
+        pattern:    Spanned<SSTPattern>,
+        expression: Spanned<SST>
+    ) -> SST {
+        SST::Mutate {
+            pattern:    Box::new(pattern),
+            expression: Box::new(expression)
+        }
+    }
+
     /// Shortcut for creating an `SST::Lambda` variant.
     pub fn lambda(
         pattern:    Spanned<SSTPattern>,
@@ -124,11 +169,41 @@ impl SST {
         }
     }
 
+    /// Shortcut for creating a `SST::Range` variant.
+    pub fn range(start: Spanned<SST>, end: Spanned<SST>) -> SST {
+        SST::Range {
+            start: Box::new(start),
+            end:   Box::new(end),
+        }
+    }
+
+    /// Shortcut for creating a `SST::Index` variant.
+    pub fn index(expression: Spanned<SST>, index: Spanned<SST>) -> SST {
+        SST::Index {
+            expression: Box::new(expression),
+            index:      Box::new(index),
+        }
+    }
+
+    /// Shortcut for creating a `SST::For` variant.
+    pub fn for_loop(
+        pattern:  Spanned<SSTPattern>,
+        iterable: Spanned<SST>,
+        body:     Spanned<SST>,
+    ) -> SST {
+        SST::For {
+            pattern:  Box::new(pattern),
+            iterable: Box::new(iterable),
+            body:     Box::new(body),
+        }
+    }
+
     // Shortcut for creating an `SST::FFI` variant.
     pub fn ffi(name: &str, expression: Spanned<SST>) -> SST {
         SST::FFI {
             name: name.to_string(),
             expression: Box::new(expression),
+            keywords: vec![],
         }
     }
 }
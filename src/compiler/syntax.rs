@@ -23,6 +23,91 @@ impl fmt::Display for Syntax {
     }
 }
 
+/// Represents an error found while generating bytecode, see `gen::gen`.
+/// Each variant points at the part of the source the error was found in.
+/// Can be converted into a `Syntax` error,
+/// so it can be handled like an error from any other compiler pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GenError {
+    /// A call to an FFI function that does not exist.
+    UndefinedFFI { name: String, span: Span },
+    /// Keyword arguments passed to magic handled by the VM directly, like `magic "map"`.
+    UnexpectedKeywords { name: String, span: Span },
+    /// An update to a local before it is declared.
+    UpdateBeforeDeclare(Span),
+    /// An assignment to a literal, rather than to a variable or pattern.
+    AssignToLiteral(Span),
+}
+
+impl GenError {
+    /// The message describing the error.
+    pub fn message(&self) -> String {
+        match self {
+            GenError::UndefinedFFI { name, .. } => format!("The ffi function '{}' is not defined", name),
+            GenError::UnexpectedKeywords { name, .. } => format!("The magic '{}' does not take keyword arguments", name),
+            GenError::UpdateBeforeDeclare(_) => "A variable can not be updated before it is declared".to_string(),
+            GenError::AssignToLiteral(_) => "Can not assign to a literal".to_string(),
+        }
+    }
+
+    /// Where the error was found.
+    pub fn span(&self) -> &Span {
+        match self {
+            GenError::UndefinedFFI { span, .. }
+            | GenError::UnexpectedKeywords { span, .. }
+            | GenError::UpdateBeforeDeclare(span)
+            | GenError::AssignToLiteral(span) => span,
+        }
+    }
+}
+
+impl From<GenError> for Syntax {
+    fn from(error: GenError) -> Syntax {
+        Syntax::error(&error.message(), error.span())
+    }
+}
+
+impl fmt::Display for GenError {
+    fn fmt (&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&Syntax::from(self.clone()), f)
+    }
+}
+
+/// Represents something suspicious found at compile time,
+/// like a variable that is never used.
+/// Unlike a `Syntax` error, a warning doesn't stop compilation.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Warning {
+    pub message: String,
+    pub span:    Span,
+    /// Another part of the source the warning refers to, if any,
+    /// e.g. the declaration of a variable that is shadowed.
+    pub related: Option<Span>,
+}
+
+impl Warning {
+    /// Creates a new warning.
+    pub fn new(message: &str, span: &Span) -> Warning {
+        Warning { message: message.to_string(), span: span.clone(), related: None }
+    }
+
+    /// Creates a new warning that also points at a related part of the source.
+    pub fn related(message: &str, span: &Span, related: &Span) -> Warning {
+        Warning { related: Some(related.clone()), ..Warning::new(message, span) }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt (&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // like a traceback, the related span comes first
+        if let Some(related) = &self.related {
+            if !related.is_empty() { fmt::Display::fmt(related, f)? };
+        }
+        if !self.span.is_empty() { fmt::Display::fmt(&self.span, f)? };
+        write!(f, "Warning: {}", self.message)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
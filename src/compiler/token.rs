@@ -1,5 +1,8 @@
 use std::fmt::Display;
-use crate::common::data::Data;
+use crate::common::{
+    data::Data,
+    span::Spanned,
+};
 
 // TODO: remove associated data from tokens.
 
@@ -14,23 +17,43 @@ pub enum Token {
     CloseBracket,
     OpenParen,
     CloseParen,
+    OpenSquare,
+    CloseSquare,
     Sep,
     Pair,
+    Colon,
 
     // Keywords
     Syntax,
     Assign,
+    AddAssign,
+    SubAssign,
+    MulAssign,
+    DivAssign,
+    RemAssign,
     Lambda,
     Compose,
+    Pipe,
     Magic,
+    For,
+    In,
+    Try,
+    Import,
+    Const,
+    Where,
+    Question,
+    Return,
     // pseudokeywords
     Keyword(String),
 
     // Datatypes
     // TODO: just have one variant, `Data`
     Unit,
+    Nil,
     Number(Data),
     String(Data),
+    Bytes(Data),
+    Interpolation(Vec<Piece>),
     Boolean(Data),
 
     // defined by span rather than be contents
@@ -42,12 +65,31 @@ pub enum Token {
     Mul, Div, Rem,
     Pow,
 
+    BitAnd, BitOr, BitXor,
+    ShiftLeft, ShiftRight,
+
     Equal,
+    NotEqual,
+    Range,
+
+    // only emitted when lexing with comments
+    Comment(String),
 
     // EoS
     End,
 }
 
+/// A piece of an interpolated string, like `"x is ${x + 1}"`,
+/// see `Token::Interpolation`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Piece {
+    /// Text included as is, with escapes already converted.
+    Text(String),
+    /// The tokens of an embedded expression, between `${` and `}`,
+    /// followed by a `Token::End`.
+    Expression(Vec<Spanned<Token>>),
+}
+
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // pretty formatting for tokens
@@ -57,25 +99,53 @@ impl Display for Token {
             Token::CloseBracket => "a closing bracket",
             Token::OpenParen    => "an openening paren",
             Token::CloseParen   => "a closing paren",
+            Token::OpenSquare   => "an opening square bracket",
+            Token::CloseSquare  => "a closing square bracket",
             Token::Sep          => "a separator",
+            Token::Colon        => "a colon",
             Token::Syntax       => "a syntax definition",
             Token::Assign       => "an assignment",
+            Token::AddAssign    => "an addition assignment",
+            Token::SubAssign    => "a subtraction assignment",
+            Token::MulAssign    => "a multiplication assignment",
+            Token::DivAssign    => "a division assignment",
+            Token::RemAssign    => "a remainder assignment",
             Token::Lambda       => "a lambda",
             Token::Compose      => "a composition",
+            Token::Pipe         => "a pipe",
             Token::Unit         => "the Unit, '()'",
+            Token::Nil          => "nil",
             Token::Pair         => "a tuple",
             Token::Magic        => "a magic keyword",
+            Token::For          => "a for loop",
+            Token::In           => "the keyword 'in'",
+            Token::Try          => "a try expression",
+            Token::Import       => "an import",
+            Token::Const        => "a constant",
+            Token::Where        => "the keyword 'where'",
+            Token::Question     => "a nil propagation",
+            Token::Return       => "a return",
             Token::Symbol       => "a symbol",
             Token::Label        => "a Label", // capitilized to mimic actual labels
             Token::Number(_)    => "a number",
             Token::String(_)    => "a string",
+            Token::Interpolation(_) => "an interpolated string",
+            Token::Bytes(_)     => "a byte string",
             Token::Add          => "an addition",
             Token::Sub          => "a subtraction",
             Token::Mul          => "a multiplication",
             Token::Div          => "a division",
             Token::Rem          => "a remainder",
             Token::Pow          => "a power of",
+            Token::BitAnd       => "a bitwise and",
+            Token::BitOr        => "a bitwise or",
+            Token::BitXor       => "a bitwise xor",
+            Token::ShiftLeft    => "a left shift",
+            Token::ShiftRight   => "a right shift",
             Token::Equal        => "an equality test",
+            Token::NotEqual     => "an inequality test",
+            Token::Range        => "a range",
+            Token::Comment(_)   => "a comment",
             Token::End          => "end of source",
             Token::Keyword(k) => { return write!(f, "the pseudokeyword '{}", k); },
             Token::Boolean(b) => { return write!(f, "the boolean {}",        b); },
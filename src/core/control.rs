@@ -1,5 +1,13 @@
-use crate::common::data::Data;
-use crate::core::extract::triop;
+use std::collections::HashMap;
+
+use crate::common::{
+    data::Data,
+    span::Span,
+};
+use crate::core::{
+    extract::triop,
+    ffi::{FFIFunction, Partial},
+};
 
 /// An implementation of an if statement, as an FFI.
 /// Interesting idea, not sure if I'm going to keep it.
@@ -15,3 +23,58 @@ pub fn if_choice(data: Data) -> Result<Data, String> {
         )
     }
 }
+
+/// Constructs an error value with a message,
+/// which can be raised with `error`, like an error caught by `try`.
+pub fn make_error(data: Data) -> Result<Data, String> {
+    match data {
+        Data::String(message) => Ok(Data::Error { message, span: Span::empty() }),
+        other => Err(format!("Expected the message of an error to be a string, found '{}'", other)),
+    }
+}
+
+/// Returns the message of an error value.
+pub fn error_message(data: Data) -> Result<Data, String> {
+    match data {
+        Data::Error { message, .. } => Ok(Data::String(message)),
+        other => Err(format!("Expected an error, found '{}'", other)),
+    }
+}
+
+/// Returns a function that finds the number of arguments a function takes,
+/// i.e. `2` for `a b -> a + b`.
+/// Natives are passed by name, like `"add"`,
+/// and report the arity they were declared with, looked up in `natives`.
+pub fn arity(natives: HashMap<String, usize>) -> FFIFunction {
+    FFIFunction::new(Box::new(move |data| {
+        if let Some(arity) = data.arity() {
+            return Ok(Data::Integer(arity as i64));
+        }
+
+        match data {
+            Data::String(name) => match natives.get(&name) {
+                Some(arity) => Ok(Data::Integer(*arity as i64)),
+                None => Err(format!("The native function '{}' does not declare its arity", name)),
+            },
+            other => Err(format!("Expected a function or the name of a native function, found '{}'", other)),
+        }
+    }))
+}
+
+/// Returns a function that looks up a native function by name, like `"add"`,
+/// so that it can be called like a closure, one argument at a time,
+/// i.e. `add = magic "native" "add"; inc = add 1; inc 2` is `3`.
+/// Only natives that declare their arity can be looked up,
+/// as otherwise it's not known how many arguments to wait for.
+pub fn native(natives: HashMap<String, FFIFunction>) -> FFIFunction {
+    FFIFunction::new(Box::new(move |data| match data {
+        Data::String(name) => match natives.get(&name) {
+            Some(function) if function.arity().is_some() => Ok(
+                Data::Partial(Box::new(Partial::new(function.clone())))
+            ),
+            Some(_) => Err(format!("The native function '{}' does not declare its arity", name)),
+            None    => Err(format!("There is no native function named '{}'", name)),
+        },
+        other => Err(format!("Expected the name of a native function, found '{}'", other)),
+    }))
+}
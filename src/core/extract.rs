@@ -7,7 +7,10 @@ use crate::common::data::Data;
 /// A Rust tuple of two items.
 pub fn binop(data: Data) -> (Data, Data) {
     match data {
-        Data::Tuple(t) if t.len() == 2 => (t[0].clone(), t[1].clone()),
+        Data::Tuple(t) if t.len() == 2 => {
+            let mut items = t.into_iter();
+            (items.next().unwrap(), items.next().unwrap())
+        },
         _ => unreachable!("bad data layout passed to ffi"),
     }
 }
@@ -16,7 +19,10 @@ pub fn binop(data: Data) -> (Data, Data) {
 /// A Rust tuple of three items.
 pub fn triop(data: Data) -> (Data, Data, Data) {
     match data {
-        Data::Tuple(t) if t.len() == 3 => (t[0].clone(), t[1].clone(), t[2].clone()),
+        Data::Tuple(t) if t.len() == 3 => {
+            let mut items = t.into_iter();
+            (items.next().unwrap(), items.next().unwrap(), items.next().unwrap())
+        },
         _ => unreachable!("bad data layout passed to ffi"),
     }
 }
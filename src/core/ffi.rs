@@ -2,26 +2,137 @@ use std::{
     rc::Rc,
     collections::HashMap,
 };
-use crate::common::data::Data;
+use crate::common::{
+    data::Data,
+    key::Key,
+    map::Map,
+};
 
-// TODO: have FFI function keep track of number of arguments
-// it takes, so this invariant can be checket at compile time?
+// TODO: check the declared arity of FFI functions at compile time?
 // TODO: find size of FFI function (128 bytes on 64-bit?)
+/// Something outside of the VM that an FFI function may need access to.
+/// Embedders can deny a VM capabilities to sandbox the programs it runs,
+/// see `VM::capabilities`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Reading and writing files.
+    FileSystem,
+}
+
+impl Capability {
+    /// Every capability; a VM has all of them unless restricted.
+    pub const ALL: &'static [Capability] = &[
+        Capability::FileSystem,
+    ];
+
+    /// Returns the name of the capability, as shown in errors.
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::FileSystem => "file system",
+        }
+    }
+}
+
 /// Represents a single FFI function,
 /// Bound at compile time,
 /// Through the use of `FFI`.
+/// A function may require a `Capability` to be called,
+/// and may take keyword arguments, see `FFIFunction::with_keywords`.
+/// A function may also declare its arity, see `FFIFunction::with_arity`.
 #[derive(Clone)]
-pub struct FFIFunction(Rc<dyn Fn(Data) -> Result<Data, String>>);
+pub struct FFIFunction(
+    Rc<dyn Fn(Data) -> Result<Data, String>>,
+    Option<Capability>,
+    Option<Rc<[String]>>,
+    Option<usize>,
+);
 
 impl FFIFunction {
     pub fn new(function: Box<dyn Fn(Data) -> Result<Data, String>>) -> FFIFunction {
-        FFIFunction(Rc::new(function))
+        FFIFunction(Rc::new(function), None, None, None)
+    }
+
+    /// Creates an FFI function that takes keyword arguments, e.g. `magic "name" (1, x: 2)`.
+    /// Instead of the argument as passed, the function is called with a pair:
+    /// the positional arguments, and a map from the name of each keyword argument given to its value.
+    /// The positional arguments are `()` if there are none,
+    /// the argument itself if there is one, and a tuple otherwise,
+    /// see `desugar::Transformer::ffi`.
+    /// Passing a keyword argument that is not listed in `keywords` is an error.
+    pub fn with_keywords(
+        function: Box<dyn Fn(Data) -> Result<Data, String>>,
+        keywords: &[&str],
+    ) -> FFIFunction {
+        let keywords = keywords.iter().map(|k| k.to_string()).collect();
+        FFIFunction(Rc::new(function), None, Some(keywords), None)
+    }
+
+    /// Creates an FFI function that can only be called
+    /// if the VM calling it has been granted a capability.
+    pub fn with_capability(
+        function: Box<dyn Fn(Data) -> Result<Data, String>>,
+        capability: Capability,
+    ) -> FFIFunction {
+        FFIFunction(Rc::new(function), Some(capability), None, None)
+    }
+
+    /// Declares the number of arguments this function takes,
+    /// e.g. `2` for a function called with a pair, or `0` for one called with `()`.
+    /// When called from Passerine, see `Partial`, the function is curried on this arity:
+    /// it is applied to one argument at a time, and called once it has them all,
+    /// so calling its result with more arguments is an error unless that result is a function.
+    /// Functions without a declared arity take a single argument.
+    pub fn with_arity(mut self, arity: usize) -> FFIFunction {
+        self.3 = Some(arity);
+        self
+    }
+
+    /// Returns the declared number of arguments this function takes, if declared.
+    pub fn arity(&self) -> Option<usize> {
+        self.3
+    }
+
+    /// Returns the capability required to call this function, if any.
+    pub fn capability(&self) -> Option<Capability> {
+        self.1
+    }
+
+    /// Returns the names of the keyword arguments this function takes,
+    /// if it takes keyword arguments at all.
+    pub fn keywords(&self) -> Option<&[String]> {
+        self.2.as_deref()
     }
 
     #[inline]
     pub fn call(&self, data: Data) -> Result<Data, String> {
         (self.0)(data)
     }
+
+    /// Calls the function with the keyword arguments passed to it,
+    /// which are collected separately from the argument, see `FFIFunction::with_keywords`.
+    /// Functions that don't take keyword arguments are called with the argument as is,
+    /// but it's an error to pass them keyword arguments.
+    /// Functions that take keyword arguments are always called with a map of them,
+    /// which is empty if none were passed.
+    pub fn call_with_keywords(&self, data: Data, named: Vec<(String, Data)>) -> Result<Data, String> {
+        let keywords = match self.keywords() {
+            Some(keywords) => keywords,
+            None if named.is_empty() => return self.call(data),
+            None => return Err("This ffi function does not take keyword arguments".to_string()),
+        };
+
+        let mut map = Map::new();
+        for (name, value) in named {
+            if !keywords.contains(&name) {
+                return Err(format!("This ffi function does not take the keyword argument '{}'", name));
+            }
+            if map.insert(Key::String(name.clone()), value).is_some() {
+                return Err(format!("The keyword argument '{}' was passed more than once", name));
+            }
+        }
+
+        self.call(Data::Tuple(vec![data, Data::Map(map)]))
+    }
 }
 
 impl std::fmt::Debug for FFIFunction {
@@ -36,6 +147,46 @@ impl PartialEq for FFIFunction {
     }
 }
 
+/// A native function applied to some of its arguments,
+/// which is called like a curried closure, one argument at a time, see `VM::call`.
+/// Once it has been applied to as many arguments as its arity,
+/// the native function is called with all of them.
+#[derive(Debug, Clone)]
+pub struct Partial {
+    pub function: FFIFunction,
+    pub args:     Vec<Data>,
+}
+
+impl Partial {
+    /// Wraps a native function that hasn't been applied to any arguments yet.
+    pub fn new(function: FFIFunction) -> Partial {
+        Partial { function, args: vec![] }
+    }
+
+    /// The number of arguments still to be applied before the function is called.
+    /// Functions of arity `0`, which are passed `()`, still take that one argument.
+    pub fn remaining(&self) -> usize {
+        self.function.arity().unwrap_or(1).max(1) - self.args.len()
+    }
+
+    /// The argument to call the native function with once every argument has been applied,
+    /// i.e. the argument itself for a function of one argument, and a tuple otherwise.
+    pub fn into_argument(mut self) -> Data {
+        match self.args.len() {
+            1 => self.args.pop().unwrap(),
+            _ => Data::Tuple(self.args),
+        }
+    }
+}
+
+impl PartialEq for Partial {
+    /// Partially applied functions are equal if they apply the same function,
+    /// not just one that behaves the same, to equal arguments.
+    fn eq(&self, other: &Partial) -> bool {
+        Rc::ptr_eq(&self.function.0, &other.function.0) && self.args == other.args
+    }
+}
+
 /// A foreign functional interface, mapping names to functions,
 /// passed to the compiler at the bytecode generation step.
 pub struct FFI(HashMap<String, FFIFunction>);
@@ -74,6 +225,19 @@ impl FFI {
         }
     }
 
+    /// Returns every function, by name.
+    pub fn functions(&self) -> HashMap<String, FFIFunction> {
+        self.0.clone()
+    }
+
+    /// Returns the declared arity of each function that declares one,
+    /// see `FFIFunction::with_arity`.
+    pub fn arities(&self) -> HashMap<String, usize> {
+        self.0.iter()
+            .filter_map(|(name, function)| function.arity().map(|arity| (name.clone(), arity)))
+            .collect()
+    }
+
     // TODO: just return FFI?
 
     /// Tries to merge one FFI into another.
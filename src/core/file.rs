@@ -0,0 +1,28 @@
+use std::fs;
+
+use crate::common::data::Data;
+use crate::core::extract::binop;
+
+// NOTE: these functions require the `FileSystem` capability,
+// so that embedders can deny programs access to files.
+
+/// Reads the contents of the file at a path into a string.
+pub fn read_file(data: Data) -> Result<Data, String> {
+    match data {
+        Data::String(path) => fs::read_to_string(&path)
+            .map(Data::String)
+            .map_err(|e| format!("Could not read the file '{}': {}", path, e)),
+        _ => Err("Expected a string path of the file to read".to_string()),
+    }
+}
+
+/// Writes a string to the file at a path,
+/// creating the file if it does not exist, and replacing its contents if it does.
+pub fn write_file(data: Data) -> Result<Data, String> {
+    match binop(data) {
+        (Data::String(path), Data::String(contents)) => fs::write(&path, contents)
+            .map(|()| Data::Unit)
+            .map_err(|e| format!("Could not write to the file '{}': {}", path, e)),
+        _ => Err("Expected a string path and the string contents to write to the file".to_string()),
+    }
+}
@@ -1,4 +1,10 @@
+use std::{
+    io::{self, stdin, BufRead, Read},
+    cell::RefCell,
+};
+
 use crate::common::data::Data;
+use crate::core::ffi::FFIFunction;
 
 /// Prints some data to stdout with a trailing newline.
 pub fn println(data: Data) -> Result<Data, String> {
@@ -15,3 +21,71 @@ pub fn print(data: Data) -> Result<Data, String> {
 pub fn to_string(data: Data) -> Result<Data, String> {
     Ok(Data::String(format!("{}", data)))
 }
+
+/// Returns a function that reads a line from some input,
+/// without the trailing newline.
+/// Reaching the end of the input is an error.
+/// The core FFI reads from stdin, but any input can be used,
+/// for instance, a canned buffer when testing, see `core::ffi_core_with_input`.
+pub fn read_line<R: BufRead + 'static>(input: R) -> FFIFunction {
+    let input = RefCell::new(input);
+
+    FFIFunction::new(Box::new(move |data| {
+        if data != Data::Unit {
+            return Err("Expected no arguments, i.e. (), to read a line".to_string());
+        }
+
+        let mut line = String::new();
+        match input.borrow_mut().read_line(&mut line) {
+            Ok(0)  => return Err("Reached the end of the input while reading a line".to_string()),
+            Ok(_)  => (),
+            Err(e) => return Err(format!("Could not read a line: {}", e)),
+        }
+
+        if line.ends_with('\n') { line.pop(); }
+        if line.ends_with('\r') { line.pop(); }
+        Ok(Data::String(line))
+    }))
+}
+
+/// Reads from the stdin of the process, a line at a time.
+/// Each line is taken from the buffer stdin shares across the process,
+/// leaving the lines after it there,
+/// so input is not lost when there is more than one core FFI.
+/// Stdin is only locked while a line is being read.
+#[derive(Debug, Default)]
+pub struct SharedStdin {
+    line:     Vec<u8>,
+    position: usize,
+}
+
+impl SharedStdin {
+    pub fn new() -> SharedStdin {
+        SharedStdin::default()
+    }
+}
+
+impl Read for SharedStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let amount = available.len().min(buf.len());
+        buf[..amount].copy_from_slice(&available[..amount]);
+        self.consume(amount);
+        Ok(amount)
+    }
+}
+
+impl BufRead for SharedStdin {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position >= self.line.len() {
+            self.line.clear();
+            self.position = 0;
+            stdin().lock().read_until(b'\n', &mut self.line)?;
+        }
+        Ok(&self.line[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.line.len());
+    }
+}
@@ -0,0 +1,128 @@
+use crate::common::data::Data;
+use crate::core::extract::binop;
+
+// NOTE: lists have value semantics, so these functions never mutate a list in place.
+// Instead, they take ownership of the lists passed to them and return a new list,
+// reusing the storage of the first list rather than copying it.
+
+/// Returns a new list with an item appended to the end,
+/// i.e. `([1, 2], 3)` becomes `[1, 2, 3]`.
+/// The original list is left unchanged.
+pub fn push(data: Data) -> Result<Data, String> {
+    match binop(data) {
+        (Data::List(mut items), item) => {
+            items.push(item);
+            Ok(Data::List(items))
+        },
+        _ => Err("Expected a list and an item to push onto it".to_string()),
+    }
+}
+
+/// Returns a new list with the items of the second list after the first,
+/// i.e. `([1, 2], [3])` becomes `[1, 2, 3]`.
+/// Neither of the original lists are changed.
+pub fn concat(data: Data) -> Result<Data, String> {
+    match binop(data) {
+        (Data::List(mut left), Data::List(mut right)) => {
+            left.append(&mut right);
+            Ok(Data::List(left))
+        },
+        _ => Err("Expected two lists to concatenate".to_string()),
+    }
+}
+
+/// Returns a new list with the items in reverse order,
+/// i.e. `[1, 2, 3]` becomes `[3, 2, 1]`.
+/// The original list is left unchanged.
+pub fn reverse(data: Data) -> Result<Data, String> {
+    match data {
+        Data::List(mut items) => {
+            items.reverse();
+            Ok(Data::List(items))
+        },
+        _ => Err("Expected a list to reverse".to_string()),
+    }
+}
+
+/// Returns a new list with the items sorted in ascending order,
+/// i.e. `[3, 1, 2]` becomes `[1, 2, 3]`.
+/// The original list is left unchanged.
+/// Every item must be of the same type, and be one of:
+///
+/// - Integers, ordered numerically.
+/// - Reals, ordered numerically, with `-0.0` before `0.0`,
+///   and NaNs after every other number.
+/// - Strings or characters, ordered by unicode codepoint, one character at a time,
+///   so `"B"` comes before `"a"`, and `"a"` before `"ab"`.
+///
+/// The sort is stable, and raises a runtime error if the items can not be ordered.
+pub fn sort(data: Data) -> Result<Data, String> {
+    let mut items = match data {
+        Data::List(items) => items,
+        _ => return Err("Expected a list to sort".to_string()),
+    };
+
+    let orderable = match items.first() {
+        None => return Ok(Data::List(items)),
+        Some(Data::Integer(_)) => |d: &Data| matches!(d, Data::Integer(_)),
+        Some(Data::Real(_))    => |d: &Data| matches!(d, Data::Real(_)),
+        Some(Data::String(_))  => |d: &Data| matches!(d, Data::String(_)),
+        Some(Data::Char(_))    => |d: &Data| matches!(d, Data::Char(_)),
+        Some(other) => return Err(format!(
+            "Can only sort integers, reals, strings, or characters, found '{}'", other,
+        )),
+    };
+
+    if let Some(other) = items.iter().find(|item| !orderable(item)) {
+        return Err(format!(
+            "Can only sort items of the same type, found '{:?}' and '{:?}'", items[0], other,
+        ));
+    }
+
+    items.sort_by(|l, r| match (l, r) {
+        (Data::Integer(l), Data::Integer(r)) => l.cmp(r),
+        (Data::Real(l),    Data::Real(r))    => l.total_cmp(r),
+        (Data::String(l),  Data::String(r))  => l.cmp(r),
+        (Data::Char(l),    Data::Char(r))    => l.cmp(r),
+        _ => unreachable!("Items to sort were checked to be of the same type"),
+    });
+
+    Ok(Data::List(items))
+}
+
+/// Pairs up the items of two lists, returning a list of pairs,
+/// i.e. `([1, 2], ["a", "b"])` becomes `[(1, "a"), (2, "b")]`.
+/// If one list is longer than the other, its extra items are left out.
+pub fn zip(data: Data) -> Result<Data, String> {
+    let (left, right) = match data {
+        Data::Tuple(t) if t.len() == 2 => {
+            let mut items = t.into_iter();
+            (items.next().unwrap(), items.next().unwrap())
+        },
+        other => return Err(format!("Expected a pair of lists to zip together, found '{}'", other)),
+    };
+
+    match (left, right) {
+        (Data::List(left), Data::List(right)) => Ok(Data::List(
+            left.into_iter()
+                .zip(right)
+                .map(|(l, r)| Data::Tuple(vec![l, r]))
+                .collect()
+        )),
+        _ => Err("Expected two lists to zip together".to_string()),
+    }
+}
+
+/// Pairs each item of a list with its index, returning a list of pairs,
+/// i.e. `["a", "b"]` becomes `[(0, "a"), (1, "b")]`.
+pub fn enumerate(data: Data) -> Result<Data, String> {
+    match data {
+        Data::List(items) => Ok(Data::List(
+            items.into_iter()
+                .enumerate()
+                .map(|(i, item)| Data::Tuple(vec![Data::Integer(i as i64), item]))
+                .collect()
+        )),
+        _ => Err("Expected a list to enumerate".to_string()),
+    }
+}
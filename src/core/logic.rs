@@ -1,16 +1,7 @@
 use crate::common::data::Data;
 use crate::core::extract::binop;
 
-// TODO: implement equality rather than just deriving PartialEq on Data.
-
-// Rust hit it right on the nose with the difference between equality and partial equality
-// TODO: equality vs partial equality in passerine?
-
-/// Returns `true` if the `Data` are equal, false otherwise.
-pub fn equal(data: Data) -> Result<Data, String> {
-    let (left, right) = binop(data);
-    Ok(Data::Boolean(left == right))
-}
+// NOTE: equality is implemented by the VM, see `VM::equal`.
 
 pub fn greater(data: Data) -> Result<Data, String> {
     // TODO: type coercion
@@ -55,3 +46,18 @@ pub fn less_equal(data: Data) -> Result<Data, String> {
 
     Ok(Data::Boolean(result))
 }
+
+/// Checks whether an item is in a list, compared by value,
+/// or whether a string or character is in a string, i.e. `x in xs`.
+pub fn contains(data: Data) -> Result<Data, String> {
+    let result = match binop(data) {
+        (item,              Data::List(items))    => items.contains(&item),
+        (Data::String(sub), Data::String(string)) => string.contains(sub.as_str()),
+        (Data::Char(c),     Data::String(string)) => string.contains(c),
+        (item, other) => return Err(format!(
+            "Can not check whether '{}' is in '{}', expected a list or a string", item, other,
+        )),
+    };
+
+    Ok(Data::Boolean(result))
+}
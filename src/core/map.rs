@@ -0,0 +1,57 @@
+use std::convert::TryFrom;
+
+use crate::common::{
+    data::Data,
+    key::Key,
+    map::Map,
+};
+use crate::core::extract::{binop, triop};
+
+// NOTE: like lists, maps have value semantics,
+// so inserting into a map returns a new map.
+
+/// Builds a map from a list of key-value pairs,
+/// i.e. `[("a", 1), ("b", 2)]`.
+/// Later pairs replace earlier pairs with the same key.
+/// Raises an error if a key can not be used as a key, see `Key`.
+pub fn map_from(data: Data) -> Result<Data, String> {
+    let pairs = match data {
+        Data::List(pairs) => pairs,
+        _ => return Err("Expected a list of key-value pairs".to_string()),
+    };
+
+    let mut map = Map::new();
+    for pair in pairs {
+        let (key, value) = match pair {
+            Data::Tuple(t) if t.len() == 2 => binop(Data::Tuple(t)),
+            _ => return Err("Expected a list of key-value pairs".to_string()),
+        };
+        map.insert(Key::try_from(key)?, value);
+    }
+
+    Ok(Data::Map(map))
+}
+
+/// Returns a new map with a key set to a value, i.e. `(map, key, value)`.
+/// The original map is left unchanged.
+pub fn insert(data: Data) -> Result<Data, String> {
+    match triop(data) {
+        (Data::Map(mut map), key, value) => {
+            map.insert(Key::try_from(key)?, value);
+            Ok(Data::Map(map))
+        },
+        _ => Err("Expected a map, a key, and a value to insert".to_string()),
+    }
+}
+
+/// Returns the value of a key in a map, i.e. `(map, key)`.
+/// Raises an error if the key is not in the map.
+pub fn get(data: Data) -> Result<Data, String> {
+    match binop(data) {
+        (Data::Map(map), key) => {
+            let key = Key::try_from(key)?;
+            map.get(&key).cloned().ok_or_else(|| format!("The key {} is not in the map", key))
+        },
+        _ => Err("Expected a map and a key to look up".to_string()),
+    }
+}
@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 use crate::common::data::Data;
 use crate::core::extract::binop;
 
@@ -46,14 +48,34 @@ pub fn mul(data: Data) -> Result<Data, String> {
     Ok(result)
 }
 
+/// The error raised when dividing by zero,
+/// no matter whether the numbers are integers or reals.
+pub const DIVISION_BY_ZERO: &str = "Division by zero";
+
+/// Raises an error if a divisor is zero.
+/// Reals are checked too, so that they raise the same error as integers
+/// rather than silently producing an infinity or NaN.
+/// Note that `-0.0` is zero as well.
+fn check_divisor(divisor: &Data) -> Result<(), String> {
+    match divisor {
+        Data::Integer(0) => Err(DIVISION_BY_ZERO.to_string()),
+        Data::Real(r) if *r == 0.0 => Err(DIVISION_BY_ZERO.to_string()),
+        _ => Ok(()),
+    }
+}
+
 /// Division between two numbers.
-/// Raises a runtime error if there is a division by zero.
+/// Raises a runtime error if there is a division by zero,
+/// or if integer division overflows, i.e. the smallest integer divided by `-1`.
 pub fn div(data: Data) -> Result<Data, String> {
-    let result = match binop(data) {
-        (Data::Real(_), Data::Real(n)) if n == 0.0 => return Err("Division by zero".to_string()),
+    let (left, right) = binop(data);
+    check_divisor(&right)?;
+
+    let result = match (left, right) {
         (Data::Real(l), Data::Real(r)) => Data::Real(l / r),
-        (Data::Integer(_), Data::Integer(n)) if n == 0 => return Err("Division by zero".to_string()),
-        (Data::Integer(l), Data::Integer(r)) => Data::Integer(l / r),
+        (Data::Integer(l), Data::Integer(r)) => Data::Integer(
+            l.checked_div(r).ok_or_else(|| "Integer overflow in division".to_string())?
+        ),
         _ => return Err("Division between unsupported datatypes".to_string()),
     };
 
@@ -61,13 +83,16 @@ pub fn div(data: Data) -> Result<Data, String> {
 }
 
 /// rem of left operand by right operand division.
-/// Raises a runtime error if there is a division by zero.
+/// Like `div`, raises a runtime error if there is a division by zero or an overflow.
 pub fn rem(data: Data) -> Result<Data, String> {
-    let result = match binop(data) {
-        (Data::Real(_),   Data::Real(r)) if r == 0.0 => return Err("Division by zero".to_string()),
+    let (left, right) = binop(data);
+    check_divisor(&right)?;
+
+    let result = match (left, right) {
         (Data::Real(l),   Data::Real(r)) => Data::Real(l.rem_euclid(r)),
-        (Data::Integer(_), Data::Integer(n)) if n == 0 => return Err("Division by zero".to_string()),
-        (Data::Integer(l), Data::Integer(r)) => Data::Integer(l.rem_euclid(r)),
+        (Data::Integer(l), Data::Integer(r)) => Data::Integer(
+            l.checked_rem_euclid(r).ok_or_else(|| "Integer overflow in division".to_string())?
+        ),
         _ => return Err("Division between unsupported datatypes".to_string()),
     };
 
@@ -84,3 +109,351 @@ pub fn pow(data: Data) -> Result<Data, String> {
 
     Ok(result)
 }
+
+/// Extracts the two integers an explicitly checked or saturating operation is applied to.
+fn integers(data: Data, operation: &str) -> Result<(i64, i64), String> {
+    match binop(data) {
+        (Data::Integer(l), Data::Integer(r)) => Ok((l, r)),
+        _ => Err(format!("{} is only supported between integers", operation)),
+    }
+}
+
+/// Raises a runtime error if a checked operation overflowed.
+fn checked(result: Option<i64>, operation: &str) -> Result<Data, String> {
+    result.map(Data::Integer)
+        .ok_or_else(|| format!("Integer overflow in checked {}", operation))
+}
+
+/// Addition between two integers,
+/// raising a runtime error rather than wrapping around on overflow.
+pub fn checked_add(data: Data) -> Result<Data, String> {
+    let (l, r) = integers(data, "Checked addition")?;
+    checked(l.checked_add(r), "addition")
+}
+
+/// Subtraction between two integers,
+/// raising a runtime error rather than wrapping around on overflow.
+pub fn checked_sub(data: Data) -> Result<Data, String> {
+    let (l, r) = integers(data, "Checked subtraction")?;
+    checked(l.checked_sub(r), "subtraction")
+}
+
+/// Multiplication between two integers,
+/// raising a runtime error rather than wrapping around on overflow.
+pub fn checked_mul(data: Data) -> Result<Data, String> {
+    let (l, r) = integers(data, "Checked multiplication")?;
+    checked(l.checked_mul(r), "multiplication")
+}
+
+/// Addition between two integers,
+/// clamping the result to the largest or smallest integer on overflow.
+pub fn saturating_add(data: Data) -> Result<Data, String> {
+    let (l, r) = integers(data, "Saturating addition")?;
+    Ok(Data::Integer(l.saturating_add(r)))
+}
+
+/// Subtraction between two integers,
+/// clamping the result to the largest or smallest integer on overflow.
+pub fn saturating_sub(data: Data) -> Result<Data, String> {
+    let (l, r) = integers(data, "Saturating subtraction")?;
+    Ok(Data::Integer(l.saturating_sub(r)))
+}
+
+/// Multiplication between two integers,
+/// clamping the result to the largest or smallest integer on overflow.
+pub fn saturating_mul(data: Data) -> Result<Data, String> {
+    let (l, r) = integers(data, "Saturating multiplication")?;
+    Ok(Data::Integer(l.saturating_mul(r)))
+}
+
+/// Bitwise and between two integers.
+pub fn bit_and(data: Data) -> Result<Data, String> {
+    match binop(data) {
+        (Data::Integer(l), Data::Integer(r)) => Ok(Data::Integer(l & r)),
+        _ => Err("Bitwise and is only supported between integers".to_string()),
+    }
+}
+
+/// Bitwise or between two integers.
+pub fn bit_or(data: Data) -> Result<Data, String> {
+    match binop(data) {
+        (Data::Integer(l), Data::Integer(r)) => Ok(Data::Integer(l | r)),
+        _ => Err("Bitwise or is only supported between integers".to_string()),
+    }
+}
+
+/// Bitwise exclusive or between two integers.
+pub fn bit_xor(data: Data) -> Result<Data, String> {
+    match binop(data) {
+        (Data::Integer(l), Data::Integer(r)) => Ok(Data::Integer(l ^ r)),
+        _ => Err("Bitwise xor is only supported between integers".to_string()),
+    }
+}
+
+/// Checks that an integer can be shifted by some amount,
+/// i.e. that the amount is between 0 and 63.
+fn shift_amount(amount: i64) -> Result<u32, String> {
+    if (0..64).contains(&amount) {
+        Ok(amount as u32)
+    } else {
+        Err(format!("Can not shift an integer by {} bits, expected 0 to 63", amount))
+    }
+}
+
+/// Shifts an integer left by some number of bits.
+/// Raises a runtime error if the shift is negative or 64 bits or more.
+pub fn shift_left(data: Data) -> Result<Data, String> {
+    match binop(data) {
+        (Data::Integer(l), Data::Integer(r)) => Ok(Data::Integer(l << shift_amount(r)?)),
+        _ => Err("Shifts are only supported on integers".to_string()),
+    }
+}
+
+/// Shifts an integer right by some number of bits, preserving its sign.
+/// Raises a runtime error if the shift is negative or 64 bits or more.
+pub fn shift_right(data: Data) -> Result<Data, String> {
+    match binop(data) {
+        (Data::Integer(l), Data::Integer(r)) => Ok(Data::Integer(l >> shift_amount(r)?)),
+        _ => Err("Shifts are only supported on integers".to_string()),
+    }
+}
+
+/// Rounds a real to some number of decimal places, i.e. `(3.14159, 2)` becomes `3.14`.
+/// Halfway cases are rounded away from zero.
+/// Raises a runtime error if the number of places is negative.
+pub fn round(data: Data) -> Result<Data, String> {
+    let (n, places) = match data {
+        Data::Tuple(t) if t.len() == 2 => match (&t[0], &t[1]) {
+            (Data::Real(_), Data::Integer(p)) if *p < 0 => return Err(
+                "Can not round to a negative number of decimal places".to_string()
+            ),
+            (Data::Real(n), Data::Integer(p)) => (*n, *p as u64),
+            _ => return Err("Expected a real and an integer number of decimal places".to_string()),
+        },
+        other => return Err(format!(
+            "Expected a real and a number of decimal places to round it to, found '{}'", other,
+        )),
+    };
+
+    Ok(Data::Real(round_places(n, places)))
+}
+
+/// Rounds a real to some number of decimal places, rounding halfway cases away from zero.
+/// This is shared by `round` and by printing with `VM::float_precision`,
+/// so the two always agree.
+/// Asking for more places than a real can hold leaves it as is.
+pub fn round_places(n: f64, places: u64) -> f64 {
+    // past this 10^places is no longer finite
+    let places = places.min(f64::MAX_10_EXP as u64) as i32;
+    let scale  = 10f64.powi(places);
+    let scaled = n * scale;
+
+    // a real this large has no digits left that far past the decimal point
+    if !scaled.is_finite() { return n; }
+    scaled.round() / scale
+}
+
+/// The absolute value of a number, i.e. `-3` becomes `3`.
+/// Raises a runtime error if the absolute value of an integer does not fit.
+pub fn abs(data: Data) -> Result<Data, String> {
+    let result = match data {
+        Data::Real(n)    => Data::Real(n.abs()),
+        Data::Integer(n) => Data::Integer(n.checked_abs()
+            .ok_or_else(|| "Integer overflow while taking the absolute value".to_string())?),
+        other => return Err(format!("Expected a number to take the absolute value of, found '{}'", other)),
+    };
+
+    Ok(result)
+}
+
+/// Converts a real to an integer after rounding it in some way,
+/// used by `floor`, `ceil`, and `trunc`.
+/// Integers are already whole, so they are returned as is.
+/// Raises a runtime error if the rounded real does not fit in an integer.
+fn whole(data: Data, name: &str, rounding: fn(f64) -> f64) -> Result<Data, String> {
+    match data {
+        Data::Real(n) => {
+            let rounded = rounding(n);
+            if rounded.is_finite() && rounded >= i64::MIN as f64 && rounded < i64::MAX as f64 {
+                Ok(Data::Integer(rounded as i64))
+            } else {
+                Err(format!("The {} of {} does not fit in an integer", name, n))
+            }
+        },
+        Data::Integer(n) => Ok(Data::Integer(n)),
+        other => Err(format!("Expected a number to take the {} of, found '{}'", name, other)),
+    }
+}
+
+/// Rounds a number down to the nearest integer, i.e. `2.7` becomes `2` and `-2.7` becomes `-3`.
+pub fn floor(data: Data) -> Result<Data, String> {
+    whole(data, "floor", f64::floor)
+}
+
+/// Rounds a number up to the nearest integer, i.e. `2.2` becomes `3` and `-2.2` becomes `-2`.
+pub fn ceil(data: Data) -> Result<Data, String> {
+    whole(data, "ceiling", f64::ceil)
+}
+
+/// Rounds a number towards zero, i.e. `2.7` becomes `2` and `-2.7` becomes `-2`.
+pub fn trunc(data: Data) -> Result<Data, String> {
+    whole(data, "truncation", f64::trunc)
+}
+
+/// Converts a number to a real, so functions that always return reals
+/// can be applied to integers as well.
+fn real(data: Data, name: &str) -> Result<f64, String> {
+    match data {
+        Data::Real(n)    => Ok(n),
+        Data::Integer(n) => Ok(n as f64),
+        other => Err(format!("Expected a number to take the {} of, found '{}'", name, other)),
+    }
+}
+
+/// The square root of a number, which is always a real.
+/// Raises a runtime error if the number is negative.
+pub fn sqrt(data: Data) -> Result<Data, String> {
+    let n = real(data, "square root")?;
+
+    if n < 0.0 {
+        return Err(format!("Can not take the square root of a negative number, found {}", n));
+    }
+
+    Ok(Data::Real(n.sqrt()))
+}
+
+/// The sine of an angle in radians.
+pub fn sin(data: Data) -> Result<Data, String> {
+    Ok(Data::Real(real(data, "sine")?.sin()))
+}
+
+/// The cosine of an angle in radians.
+pub fn cos(data: Data) -> Result<Data, String> {
+    Ok(Data::Real(real(data, "cosine")?.cos()))
+}
+
+/// The tangent of an angle in radians.
+pub fn tan(data: Data) -> Result<Data, String> {
+    Ok(Data::Real(real(data, "tangent")?.tan()))
+}
+
+/// Euler's number raised to the power of a number.
+pub fn exp(data: Data) -> Result<Data, String> {
+    Ok(Data::Real(real(data, "exponential")?.exp()))
+}
+
+/// Raises a runtime error if a logarithm is taken of a number that isn't positive.
+fn positive(n: f64, name: &str) -> Result<f64, String> {
+    if n > 0.0 {
+        Ok(n)
+    } else {
+        Err(format!("Can not take the {} of {}, expected a positive number", name, n))
+    }
+}
+
+/// The natural logarithm of a number.
+/// Raises a runtime error if the number is not positive.
+pub fn ln(data: Data) -> Result<Data, String> {
+    let n = positive(real(data, "natural logarithm")?, "natural logarithm")?;
+    Ok(Data::Real(n.ln()))
+}
+
+/// The base 10 logarithm of a number.
+/// Raises a runtime error if the number is not positive.
+pub fn log10(data: Data) -> Result<Data, String> {
+    let n = positive(real(data, "base 10 logarithm")?, "base 10 logarithm")?;
+    Ok(Data::Real(n.log10()))
+}
+
+/// Returns the smallest of some numbers, i.e. `(3, 2)` becomes `2`.
+/// Takes either a tuple or a list of numbers of the same type.
+/// Raises a runtime error if the list is empty.
+pub fn min(data: Data) -> Result<Data, String> {
+    extreme(data, Ordering::Less)
+}
+
+/// Returns the largest of some numbers, i.e. `(3, 2)` becomes `3`.
+/// Takes either a tuple or a list of numbers of the same type.
+/// Raises a runtime error if the list is empty.
+pub fn max(data: Data) -> Result<Data, String> {
+    extreme(data, Ordering::Greater)
+}
+
+/// Adds up some numbers of the same type, i.e. `[1, 2, 3]` becomes `6`.
+/// Takes either a tuple or a list of numbers.
+/// The sum of an empty list is `0`.
+/// Raises a runtime error if adding integers overflows.
+pub fn sum(data: Data) -> Result<Data, String> {
+    reduce(data, "sum", 0, i64::checked_add, |l, r| l + r)
+}
+
+/// Multiplies together some numbers of the same type, i.e. `[2, 3, 4]` becomes `24`.
+/// Takes either a tuple or a list of numbers.
+/// The product of an empty list is `1`.
+/// Raises a runtime error if multiplying integers overflows.
+pub fn product(data: Data) -> Result<Data, String> {
+    reduce(data, "product", 1, i64::checked_mul, |l, r| l * r)
+}
+
+/// Combines some numbers of the same type from left to right,
+/// starting from the first number, or `identity` if there are none.
+fn reduce(
+    data:     Data,
+    name:     &str,
+    identity: i64,
+    integer:  fn(i64, i64) -> Option<i64>,
+    real:     fn(f64, f64) -> f64,
+) -> Result<Data, String> {
+    let numbers = match data {
+          Data::Tuple(items)
+        | Data::List(items) => items,
+        _ => return Err(format!("Expected a tuple or list of numbers to find the {} of", name)),
+    };
+
+    let mut result = None;
+    for number in numbers {
+        result = Some(match (result, number) {
+            (None, n @ Data::Integer(_)) | (None, n @ Data::Real(_)) => n,
+            (Some(Data::Integer(l)), Data::Integer(r)) => Data::Integer(
+                integer(l, r).ok_or_else(|| format!("Integer overflow in {}", name))?
+            ),
+            (Some(Data::Real(l)), Data::Real(r)) => Data::Real(real(l, r)),
+            (_, n @ Data::Integer(_)) | (_, n @ Data::Real(_)) => return Err(format!(
+                "Expected numbers of the same type to find the {} of, found '{}'", name, n,
+            )),
+            (_, other) => return Err(format!(
+                "Expected numbers to find the {} of, found '{}'", name, other,
+            )),
+        });
+    }
+
+    Ok(result.unwrap_or(Data::Integer(identity)))
+}
+
+/// Finds the first number that no other number is ordered before, given an ordering.
+fn extreme(data: Data, ordering: Ordering) -> Result<Data, String> {
+    let mut numbers = match data {
+          Data::Tuple(items)
+        | Data::List(items) => items.into_iter(),
+        _ => return Err("Expected a tuple or list of numbers".to_string()),
+    };
+
+    let mut best = numbers.next()
+        .ok_or_else(|| "Expected at least one number".to_string())?;
+
+    for number in numbers {
+        let compared = match (&number, &best) {
+            (Data::Integer(l), Data::Integer(r)) => l.cmp(r),
+            (Data::Real(l),    Data::Real(r))    => l.partial_cmp(r)
+                .ok_or_else(|| "Can not compare NaN".to_string())?,
+            _ => return Err("Expected numbers of the same type".to_string()),
+        };
+
+        if compared == ordering { best = number; }
+    }
+
+    match best {
+        Data::Integer(_) | Data::Real(_) => Ok(best),
+        _ => Err("Expected a tuple or list of numbers".to_string()),
+    }
+}
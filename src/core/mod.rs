@@ -8,37 +8,122 @@ pub mod math;
 pub mod io;
 pub mod control;
 pub mod logic;
+pub mod list;
+pub mod map;
+pub mod string;
+pub mod file;
 
-use ffi::{FFI, FFIFunction};
+use std::io::BufRead;
 
-// Returns the core FFI used by Passerine.
-// Implements basic langauge features, like addition.
+use ffi::{FFI, FFIFunction, Capability};
+
+/// Returns the core FFI used by Passerine.
+/// Implements basic langauge features, like addition.
+/// Input is read from stdin, see `io::SharedStdin`.
 pub fn ffi_core() -> FFI {
+    ffi_core_with_input(io::SharedStdin::new())
+}
+
+/// Returns the core FFI, reading input from something other than stdin.
+/// This is useful for testing programs that read input.
+pub fn ffi_core_with_input<R: BufRead + 'static>(input: R) -> FFI {
     let mut ffi = FFI::new();
 
-    // math
-    ffi.add("add", FFIFunction::new(Box::new(math::add))).unwrap();
-    ffi.add("sub", FFIFunction::new(Box::new(math::sub))).unwrap();
-    ffi.add("neg", FFIFunction::new(Box::new(math::neg))).unwrap();
-    ffi.add("mul", FFIFunction::new(Box::new(math::mul))).unwrap();
-    ffi.add("div", FFIFunction::new(Box::new(math::div))).unwrap();
-    ffi.add("rem", FFIFunction::new(Box::new(math::rem))).unwrap();
-    ffi.add("pow", FFIFunction::new(Box::new(math::pow))).unwrap();
+    register_math(&mut ffi).unwrap();
 
     // io
-    ffi.add("println",   FFIFunction::new(Box::new(io::println))).unwrap();
-    ffi.add("print",     FFIFunction::new(Box::new(io::print))).unwrap();
-    ffi.add("to_string", FFIFunction::new(Box::new(io::to_string))).unwrap();
+    ffi.add("println",   FFIFunction::new(Box::new(io::println)).with_arity(1)).unwrap();
+    ffi.add("print",     FFIFunction::new(Box::new(io::print)).with_arity(1)).unwrap();
+    ffi.add("to_string", FFIFunction::new(Box::new(io::to_string)).with_arity(1)).unwrap();
+    ffi.add("read_line", io::read_line(input).with_arity(0)).unwrap();
 
     // control
-    ffi.add("if", FFIFunction::new(Box::new(control::if_choice))).unwrap();
+    ffi.add("if", FFIFunction::new(Box::new(control::if_choice)).with_arity(3)).unwrap();
+    ffi.add("make_error",    FFIFunction::new(Box::new(control::make_error)).with_arity(1)).unwrap();
+    ffi.add("error_message", FFIFunction::new(Box::new(control::error_message)).with_arity(1)).unwrap();
 
     // logic
-    ffi.add("equal",         FFIFunction::new(Box::new(logic::equal))).unwrap();
-    ffi.add("greater",       FFIFunction::new(Box::new(logic::greater))).unwrap();
-    ffi.add("less",          FFIFunction::new(Box::new(logic::less))).unwrap();
-    ffi.add("greater_equal", FFIFunction::new(Box::new(logic::greater_equal))).unwrap();
-    ffi.add("less_equal",    FFIFunction::new(Box::new(logic::less_equal))).unwrap();
+    ffi.add("greater",       FFIFunction::new(Box::new(logic::greater)).with_arity(2)).unwrap();
+    ffi.add("less",          FFIFunction::new(Box::new(logic::less)).with_arity(2)).unwrap();
+    ffi.add("greater_equal", FFIFunction::new(Box::new(logic::greater_equal)).with_arity(2)).unwrap();
+    ffi.add("less_equal",    FFIFunction::new(Box::new(logic::less_equal)).with_arity(2)).unwrap();
+    ffi.add("contains",      FFIFunction::new(Box::new(logic::contains)).with_arity(2)).unwrap();
+
+    // list
+    ffi.add("push",   FFIFunction::new(Box::new(list::push)).with_arity(2)).unwrap();
+    ffi.add("concat", FFIFunction::new(Box::new(list::concat)).with_arity(2)).unwrap();
+    ffi.add("reverse", FFIFunction::new(Box::new(list::reverse)).with_arity(1)).unwrap();
+    ffi.add("sort",    FFIFunction::new(Box::new(list::sort)).with_arity(1)).unwrap();
+    ffi.add("zip",       FFIFunction::new(Box::new(list::zip)).with_arity(2)).unwrap();
+    ffi.add("enumerate", FFIFunction::new(Box::new(list::enumerate)).with_arity(1)).unwrap();
+
+    // map
+    ffi.add("map_from", FFIFunction::new(Box::new(map::map_from)).with_arity(1)).unwrap();
+    ffi.add("insert",   FFIFunction::new(Box::new(map::insert)).with_arity(3)).unwrap();
+    ffi.add("get",      FFIFunction::new(Box::new(map::get)).with_arity(2)).unwrap();
+
+    // string
+    ffi.add("len",      FFIFunction::new(Box::new(string::len)).with_arity(1)).unwrap();
+    ffi.add("byte_len", FFIFunction::new(Box::new(string::byte_len)).with_arity(1)).unwrap();
+    ffi.add("is_empty", FFIFunction::new(Box::new(string::is_empty)).with_arity(1)).unwrap();
+    ffi.add("split",    FFIFunction::new(Box::new(string::split)).with_arity(2)).unwrap();
+    ffi.add("join",     FFIFunction::new(Box::new(string::join)).with_arity(2)).unwrap();
+    ffi.add("concat_all", FFIFunction::new(Box::new(string::concat_all)).with_arity(1)).unwrap();
+    ffi.add("format",   FFIFunction::new(Box::new(string::format))).unwrap();
+
+    // file
+    ffi.add("read_file",  FFIFunction::with_capability(Box::new(file::read_file),  Capability::FileSystem).with_arity(1)).unwrap();
+    ffi.add("write_file", FFIFunction::with_capability(Box::new(file::write_file), Capability::FileSystem).with_arity(2)).unwrap();
+
+    // reflection, which knows about every function above
+    let mut arities = ffi.arities();
+    arities.insert("arity".to_string(), 1);
+    arities.insert("native".to_string(), 1);
+    ffi.add("arity", control::arity(arities).with_arity(1)).unwrap();
+    ffi.add("native", control::native(ffi.functions()).with_arity(1)).unwrap();
 
     ffi
 }
+
+/// Adds the math functions, like addition and `sqrt`, to an FFI.
+/// These are part of the core FFI,
+/// but they can also be registered on their own, as a unit.
+/// Returns an error if any of them has already been added.
+pub fn register_math(ffi: &mut FFI) -> Result<(), String> {
+    ffi.add("add", FFIFunction::new(Box::new(math::add)).with_arity(2))?;
+    ffi.add("sub", FFIFunction::new(Box::new(math::sub)).with_arity(2))?;
+    ffi.add("neg", FFIFunction::new(Box::new(math::neg)).with_arity(1))?;
+    ffi.add("mul", FFIFunction::new(Box::new(math::mul)).with_arity(2))?;
+    ffi.add("div", FFIFunction::new(Box::new(math::div)).with_arity(2))?;
+    ffi.add("rem", FFIFunction::new(Box::new(math::rem)).with_arity(2))?;
+    ffi.add("pow", FFIFunction::new(Box::new(math::pow)).with_arity(2))?;
+    ffi.add("bit_and",     FFIFunction::new(Box::new(math::bit_and)).with_arity(2))?;
+    ffi.add("bit_or",      FFIFunction::new(Box::new(math::bit_or)).with_arity(2))?;
+    ffi.add("bit_xor",     FFIFunction::new(Box::new(math::bit_xor)).with_arity(2))?;
+    ffi.add("shift_left",  FFIFunction::new(Box::new(math::shift_left)).with_arity(2))?;
+    ffi.add("shift_right", FFIFunction::new(Box::new(math::shift_right)).with_arity(2))?;
+    ffi.add("checked_add",    FFIFunction::new(Box::new(math::checked_add)).with_arity(2))?;
+    ffi.add("checked_sub",    FFIFunction::new(Box::new(math::checked_sub)).with_arity(2))?;
+    ffi.add("checked_mul",    FFIFunction::new(Box::new(math::checked_mul)).with_arity(2))?;
+    ffi.add("saturating_add", FFIFunction::new(Box::new(math::saturating_add)).with_arity(2))?;
+    ffi.add("saturating_sub", FFIFunction::new(Box::new(math::saturating_sub)).with_arity(2))?;
+    ffi.add("saturating_mul", FFIFunction::new(Box::new(math::saturating_mul)).with_arity(2))?;
+    ffi.add("round", FFIFunction::new(Box::new(math::round)).with_arity(2))?;
+    ffi.add("abs",   FFIFunction::new(Box::new(math::abs)).with_arity(1))?;
+    ffi.add("floor", FFIFunction::new(Box::new(math::floor)).with_arity(1))?;
+    ffi.add("ceil",  FFIFunction::new(Box::new(math::ceil)).with_arity(1))?;
+    ffi.add("trunc", FFIFunction::new(Box::new(math::trunc)).with_arity(1))?;
+    ffi.add("sqrt",  FFIFunction::new(Box::new(math::sqrt)).with_arity(1))?;
+    ffi.add("sin",   FFIFunction::new(Box::new(math::sin)).with_arity(1))?;
+    ffi.add("cos",   FFIFunction::new(Box::new(math::cos)).with_arity(1))?;
+    ffi.add("tan",   FFIFunction::new(Box::new(math::tan)).with_arity(1))?;
+    ffi.add("exp",   FFIFunction::new(Box::new(math::exp)).with_arity(1))?;
+    ffi.add("ln",    FFIFunction::new(Box::new(math::ln)).with_arity(1))?;
+    ffi.add("log10", FFIFunction::new(Box::new(math::log10)).with_arity(1))?;
+    ffi.add("min",   FFIFunction::new(Box::new(math::min)))?;
+    ffi.add("max",   FFIFunction::new(Box::new(math::max)))?;
+    ffi.add("sum",     FFIFunction::new(Box::new(math::sum)).with_arity(1))?;
+    ffi.add("product", FFIFunction::new(Box::new(math::product)).with_arity(1))?;
+
+    Ok(())
+}
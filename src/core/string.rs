@@ -0,0 +1,140 @@
+use crate::common::data::Data;
+use crate::core::extract::binop;
+
+// NOTE: strings are UTF-8, so a character may take up more than one byte.
+// Lengths are counted in characters, i.e. unicode scalar values,
+// so `"héllo"` has a `len` of 5 but a `byte_len` of 6.
+
+/// Returns the number of characters in a string.
+pub fn len(data: Data) -> Result<Data, String> {
+    match data {
+        Data::String(s) => Ok(Data::Integer(s.chars().count() as i64)),
+        _ => Err("Expected a string to find the length of".to_string()),
+    }
+}
+
+/// Returns the number of bytes in the UTF-8 encoding of a string.
+pub fn byte_len(data: Data) -> Result<Data, String> {
+    match data {
+        Data::String(s) => Ok(Data::Integer(s.len() as i64)),
+        _ => Err("Expected a string to find the length in bytes of".to_string()),
+    }
+}
+
+/// Returns whether a string has no characters.
+pub fn is_empty(data: Data) -> Result<Data, String> {
+    match data {
+        Data::String(s) => Ok(Data::Boolean(s.is_empty())),
+        _ => Err("Expected a string to check whether it is empty".to_string()),
+    }
+}
+
+/// Splits a string on every occurrence of a separator, returning a list of strings,
+/// i.e. `("a,b,,c", ",")` becomes `["a", "b", "", "c"]`.
+/// Splitting an empty string yields a list with a single empty string,
+/// so that joining the parts with the same separator gives back the original.
+/// An empty separator splits a string into its characters, so `("", "")` becomes `[]`.
+pub fn split(data: Data) -> Result<Data, String> {
+    let (string, separator) = match binop(data) {
+        (Data::String(s), Data::String(sep)) => (s, sep),
+        _ => return Err("Expected a string and a string separator to split it on".to_string()),
+    };
+
+    let parts = if separator.is_empty() {
+        string.chars().map(|c| Data::String(c.to_string())).collect()
+    } else {
+        string.split(separator.as_str()).map(|s| Data::String(s.to_string())).collect()
+    };
+
+    Ok(Data::List(parts))
+}
+
+/// Joins a list of strings into one string, with a separator between each,
+/// i.e. `(["a", "b"], ", ")` becomes `"a, b"`.
+/// Joining an empty list yields an empty string.
+pub fn join(data: Data) -> Result<Data, String> {
+    let (items, separator) = match binop(data) {
+        (Data::List(l), Data::String(sep)) => (l, sep),
+        _ => return Err("Expected a list of strings and a string separator to join them with".to_string()),
+    };
+
+    let mut strings = vec![];
+    for item in items {
+        match item {
+            Data::String(s) => strings.push(s),
+            other => return Err(format!("Expected a list of strings to join, found '{}'", other)),
+        }
+    }
+
+    Ok(Data::String(strings.join(&separator)))
+}
+
+/// Concatenates a list of strings into one string,
+/// i.e. `["a", "b", "c"]` becomes `"abc"`.
+/// Concatenating an empty list yields an empty string.
+pub fn concat_all(data: Data) -> Result<Data, String> {
+    let items = match data {
+        Data::List(items) => items,
+        _ => return Err("Expected a list of strings to concatenate".to_string()),
+    };
+
+    let mut string = String::new();
+    for item in items {
+        match item {
+            Data::String(s) => string.push_str(&s),
+            other => return Err(format!("Expected a list of strings to concatenate, found '{}'", other)),
+        }
+    }
+
+    Ok(Data::String(string))
+}
+
+/// Substitutes arguments into a template string in order, using `Display`,
+/// i.e. `("{} + {} = {}", 1, 2, 3)` becomes `"1 + 2 = 3"`.
+/// `{{` and `}}` stand for literal braces.
+/// A template with no placeholders can be passed on its own.
+/// It's an error if the number of placeholders and arguments differ.
+pub fn format(data: Data) -> Result<Data, String> {
+    let expected = "Expected a format string, followed by the arguments to substitute into it";
+    let (template, arguments) = match data {
+        Data::String(template) => (template, vec![]),
+        Data::Tuple(items) => {
+            let mut items = items.into_iter();
+            match items.next() {
+                Some(Data::String(template)) => (template, items.collect::<Vec<Data>>()),
+                _ => return Err(expected.to_string()),
+            }
+        },
+        _ => return Err(expected.to_string()),
+    };
+
+    let mut formatted = String::new();
+    let mut placeholders = 0;
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) => { chars.next(); formatted.push('{'); },
+            ('}', Some('}')) => { chars.next(); formatted.push('}'); },
+            ('{', Some('}')) => {
+                chars.next();
+                if let Some(argument) = arguments.get(placeholders) {
+                    formatted.push_str(&argument.to_string());
+                }
+                placeholders += 1;
+            },
+            ('{', _) => return Err("Expected '}' to close the '{' in the format string, use '{{' for a literal brace".to_string()),
+            ('}', _) => return Err("Found an unmatched '}' in the format string, use '}}' for a literal brace".to_string()),
+            (other, _) => formatted.push(other),
+        }
+    }
+
+    if placeholders != arguments.len() {
+        return Err(format!(
+            "Expected {} arguments to fill the placeholders in the format string, found {}",
+            placeholders, arguments.len(),
+        ));
+    }
+
+    Ok(Data::String(formatted))
+}
@@ -0,0 +1,159 @@
+//! A single error type for everything that can go wrong
+//! while compiling or running a program,
+//! for tools that handle both kinds of errors in the same way.
+
+use std::fmt;
+
+use crate::common::span::Span;
+use crate::compiler::syntax::{Syntax, GenError};
+use crate::vm::trace::Trace;
+
+/// Either a static error raised while compiling,
+/// an error raised while generating bytecode,
+/// or a runtime error raised by the VM.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasserineError {
+    Syntax(Syntax),
+    Gen(GenError),
+    Trace(Trace),
+}
+
+impl PasserineError {
+    /// The stage of the pipeline that raised the error,
+    /// either `"compile"` or `"runtime"`.
+    pub fn stage(&self) -> &'static str {
+        match self {
+            PasserineError::Syntax(_) => "compile",
+            PasserineError::Gen(_)    => "compile",
+            PasserineError::Trace(_)  => "runtime",
+        }
+    }
+
+    /// The message describing the error.
+    pub fn message(&self) -> String {
+        match self {
+            PasserineError::Syntax(s) => s.message.clone(),
+            PasserineError::Gen(g)    => g.message(),
+            PasserineError::Trace(t)  => t.message().to_string(),
+        }
+    }
+
+    /// Where the error occurred.
+    /// For runtime errors this is where the error was raised,
+    /// rather than any of the calls it propagated through.
+    pub fn span(&self) -> Span {
+        match self {
+            PasserineError::Syntax(s) => s.span.clone(),
+            PasserineError::Gen(g)    => g.span().clone(),
+            PasserineError::Trace(t)  => t.spans().first()
+                .cloned()
+                .unwrap_or_else(Span::empty),
+        }
+    }
+
+    /// Serializes the error as a JSON object, so it can be consumed by tooling:
+    /// ```plain
+    /// {"stage": "compile", "message": "...", "span": {"start": 4, "end": 7, "line": 1, "col": 5}}
+    /// ```
+    /// `start` and `end` are byte offsets into the source,
+    /// `line` and `col` start from 1, see `Span::line_col`.
+    /// If the error has no location, `span` is `null`.
+    pub fn to_json(&self) -> String {
+        let span = self.span();
+        let location = match span.line_col() {
+            Some((line, col)) => format!(
+                "{{\"start\": {}, \"end\": {}, \"line\": {}, \"col\": {}}}",
+                span.offset, span.end(), line, col,
+            ),
+            None => "null".to_string(),
+        };
+
+        format!(
+            "{{\"stage\": {}, \"message\": {}, \"span\": {}}}",
+            json_string(self.stage()), json_string(&self.message()), location,
+        )
+    }
+}
+
+/// Quotes a string for use in JSON, escaping characters as needed.
+fn json_string(string: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in string.chars() {
+        match c {
+            '"'  => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+impl From<Syntax> for PasserineError {
+    fn from(syntax: Syntax) -> PasserineError {
+        PasserineError::Syntax(syntax)
+    }
+}
+
+impl From<GenError> for PasserineError {
+    fn from(error: GenError) -> PasserineError {
+        PasserineError::Gen(error)
+    }
+}
+
+impl From<Trace> for PasserineError {
+    fn from(trace: Trace) -> PasserineError {
+        PasserineError::Trace(trace)
+    }
+}
+
+impl fmt::Display for PasserineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasserineError::Syntax(s) => fmt::Display::fmt(s, f),
+            PasserineError::Gen(g)    => fmt::Display::fmt(g, f),
+            PasserineError::Trace(t)  => fmt::Display::fmt(t, f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::common::source::Source;
+
+    #[test]
+    fn parse_error_json() {
+        let source = Source::source("x = 1\n(a + b) = \"q\"");
+        let error = crate::compile(source).unwrap_err();
+        assert_eq!(
+            error.to_json(),
+            "{\"stage\": \"compile\", \"message\": \"Unexpected construct inside pattern, the left side of an assignment must be a variable or a pattern\", \
+             \"span\": {\"start\": 6, \"end\": 13, \"line\": 2, \"col\": 1}}",
+        );
+    }
+
+    #[test]
+    fn gen_error_json() {
+        let source = Source::source("magic \"nope\" 1");
+        let error = crate::compile(source).unwrap_err();
+        assert!(matches!(error, super::PasserineError::Gen(_)));
+        assert_eq!(
+            error.to_json(),
+            "{\"stage\": \"compile\", \"message\": \"The ffi function 'nope' is not defined\", \
+             \"span\": {\"start\": 0, \"end\": 14, \"line\": 1, \"col\": 1}}",
+        );
+    }
+
+    #[test]
+    fn runtime_error_json() {
+        let source = Source::source("error \"bad \\\"input\\\"\"");
+        let json = crate::run(crate::compile(source).unwrap()).unwrap_err().to_json();
+        assert!(json.starts_with("{\"stage\": \"runtime\", \"message\": \"bad \\\"input\\\"\", \"span\": {"));
+    }
+}
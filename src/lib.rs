@@ -21,28 +21,29 @@
 //! [passerine.io](https://www.passerine.io/#install).
 //!
 //! ## Embedding Passerine in Rust
-//! > TODO: Clean up crate visibility, create `run` function.
+//! > TODO: Clean up crate visibility.
 //!
 //! Add passerine to your `Cargo.toml`:
 //! ```toml
 //! # make sure this is the latest version
-//! passerine = 0.9
+//! passerine = "0.10"
 //! ```
 //! Then simply:
-//! ```ignore
-//! // DISCLAIMER: The `run` function used here has not been implemented yet,
-//! //             although the underlying interface is mostly stable.
-//!
-//! use passerine;
+//! ```
+//! use passerine::{common::source::Source, compile, run};
 //!
 //! fn main() {
-//!     passerine::run("print \"Hello from Passerine!\"");
+//!     let source = Source::source("println \"Hello from Passerine!\"");
+//!     run(compile(source).unwrap()).unwrap();
 //! }
 //! ```
 //!
+//! Compiling and running are separate steps,
+//! so a compiled [`Closure`] can be kept around and run more than once.
+//!
 //! ## Overview of the compilation process
 //! > NOTE: For a more detail, read through the documentation
-//! for any of the components mentioned.
+//! > for any of the components mentioned.
 //!
 //! Within the compiler pipeline, source code is represented as a `Source` object.
 //! A source is a reference to some code, with an associated path
@@ -91,6 +92,8 @@
 //! errors during execution.
 //! A `Trace` is similar to `Syntax`, but it keeps track of
 //! multiple spans representing function calls and so on.
+//! The top-level `compile` and `run` functions report both kinds of error
+//! as an `error::PasserineError`.
 //!
 //! After this, raw `Lambda`s are passed to the `VM` to be run.
 //! before being run by the `VM`, `Lambdas` are wrapped in `Closure`s,
@@ -109,7 +112,7 @@
 //! #     .and_then(parse)
 //! #     .and_then(desugar)
 //! #     .and_then(hoist)
-//! #     .and_then(gen)
+//! #     .and_then(|sst| Ok(gen(sst)?))
 //! #     .unwrap());
 //! // Initialize a VM with some bytecode:
 //! let mut vm = VM::init(bytecode);
@@ -124,32 +127,56 @@ pub mod common;
 pub mod core;
 pub mod compiler;
 pub mod vm;
+pub mod error;
 
 // exported functions:
 // TODO: clean up exports
 
 use std::rc::Rc;
-use common::{closure::Closure, source::Source};
-use compiler::{lex, parse, desugar, hoist, gen::{gen, gen_with_ffi}, syntax::Syntax};
-use crate::core::ffi::FFI;
-use vm::{VM, trace::Trace};
-
-/// Compiles a [`Source`] to some bytecode.
-pub fn compile(source: Rc<Source>) -> Result<Closure, Syntax> {
-    let tokens   =   lex(source)?;
-    let ast      = parse(tokens)?;
-    let cst      =  desugar(ast)?;
-    let sst      =    hoist(cst)?;
-    let bytecode =      gen(sst)?;
+use common::{closure::Closure, source::Source, data::Data, span::Spanned};
+use compiler::{
+    lex, desugar, hoist,
+    import::{import, Resolver, FileResolver},
+    gen::gen_with_ffi,
+    syntax::Syntax,
+    ast::AST,
+};
+use crate::core::{ffi_core, ffi::FFI};
+use vm::{VM, RunStats};
+use error::PasserineError;
 
-    Ok(Closure::wrap(bytecode))
+/// Compiles a [`Source`] to some bytecode,
+/// running every step of the compiler pipeline in turn.
+/// This is the front door to the compiler;
+/// The resulting [`Closure`] can be passed to [`run`].
+/// Errors from any step are reported as a [`PasserineError`].
+pub fn compile(source: Rc<Source>) -> Result<Closure, PasserineError> {
+    compile_with_ffi(source, ffi_core())
+}
+
+/// Lexes and parses some source, returning the `AST`.
+/// This is meant for tools, like formatters,
+/// that need to inspect programs without compiling them.
+pub fn parse(source: Rc<Source>) -> Result<Spanned<AST>, Syntax> {
+    compiler::parse(lex(source)?)
 }
 
 /// Compiles a [`Source`] to some bytecode,
 /// With a specific [`FFI`].
-pub fn compile_with_ffi(source: Rc<Source>, ffi: FFI) -> Result<Closure, Syntax> {
-    let tokens   =            lex(source)?;
-    let ast      =          parse(tokens)?;
+/// Imported modules are read from the filesystem.
+pub fn compile_with_ffi(source: Rc<Source>, ffi: FFI) -> Result<Closure, PasserineError> {
+    compile_with_resolver(source, ffi, &mut FileResolver)
+}
+
+/// Compiles a [`Source`] to some bytecode,
+/// With a specific [`FFI`] and a [`Resolver`] to find imported modules.
+pub fn compile_with_resolver(
+    source:   Rc<Source>,
+    ffi:      FFI,
+    resolver: &mut dyn Resolver,
+) -> Result<Closure, PasserineError> {
+    let ast      =          parse(source)?;
+    let ast      = import(ast, resolver)?;
     let cst      =           desugar(ast)?;
     let sst      =             hoist(cst)?;
     let bytecode = gen_with_ffi(sst, ffi)?;
@@ -157,9 +184,217 @@ pub fn compile_with_ffi(source: Rc<Source>, ffi: FFI) -> Result<Closure, Syntax>
     Ok(Closure::wrap(bytecode))
 }
 
-/// Run a compiled [`Closure`].
-pub fn run(closure: Closure) -> Result<(), Trace> {
+/// Run a compiled [`Closure`] on a fresh [`VM`],
+/// returning the value the program evaluated to.
+/// Runtime errors are reported as a [`PasserineError`].
+pub fn run(closure: Closure) -> Result<Data, PasserineError> {
     let mut vm = VM::init(closure);
     vm.run()?;
-    Ok(())
+    Ok(vm.stack.pop_data())
+}
+
+/// Run a compiled [`Closure`] on a fresh [`VM`] with profiling enabled,
+/// returning the value the program evaluated to,
+/// along with the number of ops executed and how long it took.
+pub fn run_with_stats(closure: Closure) -> Result<(Data, RunStats), PasserineError> {
+    let mut vm = VM::with_profiling(closure);
+    vm.run()?;
+    let stats = vm.stats().expect("Profiling is enabled");
+    Ok((vm.stack.pop_data(), stats))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::span::Span;
+
+    #[test]
+    fn parse_program() {
+        let source = Source::source("f x");
+        let symbol = |name: &str, offset| Spanned::new(
+            AST::Symbol(name.to_string()),
+            Span::new(&source, offset, 1),
+        );
+
+        assert_eq!(
+            parse(source.clone()).unwrap().item,
+            AST::Block(vec![
+                Spanned::new(
+                    AST::Form(vec![symbol("f", 0), symbol("x", 2)]),
+                    Span::new(&source, 0, 3),
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn truncated_source() {
+        // errors about a missing token point just past the end of the source
+        let error = compile(Source::source("x = (")).unwrap_err();
+        assert_eq!(format!("{}", error), "\
+            In ./source:1:6\n   \
+               |\n \
+             1 | x = (\n   \
+               |      ^\n   \
+               |\n\
+            Syntax Error: Expected a closing paren, found end of source\
+        ");
+    }
+
+    #[test]
+    fn read_line() {
+        let input = std::io::Cursor::new("hello\r\nworld\n");
+        let ffi = crate::core::ffi_core_with_input(input);
+        let source = Source::source("
+            read_line = () -> magic \"read_line\" ()
+            first = read_line ()
+            second = read_line ()
+            (first, second)
+        ");
+
+        assert_eq!(
+            run(compile_with_ffi(source.clone(), ffi).unwrap()),
+            Ok(Data::Tuple(vec![
+                Data::String("hello".to_string()),
+                Data::String("world".to_string()),
+            ])),
+        );
+
+        // reading past the end of the input is an error
+        let ffi = crate::core::ffi_core_with_input(std::io::Cursor::new("once"));
+        let source = Source::source("read_line = () -> magic \"read_line\" (); read_line (); read_line ()");
+        assert!(run(compile_with_ffi(source, ffi).unwrap()).is_err());
+    }
+
+    #[test]
+    fn register_math() {
+        let mut ffi = crate::core::ffi::FFI::new();
+        crate::core::register_math(&mut ffi).unwrap();
+        let source = Source::source("sqrt = x -> magic \"sqrt\" x; sqrt (3 * 3)");
+        assert_eq!(run(compile_with_ffi(source, ffi).unwrap()), Ok(Data::Real(3.0)));
+
+        // the math functions can only be registered once
+        let mut ffi = crate::core::ffi_core();
+        assert!(crate::core::register_math(&mut ffi).is_err());
+    }
+
+    #[test]
+    fn keyword_arguments() {
+        use crate::core::ffi::FFIFunction;
+        use crate::common::key::Key;
+
+        let greet = |data| match data {
+            Data::Tuple(t) => match (&t[0], &t[1]) {
+                (Data::String(name), Data::Map(keywords)) => {
+                    let greeting = match keywords.get(&Key::String("greeting".to_string())) {
+                        Some(Data::String(g)) => g.clone(),
+                        _ => "hello".to_string(),
+                    };
+                    Ok(Data::String(format!("{}, {}", greeting, name)))
+                },
+                _ => Err("Expected a name".to_string()),
+            },
+            _ => Err("Expected a name".to_string()),
+        };
+        let point = |data| match data {
+            Data::Tuple(t) => match (&t[0], &t[1]) {
+                (positional, Data::Map(keywords)) => Ok(Data::Tuple(vec![
+                    positional.clone(),
+                    keywords.get(&Key::String("x".to_string())).cloned().unwrap_or(Data::Nil),
+                    keywords.get(&Key::String("y".to_string())).cloned().unwrap_or(Data::Nil),
+                ])),
+                _ => Err("Expected keyword arguments".to_string()),
+            },
+            _ => Err("Expected keyword arguments".to_string()),
+        };
+        let ffi = || {
+            let mut ffi = ffi_core();
+            ffi.add("greet", FFIFunction::with_keywords(Box::new(greet), &["greeting"])).unwrap();
+            ffi.add("point", FFIFunction::with_keywords(Box::new(point), &["x", "y"])).unwrap();
+            ffi
+        };
+        let greet = |source: &str| run(compile_with_ffi(Source::source(source), ffi()).unwrap());
+
+        assert_eq!(greet("magic \"greet\" \"world\""), Ok(Data::String("hello, world".to_string())));
+        assert_eq!(
+            greet("magic \"greet\" (\"world\", greeting: \"hi\")"),
+            Ok(Data::String("hi, world".to_string())),
+        );
+
+        // keyword arguments are collected separately, even when there are no positional arguments
+        assert_eq!(
+            greet("magic \"point\" (x: 1, y: 2)"),
+            Ok(Data::Tuple(vec![Data::Unit, Data::Integer(1), Data::Integer(2)])),
+        );
+        assert_eq!(
+            greet("magic \"point\" (1, 2, y: 3)"),
+            Ok(Data::Tuple(vec![
+                Data::Tuple(vec![Data::Integer(1), Data::Integer(2)]),
+                Data::Nil,
+                Data::Integer(3),
+            ])),
+        );
+
+        // passing a keyword the function doesn't take is an error
+        let error = greet("magic \"greet\" (\"world\", tone: \"loud\")").unwrap_err();
+        assert_eq!(error.message(), "This ffi function does not take the keyword argument 'tone'");
+        let error = greet("magic \"greet\" (tone: \"loud\", \"world\")").unwrap_err();
+        assert_eq!(error.message(), "This ffi function does not take the keyword argument 'tone'");
+
+        // as is passing keywords to a function that doesn't take any
+        let error = greet("magic \"add\" (1, 2, by: 3)").unwrap_err();
+        assert_eq!(error.message(), "This ffi function does not take keyword arguments");
+
+        // keyword arguments can't be used as ordinary data
+        let error = compile(Source::source("p = (x: 1); println p")).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "Keyword arguments can only be passed to FFI functions, i.e. `magic \"name\" (1, x: 2)`",
+        );
+        assert_eq!(greet("p = (1, 2); magic \"to_string\" p"), Ok(Data::String("(1, 2)".to_string())));
+
+        // or passed more than once
+        let error = compile_with_ffi(Source::source("magic \"point\" (x: 1, x: 2)"), ffi()).unwrap_err();
+        assert_eq!(error.message(), "The keyword argument 'x' was passed more than once");
+    }
+
+    #[test]
+    fn arity() {
+        let arity = |source: &str| run(compile(Source::source(source)).unwrap());
+
+        // functions of more than one argument take them one after the other
+        assert_eq!(arity("f = a b c -> a; magic \"arity\" f"), Ok(Data::Integer(3)));
+        assert_eq!(arity("magic \"arity\" (x -> x)"), Ok(Data::Integer(1)));
+        assert_eq!(arity("f = a -> { b = a; c -> b }; magic \"arity\" f"), Ok(Data::Integer(1)));
+        assert_eq!(arity("f = a b -> a; magic \"arity\" (f 1)"), Ok(Data::Integer(1)));
+
+        // natives report the arity they were declared with
+        assert_eq!(arity("magic \"arity\" \"add\""), Ok(Data::Integer(2)));
+        assert_eq!(arity("magic \"arity\" \"read_line\""), Ok(Data::Integer(0)));
+        assert_eq!(arity("magic \"arity\" \"arity\""), Ok(Data::Integer(1)));
+        assert_eq!(arity("add = magic \"native\" \"add\"; magic \"arity\" (add 1)"), Ok(Data::Integer(1)));
+        let error = arity("magic \"arity\" \"max\"").unwrap_err();
+        assert_eq!(error.message(), "The native function 'max' does not declare its arity");
+
+        let error = arity("magic \"arity\" 7").unwrap_err();
+        assert_eq!(error.message(), "Expected a function or the name of a native function, found '7'");
+    }
+
+    #[test]
+    fn compile_then_run() {
+        let source = Source::source("double = x -> x + x; double 21");
+        let closure = compile(source).unwrap();
+
+        // the same compiled closure can be run more than once
+        assert_eq!(run(closure.clone()), Ok(Data::Integer(42)));
+        assert_eq!(run(closure), Ok(Data::Integer(42)));
+    }
+
+    #[test]
+    fn run_stats() {
+        let closure = compile(Source::source("x = 1\nx + 2")).unwrap();
+        let (value, stats) = run_with_stats(closure).unwrap();
+        assert_eq!(value, Data::Integer(3));
+        assert_eq!(stats.instructions, 6);
+    }
 }
@@ -0,0 +1,189 @@
+use std::{
+    fmt::Debug,
+    rc::{Rc, Weak},
+    cell::RefCell,
+    collections::HashSet,
+};
+
+use crate::common::{
+    data::Data,
+    closure::{Closure, Capture},
+};
+
+/// Data shared between closures lives on the heap as reference-counted cells.
+/// Reference counting alone can't free a cycle,
+/// like a recursive closure that captures itself.
+/// A `Collector` keeps track of these cells,
+/// so that the ones no longer reachable from the `VM` can be freed.
+///
+/// Collection happens in two phases:
+/// first, every root is marked, and then unmarked cells are swept.
+pub trait Collector: Debug {
+    /// Starts tracking a newly heaped cell.
+    fn track(&mut self, cell: &Rc<RefCell<Data>>);
+
+    /// Returns whether enough cells have been tracked to warrant a collection.
+    fn should_collect(&self) -> bool;
+
+    /// Marks some data, and everything reachable from it, as live.
+    fn mark(&mut self, root: &Data);
+
+    /// Marks everything a closure strongly captures as live.
+    /// Weak captures do not keep their variables alive.
+    fn mark_closure(&mut self, closure: &Closure) {
+        for captured in closure.captures.iter() {
+            if let Capture::Strong(cell) = captured {
+                self.mark(&Data::Heaped(cell.clone()));
+            }
+        }
+    }
+
+    /// Frees every tracked cell that was not marked since the last sweep,
+    /// returning the number of cells collected.
+    fn sweep(&mut self) -> usize;
+}
+
+/// A simple mark-and-sweep `Collector`.
+/// Unreachable cells are collected by clearing their contents,
+/// which breaks any cycles they are a part of,
+/// so reference counting can do the rest.
+#[derive(Debug)]
+pub struct MarkSweep {
+    heap:      Vec<Weak<RefCell<Data>>>,
+    marked:    HashSet<*const RefCell<Data>>,
+    minimum:   usize,
+    threshold: usize,
+}
+
+impl MarkSweep {
+    /// Creates a new `MarkSweep` collector,
+    /// which will collect once `threshold` cells are being tracked.
+    /// After each collection, the threshold grows with the number of live cells.
+    pub fn new(threshold: usize) -> MarkSweep {
+        MarkSweep {
+            heap:    vec![],
+            marked:  HashSet::new(),
+            minimum: threshold,
+            threshold,
+        }
+    }
+
+    /// Returns the number of cells currently being tracked.
+    pub fn tracked(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+impl Collector for MarkSweep {
+    fn track(&mut self, cell: &Rc<RefCell<Data>>) {
+        self.heap.push(Rc::downgrade(cell));
+    }
+
+    fn should_collect(&self) -> bool {
+        self.heap.len() >= self.threshold
+    }
+
+    fn mark(&mut self, root: &Data) {
+        match root {
+            // only traverse cells that haven't been marked yet
+            Data::Heaped(cell) if self.marked.insert(Rc::as_ptr(cell)) => {
+                self.mark(&cell.borrow());
+            },
+            Data::Closure(closure) => self.mark_closure(closure),
+            Data::Partial(partial) => for arg in partial.args.iter() { self.mark(arg) },
+            Data::Label(_, data) => self.mark(data),
+              Data::Tuple(items)
+            | Data::List(items) => for item in items.iter() { self.mark(item) },
+            Data::Map(map) => for value in map.values() { self.mark(value) },
+            _ => (),
+        }
+    }
+
+    fn sweep(&mut self) -> usize {
+        let mut live = vec![];
+        let mut collected = 0;
+
+        for weak in self.heap.drain(..) {
+            let cell = match weak.upgrade() {
+                Some(cell) => cell,
+                // already freed by reference counting
+                None => continue,
+            };
+
+            if self.marked.contains(&Rc::as_ptr(&cell)) {
+                live.push(weak);
+            } else {
+                cell.replace(Data::Unit);
+                collected += 1;
+            }
+        }
+
+        self.heap = live;
+        self.marked.clear();
+        self.threshold = self.minimum.max(self.heap.len() * 2);
+        collected
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::lambda::Lambda;
+
+    /// Builds a closure that captures the cell it is stored in.
+    fn cycle() -> Rc<RefCell<Data>> {
+        let cell = Rc::new(RefCell::new(Data::Unit));
+        let mut closure = Closure::wrap(Rc::new(Lambda::empty()));
+        closure.captures.push(Capture::Strong(cell.clone()));
+        cell.replace(Data::Closure(Box::new(closure)));
+        cell
+    }
+
+    #[test]
+    fn unreachable_cycle() {
+        let mut gc = MarkSweep::new(1);
+        let cell = cycle();
+        gc.track(&cell);
+
+        let weak = Rc::downgrade(&cell);
+        drop(cell);
+        // reference counting alone can't free the cycle
+        assert!(weak.upgrade().is_some());
+
+        assert_eq!(gc.sweep(), 1);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(gc.tracked(), 0);
+    }
+
+    #[test]
+    fn reachable_cycle() {
+        let mut gc = MarkSweep::new(1);
+        let cell = cycle();
+        gc.track(&cell);
+
+        gc.mark(&Data::List(vec![Data::Heaped(cell.clone())]));
+        assert_eq!(gc.sweep(), 0);
+        assert!(matches!(&*cell.borrow(), Data::Closure(_)));
+        assert_eq!(gc.tracked(), 1);
+    }
+
+    #[test]
+    fn weak_capture() {
+        let mut gc = MarkSweep::new(1);
+        let cell = cycle();
+        gc.track(&cell);
+
+        // the closure is reachable, but its only capture is weak
+        let mut closure = match &*cell.borrow() {
+            Data::Closure(c) => c.clone(),
+            _ => unreachable!(),
+        };
+        closure.weaken(0);
+        drop(cell);
+
+        gc.mark(&Data::Closure(closure.clone()));
+        assert_eq!(gc.sweep(), 1);
+        assert!(closure.captures[0].is_weak());
+        assert!(closure.captures[0].upgrade().is_none());
+    }
+}
@@ -6,36 +6,127 @@ pub mod tag;
 pub mod stack;
 pub mod trace;
 pub mod slot;
-
-use std::mem;
+pub mod gc;
+pub mod random;
+
+use std::{
+    mem,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    cell::RefCell,
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
 
 use crate::common::{
     number::build_number,
     data::Data,
     opcode::Opcode,
     lambda::Captured,
-    closure::Closure,
+    closure::{Closure, Capture},
     span::Span,
 };
 
 use crate::vm::{
     trace::Trace,
-    slot::Suspend,
+    slot::{Slot, Suspend},
     stack::Stack,
+    gc::Collector,
+    random::Prng,
 };
 
+use crate::core::ffi::{Capability, FFIFunction, Partial};
+
+/// An error handler, installed by a `try` expression.
+/// If an error is raised while the handler is installed,
+/// the VM unwinds back to the state it was in when the handler was installed.
+#[derive(Debug, Clone)]
+pub struct Handler {
+    /// The index of the op to resume at.
+    pub target: usize,
+    /// The number of frames on the stack.
+    pub frames: usize,
+    /// The height of the stack.
+    pub height: usize,
+}
+
+/// Describes what happened when a single op was executed, see `VM::step_once`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepOutcome {
+    /// The op that was executed.
+    pub opcode: Opcode,
+    /// The number of values on the stack after the op, see `VM::stack_depth`.
+    pub stack_depth: usize,
+    /// The index of the next op to be executed in the current closure.
+    pub ip: usize,
+}
+
+/// How much work a `VM` has done, see `VM::stats`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunStats {
+    /// The number of ops executed.
+    pub instructions: usize,
+    /// The wall-clock time spent in `VM::run`.
+    pub elapsed: Duration,
+}
+
+/// Why `VM::run_to_breakpoint` returned control.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunState {
+    /// Execution paused upon reaching a line with a breakpoint.
+    Paused(usize),
+    /// There are no ops left to execute.
+    Finished,
+}
+
 /// A `VM` executes bytecode lambda closures.
 /// (That's a mouthful - think bytecode + some context).
 /// VM initialization overhead is tiny,
 /// and each VM's state is self-contained,
 /// so more than one can be spawned if needed.
+/// By default, data shared between closures is only reference counted,
+/// so cycles on the heap are leaked.
+/// A `Collector` can be provided to free them, see `VM::with_collector`.
 #[derive(Debug)]
 pub struct VM {
-    pub closure: Closure,
-    pub stack:   Stack,
-    pub ip:      usize,
+    pub closure:   Closure,
+    pub stack:     Stack,
+    pub ip:        usize,
+    pub handlers:  Vec<Handler>,
+    pub collector: Option<Box<dyn Collector>>,
+    /// Source lines execution pauses at, see `VM::run_to_breakpoint`.
+    pub breakpoints: HashSet<usize>,
+    /// The source line of the last op checked for a breakpoint.
+    last_line: Option<usize>,
+    /// What FFI functions are allowed to access, all capabilities by default.
+    /// Calling a function that needs a capability which has been removed is an error.
+    pub capabilities: HashSet<Capability>,
+    /// Whether comparing values of different types for equality,
+    /// such as `1 == true`, is a type error rather than `false`.
+    /// Off by default.
+    pub strict_equality: bool,
+    /// The maximum number of function calls that can be active at once,
+    /// see `VM::with_recursion_limit`.
+    /// Tail calls do not count towards this limit, as they do not push a new frame.
+    pub recursion_limit: usize,
+    /// How many times each opcode has been executed, indexed by byte,
+    /// if profiling is enabled, see `VM::with_profiling`.
+    counts: Option<Vec<usize>>,
+    /// The time spent running, only measured if profiling is enabled.
+    elapsed: Duration,
+    /// How many digits after the decimal point reals are printed with,
+    /// see `VM::float_precision`.
+    float_precision: Option<usize>,
+    /// The source of random numbers, seeded with the current time by default,
+    /// see `VM::seed`.
+    random: Prng,
+    /// Variables that outlive a single run, keyed by name, see `VM::with_globals`.
+    pub globals: HashMap<String, Data>,
 }
 
+/// The default maximum number of active function calls, see `VM::recursion_limit`.
+pub const DEFAULT_RECURSION_LIMIT: usize = 10_000;
+
 // NOTE: use Opcode::same and Opcode.to_byte() rather than actual bytes
 // Don't worry, the compiler *should* get rid of this overhead and just use bytes
 
@@ -47,13 +138,174 @@ impl VM {
     pub fn init(closure: Closure) -> VM {
         let mut vm = VM {
             closure,
-            stack: Stack::init(),
-            ip:    0,
+            stack:     Stack::init(),
+            ip:        0,
+            handlers:  vec![],
+            collector: None,
+            breakpoints: HashSet::new(),
+            last_line:   None,
+            capabilities: Capability::ALL.iter().copied().collect(),
+            strict_equality: false,
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+            counts:          None,
+            elapsed:         Duration::ZERO,
+            float_precision: None,
+            random:          Prng::from_time(),
+            globals:         HashMap::new(),
         };
         vm.stack.declare(vm.closure.lambda.decls);
         vm
     }
 
+    /// Initialize a new VM that uses a `Collector` to free unreachable heap data.
+    /// Collection is triggered between instructions,
+    /// whenever the collector decides it should be.
+    pub fn with_collector(closure: Closure, collector: Box<dyn Collector>) -> VM {
+        let mut vm = VM::init(closure);
+        vm.collector = Some(collector);
+        vm
+    }
+
+    /// Initialize a new VM that allows at most `limit` function calls to be active at once.
+    /// Calling a function past this limit is a runtime error,
+    /// which catches runaway recursion before it eats all available memory.
+    pub fn with_recursion_limit(closure: Closure, limit: usize) -> VM {
+        let mut vm = VM::init(closure);
+        vm.recursion_limit = limit;
+        vm
+    }
+
+    /// Initialize a new VM with the global variables left behind by a previous run,
+    /// so that, e.g. in a REPL, each line can use the variables defined by the last.
+    /// Only code generated with `gen_with_globals` uses global variables.
+    pub fn with_globals(closure: Closure, globals: HashMap<String, Data>) -> VM {
+        let mut vm = VM::init(closure);
+        vm.globals = globals;
+        vm
+    }
+
+    /// Initialize a new VM that counts how many times each opcode is executed,
+    /// which is useful for finding hotspots.
+    /// Nothing is counted unless profiling is enabled, see `VM::profile`.
+    pub fn with_profiling(closure: Closure) -> VM {
+        let mut vm = VM::init(closure);
+        vm.counts = Some(vec![0; Opcode::ALL.len()]);
+        vm
+    }
+
+    /// Sets how many digits after the decimal point reals are printed with,
+    /// e.g. `3.14159` is printed as `3.14` with a precision of 2.
+    /// This is a number of decimal places, not significant digits,
+    /// so `31.4159` is printed as `31.42`, and `0.0314` as `0.03`.
+    /// By default, or with `None`, reals are printed in full,
+    /// i.e. the shortest representation that reads back as the same number.
+    pub fn float_precision(&mut self, precision: Option<usize>) {
+        self.float_precision = precision;
+    }
+
+    /// Seeds the source of random numbers used by `magic "random"` and `"random_int"`,
+    /// so that the same seed generates the same sequence of numbers.
+    pub fn seed(&mut self, seed: u64) {
+        self.random = Prng::new(seed);
+    }
+
+    /// Formats data as the `Print` op would print it,
+    /// respecting `VM::float_precision`.
+    pub fn display(&self, data: &Data) -> String {
+        format!("{}", data.with_precision(self.float_precision))
+    }
+
+    /// Returns how many times each opcode has been executed so far,
+    /// leaving out opcodes that have not been executed at all.
+    /// Returns `None` if profiling is not enabled.
+    pub fn profile(&self) -> Option<HashMap<Opcode, usize>> {
+        let counts = self.counts.as_ref()?;
+        Some(Opcode::ALL.iter()
+            .zip(counts.iter())
+            .filter(|(_, count)| **count > 0)
+            .map(|(opcode, count)| (*opcode, *count))
+            .collect())
+    }
+
+    /// Returns the total number of ops executed and the time spent in `VM::run` so far.
+    /// Returns `None` if profiling is not enabled, see `VM::with_profiling`.
+    pub fn stats(&self) -> Option<RunStats> {
+        let counts = self.counts.as_ref()?;
+        Some(RunStats {
+            instructions: counts.iter().sum(),
+            elapsed:      self.elapsed,
+        })
+    }
+
+    /// Returns an error if calling another function
+    /// would go past the `VM::recursion_limit`.
+    /// The initial frame is not a function call, so it is not counted.
+    fn check_recursion(&self, span: Span) -> Result<(), Trace> {
+        if self.stack.frames.len() > self.recursion_limit {
+            return Err(Trace::error(
+                "Recursion",
+                &format!("Maximum recursion depth of {} exceeded", self.recursion_limit),
+                vec![span],
+            ));
+        }
+        Ok(())
+    }
+
+    /// Runs a full collection, if the VM has a `Collector`.
+    /// Everything reachable from the stack, the globals, or the current closure is kept,
+    /// the rest is freed.
+    /// Returns the number of heap cells collected.
+    pub fn collect(&mut self) -> usize {
+        let collector = match &mut self.collector {
+            Some(collector) => collector,
+            None => return 0,
+        };
+
+        collector.mark_closure(&self.closure);
+        for tagged in self.stack.stack.iter() {
+            tagged.peek(|slot| match slot {
+                Slot::Data(data)    => collector.mark(data),
+                Slot::Suspend(s)    => collector.mark_closure(&s.closure),
+                Slot::Frame         => (),
+            });
+        }
+        for data in self.globals.values() {
+            collector.mark(data);
+        }
+
+        collector.sweep()
+    }
+
+    /// Returns the index of the current instruction in the current closure.
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    /// Returns the number of values on the stack, across all frames.
+    /// Frames themselves are not counted.
+    pub fn stack_depth(&self) -> usize {
+        self.stack.stack.len() - self.stack.frames.len()
+    }
+
+    /// Returns a copy of every value on the stack, from the bottom up.
+    /// Heaped values are copied out of the heap, and frames are left out.
+    /// This does not change the state of the `VM`,
+    /// so it can be used to inspect a `VM` after it has run, or failed.
+    pub fn snapshot(&self) -> Vec<Data> {
+        let mut values = vec![];
+
+        for tagged in self.stack.stack.iter() {
+            tagged.peek(|slot| if let Slot::Data(data) = slot {
+                values.push(match data {
+                    Data::Heaped(h) => h.borrow().clone(),
+                    d => d.clone(),
+                });
+            });
+        }
+
+        values
+    }
+
     /// Advances to the next instruction.
     #[inline]
     pub fn next(&mut self)                           { self.ip += 1; }
@@ -91,20 +343,35 @@ impl VM {
 
     // core interpreter loop
 
+    /// Decodes the current instruction as an opcode.
+    pub fn opcode(&mut self) -> Result<Opcode, Trace> {
+        match Opcode::from_byte(self.peek_byte()) {
+            Some(opcode) => Ok(opcode),
+            None => Err(Trace::error(
+                "Unknown Opcode",
+                &format!("The byte {} is not a valid opcode", self.peek_byte()),
+                vec![self.current_span()],
+            )),
+        }
+    }
+
     /// Dissasembles and interprets a single (potentially fallible) bytecode op.
     /// The op definitions follow in the next `impl` block.
     /// To see what each op does, check `common::opcode::Opcode`.
     pub fn step(&mut self) -> Result<(), Trace> {
-        let opcode = Opcode::from_byte(self.peek_byte());
+        let opcode = self.opcode()?;
+        if let Some(counts) = &mut self.counts { counts[opcode.to_byte() as usize] += 1; }
 
         match opcode {
             Opcode::Con     => self.con(),
+            Opcode::ConLong => self.con_long(),
             Opcode::NotInit => self.not_init(),
             Opcode::Del     => self.del(),
             Opcode::FFICall => self.ffi_call(),
-            Opcode::Copy    => self.copy_val(),
+            Opcode::Dup     => self.dup(),
             Opcode::Capture => self.capture(),
-            Opcode::Save    => self.save(),
+            Opcode::Declare => self.declare(),
+            Opcode::Mutate  => self.mutate(),
             Opcode::SaveCap => self.save_cap(),
             Opcode::Load    => self.load(),
             Opcode::LoadCap => self.load_cap(),
@@ -117,6 +384,34 @@ impl VM {
             Opcode::UnData  => self.un_data(),
             Opcode::UnLabel => self.un_label(),
             Opcode::UnTuple => self.un_tuple(),
+            Opcode::BuildRange => self.build_range(),
+            Opcode::List    => self.list(),
+            Opcode::Jump    => self.jump(),
+            Opcode::Next    => self.next_item(),
+            Opcode::Index   => self.index(),
+            Opcode::Raise   => self.raise(),
+            Opcode::Try     => self.try_catch(),
+            Opcode::EndTry  => self.end_try(),
+            Opcode::Swap    => self.swap(),
+            Opcode::Map     => self.map(),
+            Opcode::Filter  => self.filter(),
+            Opcode::Fold    => self.fold(),
+            Opcode::LoadUnit  => self.load_data(Data::Unit),
+            Opcode::LoadTrue  => self.load_data(Data::Boolean(true)),
+            Opcode::LoadFalse => self.load_data(Data::Boolean(false)),
+            Opcode::Nop       => self.done(),
+            Opcode::Equal     => self.equal(true),
+            Opcode::NotEqual  => self.equal(false),
+            Opcode::Propagate => self.propagate(),
+            Opcode::Exit      => self.exit(),
+            Opcode::Random    => self.random(),
+            Opcode::RandomInt => self.random_int(),
+            Opcode::GetGlobal => self.get_global(),
+            Opcode::SetGlobal => self.set_global(),
+            Opcode::JumpIfFalseNoPop => self.jump_if(false),
+            Opcode::JumpIfTrueNoPop  => self.jump_if(true),
+            Opcode::DelLocals => self.del_locals(),
+            Opcode::FFICallKeywords => self.ffi_call_keywords(),
         }
     }
 
@@ -130,36 +425,158 @@ impl VM {
         self.stack.push_not_init();
     }
 
+    /// Steps the VM, recovering from any error raised if there is a handler to unwind to.
+    /// Only handlers past the first `handlers` are used,
+    /// so an error raised in a nested call can't unwind past that call.
+    pub fn step_or_recover(&mut self, handlers: usize) -> Result<(), Trace> {
+        if let Err(trace) = self.step() {
+            if self.handlers.len() <= handlers { return Err(trace); }
+            let handler = self.handlers.pop().unwrap();
+            self.recover(handler, trace);
+        }
+        Ok(())
+    }
+
+    /// Calls a closure with an argument, running it until it returns,
+    /// and then returns the value it returned.
+    /// This is used by ops that call back into Passerine, like `Map`.
+    /// Errors raised in the closure are propagated to the caller of this function;
+    /// the closure's frames are left on the stack so the error traces through them.
+    /// Note that no garbage is collected until the closure returns.
+    pub fn call_closure(&mut self, closure: Closure, argument: Data) -> Result<Data, Trace> {
+        let ip       = self.ip;
+        let frames   = self.stack.frames.len();
+        let handlers = self.handlers.len();
+        self.check_recursion(self.current_span())?;
+
+        // suspend the calling context, which will resume right after this op
+        let old_closure = mem::replace(&mut self.closure, closure);
+        self.stack.push_frame(Suspend { ip: ip + 1, closure: old_closure });
+        self.ip = 0;
+
+        self.stack.declare(self.closure.lambda.decls);
+        self.stack.push_data(argument);
+
+        while self.stack.frames.len() > frames {
+            self.step_or_recover(handlers)?;
+        }
+
+        self.ip = ip;
+        Ok(self.stack.pop_data())
+    }
+
+    /// Recovers from an error by unwinding to the state saved in a `Handler`,
+    /// then pushing the error, as a `Data::Error`, onto the stack.
+    /// The error keeps the span it was first raised at.
+    pub fn recover(&mut self, handler: Handler, trace: Trace) {
+        while self.stack.frames.len() > handler.frames {
+            self.stack.unwind_frame();
+            self.unwind();
+        }
+
+        self.stack.stack.truncate(handler.height);
+        self.ip = handler.target;
+
+        let span = trace.spans().first().cloned().unwrap_or_else(Span::empty);
+        self.stack.push_data(Data::Error { message: trace.message().to_string(), span });
+    }
+
     /// Suspends the current lambda and runs a new one on the VM.
     /// Runs until either success, in which it restores the state of the previous lambda,
     /// Or failure, in which it returns the runtime error.
-    /// In the future, fibers will allow for error handling -
-    /// right now, error in Passerine are practically panics.
+    /// Errors raised inside of a `try` are caught by its handler instead,
+    /// and only errors that no handler catches are returned.
+    /// If profiling is enabled, the time spent running is measured, see `VM::stats`.
     pub fn run(&mut self) -> Result<(), Trace> {
+        let start = self.counts.as_ref().map(|_| Instant::now());
+
         // println!("Starting\n{}", self.closure.lambda);
         let mut result = Ok(());
-
         while !self.is_terminated() {
             // println!("before: {:#?}", self.stack.stack);
             // println!("executing: {:?}", Opcode::from_byte(self.peek_byte()));
-            result = self.step();
-            if result.is_err() { break; }
+            if let Err(trace) = self.step_once() {
+                result = Err(self.unwind_trace(trace));
+                break;
+            }
             // println!("---");
         }
         // println!("after: {:?}", self.stack.stack);
         // println!("---");
 
-        if let Err(mut trace) = result {
-            while self.stack.unwind_frame() {
-                self.unwind();
-                self.ip -= 1;
-                trace.add_context(self.current_span());
+        if let Some(start) = start { self.elapsed += start.elapsed(); }
+        result
+    }
+
+    /// Executes exactly one op, as `run` would,
+    /// recovering from errors raised inside of a `try`,
+    /// and collecting garbage if needed.
+    /// Returns the op that was executed and the resulting state of the `VM`.
+    /// If an error is not caught, it is returned without unwinding the stack,
+    /// so the state of the `VM` at the point of failure can still be inspected;
+    /// use `unwind_trace` to unwind afterwards.
+    pub fn step_once(&mut self) -> Result<StepOutcome, Trace> {
+        if self.is_terminated() {
+            return Err(Trace::error(
+                "Terminated",
+                "There are no ops left to execute",
+                vec![self.current_span()],
+            ));
+        }
+
+        let opcode = self.opcode()?;
+        self.step_or_recover(0)?;
+
+        if self.collector.as_ref().is_some_and(|c| c.should_collect()) {
+            self.collect();
+        }
+
+        Ok(StepOutcome { opcode, stack_depth: self.stack_depth(), ip: self.ip })
+    }
+
+    /// Sets a breakpoint on a line of source, starting from 1.
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    /// Removes a breakpoint set with `set_breakpoint`.
+    pub fn clear_breakpoint(&mut self, line: usize) {
+        self.breakpoints.remove(&line);
+    }
+
+    /// Runs like `run`, but pauses before executing the first op of a line with a breakpoint,
+    /// using the spans of the ops to determine which line each op is on.
+    /// Calling this function again resumes execution,
+    /// pausing again when execution next moves onto a line with a breakpoint.
+    pub fn run_to_breakpoint(&mut self) -> Result<RunState, Trace> {
+        while !self.is_terminated() {
+            if let Some((line, _)) = self.current_span().line_col() {
+                if self.last_line != Some(line) {
+                    self.last_line = Some(line);
+                    if self.breakpoints.contains(&line) {
+                        return Ok(RunState::Paused(line));
+                    }
+                }
+            }
+
+            if let Err(trace) = self.step_once() {
+                return Err(self.unwind_trace(trace));
             }
+        }
 
-            result = Err(trace);
-        };
+        Ok(RunState::Finished)
+    }
 
-        result
+    /// Unwinds the stack after an uncaught error,
+    /// adding the span of each call the error passed through to its trace.
+    pub fn unwind_trace(&mut self, mut trace: Trace) -> Trace {
+        while self.stack.unwind_frame() {
+            self.unwind();
+            self.ip -= 1;
+            trace.add_context(self.current_span());
+        }
+
+        trace
     }
 
     // TODO: there are a lot of optimizations that can be made
@@ -173,6 +590,16 @@ impl VM {
     /// Load a constant and push it onto the stack.
     #[inline]
     pub fn con(&mut self) -> Result<(), Trace> {
+        // the constant index is a single byte
+        let index = self.next_byte() as usize;
+
+        self.stack.push_data(self.closure.lambda.constants[index].clone());
+        self.done()
+    }
+
+    /// Load a constant with a large index and push it onto the stack.
+    #[inline]
+    pub fn con_long(&mut self) -> Result<(), Trace> {
         // get the constant index
         let index = self.next_number();
 
@@ -180,6 +607,14 @@ impl VM {
         self.done()
     }
 
+    /// Push a common constant, such as Unit or a boolean,
+    /// which is encoded in the opcode itself rather than in the constant pool.
+    #[inline]
+    pub fn load_data(&mut self, data: Data) -> Result<(), Trace> {
+        self.stack.push_data(data);
+        self.done()
+    }
+
     #[inline]
     pub fn not_init(&mut self) -> Result<(), Trace> {
         self.stack.push_not_init();
@@ -191,24 +626,57 @@ impl VM {
     #[inline]
     pub fn capture(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
-        self.stack.heapify(index);   // move value to the heap
+        let cell  = self.stack.heapify(index); // move value to the heap
+        if let Some(collector) = &mut self.collector { collector.track(&cell); }
+        self.done()
+    }
+
+    /// Save the topmost value on the stack into a variable,
+    /// the first time the variable is assigned to.
+    /// A declaration in a loop is run more than once,
+    /// so the variable may already hold a value.
+    /// Each run declares a new variable, so if the old one was captured,
+    /// the closures that captured it keep its value, rather than seeing the new one.
+    /// A variable captured before it is declared,
+    /// e.g. by a function that calls itself, is still assigned to in place.
+    #[inline]
+    pub fn declare(&mut self) -> Result<(), Trace> {
+        let index = self.next_number();
+        if self.stack.local_captured(index) { self.stack.del_local(index); }
+        self.stack.set_local(index);
         self.done()
     }
 
-    /// Save the topmost value on the stack into a variable.
+    /// Save the topmost value on the stack into a variable
+    /// that has already been declared.
+    /// Unlike `declare`, a captured variable is always updated in place,
+    /// so the closures that captured it see the new value.
+    /// The compiler guarantees that a declaration comes first,
+    /// but it may not have been run, e.g. if it is in a loop that never ran.
     #[inline]
-    pub fn save(&mut self) -> Result<(), Trace> {
+    pub fn mutate(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
         self.stack.set_local(index);
         self.done()
     }
 
+    /// Returns the variable captured at an index in the current closure.
+    /// Raises an error if the capture is weak and the variable has been freed.
+    fn captured(&self, index: usize) -> Result<Rc<RefCell<Data>>, Trace> {
+        self.closure.captures[index].upgrade().ok_or_else(|| Trace::error(
+            "Reference",
+            "This weakly captured variable has already been freed",
+            vec![self.current_span()],
+        ))
+    }
+
     /// Save the topmost value on the stack into a captured variable.
     #[inline]
     pub fn save_cap(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
         let data  = self.stack.pop_data();
-        mem::drop(self.closure.captures[index].replace(data));
+        let cell  = self.captured(index)?;
+        mem::drop(cell.replace(data));
         self.done()
     }
 
@@ -235,7 +703,7 @@ impl VM {
     #[inline]
     pub fn load_cap(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
-        let data = self.closure.captures[index].borrow().to_owned();
+        let data = self.captured(index)?.borrow().to_owned();
 
         if let Data::NotInit = data {
             return Err(Trace::error(
@@ -249,6 +717,41 @@ impl VM {
         self.done()
     }
 
+    /// Returns the name of a global variable, stored as a string constant.
+    fn global_name(&mut self) -> String {
+        let index = self.next_number();
+        match &self.closure.lambda.constants[index] {
+            Data::String(name) => name.clone(),
+            other => unreachable!("Expected the name of a global, found {:?}", other),
+        }
+    }
+
+    /// Push a copy of a global variable's value onto the stack.
+    #[inline]
+    pub fn get_global(&mut self) -> Result<(), Trace> {
+        let name = self.global_name();
+        let data = match self.globals.get(&name) {
+            Some(data) => data.clone(),
+            None => return Err(Trace::error(
+                "Reference",
+                &format!("The global variable '{}' was referenced before assignment", name),
+                vec![self.current_span()],
+            )),
+        };
+
+        self.stack.push_data(data);
+        self.done()
+    }
+
+    /// Save the topmost value on the stack into a global variable.
+    #[inline]
+    pub fn set_global(&mut self) -> Result<(), Trace> {
+        let name = self.global_name();
+        let data = self.stack.pop_data();
+        self.globals.insert(name, data);
+        self.done()
+    }
+
     /// Delete the top item of the stack.
     #[inline]
     pub fn del(&mut self) -> Result<(), Trace> {
@@ -256,20 +759,47 @@ impl VM {
         self.done()
     }
 
-    /// Copy the top data of the stack, i.e.
+    /// Delete the locals of a block that has exited,
+    /// so their values are dropped before the function returns.
+    /// Takes the index of the first local and how many there are.
+    #[inline]
+    pub fn del_locals(&mut self) -> Result<(), Trace> {
+        let first = self.next_number();
+        let count = self.next_number();
+        for index in first..first + count {
+            self.stack.del_local(index);
+        }
+        self.done()
+    }
+
+    /// Duplicate the top data of the stack, i.e.
     /// `[F, D]` becomes `[F, D, D]`.
     #[inline]
-    pub fn copy_val(&mut self) -> Result<(), Trace> {
+    pub fn dup(&mut self) -> Result<(), Trace> {
         let data = self.stack.pop_data();
         self.stack.push_data(data.clone());
         self.stack.push_data(data);
         self.done()
     }
 
+    /// Swap the top two data of the stack, i.e.
+    /// `[F, A, B]` becomes `[F, B, A]`.
+    #[inline]
+    pub fn swap(&mut self) -> Result<(), Trace> {
+        if !self.stack.swap_top() {
+            return Err(Trace::error(
+                "Stack",
+                "Expected two values on the stack to swap",
+                vec![self.current_span()],
+            ));
+        }
+        self.done()
+    }
+
     #[inline]
     pub fn print(&mut self) -> Result<(), Trace> {
         let data = self.stack.pop_data();
-        println!("{}", data);
+        println!("{}", self.display(&data));
         self.stack.push_data(data);
         self.done()
     }
@@ -298,101 +828,567 @@ impl VM {
         self.done()
     }
 
-    fn un_data(&mut self) -> Result<(), Trace> {
-        let expected = self.stack.pop_data();
-        let data = self.stack.pop_data();
-
-        if data != expected {
-            return Err(Trace::error(
-                "Pattern Matching",
-                &format!("The data '{}' does not match the expected data '{}'", data, expected),
-                vec![self.current_span()],
-            ));
+    /// Collects the top N values on the stack into a list.
+    #[inline]
+    pub fn list(&mut self) -> Result<(), Trace> {
+        let index = self.next_number();
+        let mut items = vec![];
+        for _ in 0..index {
+            items.push(self.stack.pop_data())
         }
 
+        items.reverse();
+        self.stack.push_data(Data::List(items));
         self.done()
     }
 
-    fn un_label(&mut self) -> Result<(), Trace> {
-        let kind = match self.stack.pop_data() {
-            Data::Kind(n) => n,
-            _ => unreachable!(),
+    /// Jumps to the index of another op in the current lambda.
+    #[inline]
+    pub fn jump(&mut self) -> Result<(), Trace> {
+        self.ip = self.next_number();
+        Ok(())
+    }
+
+    /// Jumps to the target if the boolean on top of the stack is `when`,
+    /// otherwise continues with the next op.
+    /// Either way, the boolean is left on the stack.
+    pub fn jump_if(&mut self, when: bool) -> Result<(), Trace> {
+        let target = self.next_number();
+        let condition = match self.stack.pop_data() {
+            Data::Boolean(b) => b,
+            other => return Err(Trace::error(
+                "Type",
+                &format!("Expected a boolean, found '{}'", other),
+                vec![self.current_span()],
+            )),
         };
+        self.stack.push_data(Data::Boolean(condition));
 
-        let d = match self.stack.pop_data() {
-            Data::Label(n, d) if *n == kind => d,
+        if condition == when {
+            self.ip = target;
+            Ok(())
+        } else {
+            self.done()
+        }
+    }
+
+    /// Advances an iterator kept on the stack as `[.., iterable, index]`.
+    /// Maps are iterated over as `(key, value)` pairs, in insertion order.
+    /// If the index is in bounds, the index is incremented,
+    /// and the item at that index is pushed: `[.., iterable, index + 1, item]`.
+    /// Otherwise, the stack is left untouched and ip jumps to target.
+    pub fn next_item(&mut self) -> Result<(), Trace> {
+        let target = self.next_number();
+
+        let index = match self.stack.pop_data() {
+            Data::Integer(i) => i,
+            _ => unreachable!("Expected an integer index to iterate with"),
+        };
+        let iterable = self.stack.pop_data();
+
+        let item = match &iterable {
+            Data::List(l) => l.get(index as usize).cloned(),
+            Data::Range(s, e) if s + index < *e => Some(Data::Integer(s + index)),
+            Data::Range(_, _) => None,
+            Data::Map(m) => m.get_index(index as usize)
+                .map(|(k, v)| Data::Tuple(vec![k.clone().into(), v.clone()])),
             other => return Err(Trace::error(
-                "Pattern Matching",
-                &format!("The data '{}' does not match the Label '{}'", other, kind),
+                "Type",
+                &format!("The data '{}' can not be iterated over", other),
                 vec![self.current_span()],
             )),
         };
 
-        self.stack.push_data(*d);
+        self.stack.push_data(iterable);
+        match item {
+            Some(data) => {
+                self.stack.push_data(Data::Integer(index + 1));
+                self.stack.push_data(data);
+                self.done()
+            },
+            None => {
+                self.stack.push_data(Data::Integer(index));
+                self.ip = target;
+                Ok(())
+            },
+        }
+    }
+
+    /// Constructs a range from the two integers on top of the stack,
+    /// i.e. `[.., start, end]` becomes `[.., start..end]`.
+    #[inline]
+    pub fn build_range(&mut self) -> Result<(), Trace> {
+        let end   = self.stack.pop_data();
+        let start = self.stack.pop_data();
+
+        let (s, e) = match (start, end) {
+            (Data::Integer(s), Data::Integer(e)) => (s, e),
+            (s, e) => return Err(Trace::error(
+                "Type",
+                &format!("Expected two integers to construct a range, found '{}' and '{}'", s, e),
+                vec![self.current_span()],
+            )),
+        };
+
+        self.stack.push_data(Data::Range(s, e));
         self.done()
     }
 
-    fn un_tuple(&mut self) -> Result<(), Trace> {
-        let index = self.next_number();
-        let t = match self.stack.pop_data() {
-            Data::Tuple(t) => t,
+    /// Indexes into the data on top of the stack,
+    /// i.e. `[.., data, index]` becomes `[.., item]`.
+    /// Indexing into a byte string yields an integer between 0 and 255,
+    /// and indexing into a string yields the character at that position.
+    /// If the index is a range, a slice of the data is taken instead, see `VM::slice`.
+    pub fn index(&mut self) -> Result<(), Trace> {
+        let index = self.stack.pop_data();
+        let data  = self.stack.pop_data();
+
+        let i = match index {
+            Data::Integer(i) => i,
+            Data::Range(start, end) => return self.slice(data, start, end),
             other => return Err(Trace::error(
-                "Pattern Matching",
-                &format!("The data '{}' is not a tuple", other),
+                "Type",
+                &format!("Expected an integer index or a range, found '{}'", other),
                 vec![self.current_span()],
             )),
         };
 
-        let length = t.len();
-        if index >= length {
-            return Err(Trace::error(
+        let item = match &data {
+            Data::List(l)  => usize::try_from(i).ok().and_then(|i| l.get(i).cloned()),
+            Data::Bytes(b) => usize::try_from(i).ok().and_then(|i| b.get(i))
+                .map(|b| Data::Integer(*b as i64)),
+            // strings are indexed by character, not by byte
+            Data::String(s) => usize::try_from(i).ok().and_then(|i| s.chars().nth(i))
+                .map(Data::Char),
+            other => return Err(Trace::error(
+                "Type",
+                &format!("The data '{}' can not be indexed into", other),
+                vec![self.current_span()],
+            )),
+        };
+
+        match item {
+            Some(item) => self.stack.push_data(item),
+            None => return Err(Trace::error(
                 "Indexing",
-                &format!(
-                    "The tuple '{}' is of length {}, so the index {} is out-of-bounds",
-                    Data::Tuple(t), length, index
-                ),
+                &format!("The index {} is out of bounds for '{}'", i, data),
                 vec![self.current_span()],
-            ));
+            )),
         }
 
-        let data = t[index].clone();
-        self.stack.push_data(Data::Tuple(t));
-        self.stack.push_data(data);
         self.done()
     }
 
-    /// Call a function on the top of the stack, passing the next value as an argument.
-    pub fn call(&mut self) -> Result<(), Trace> {
-        // get the function and argument to run
-        let fun = match self.stack.pop_data() {
-            Data::Closure(c) => *c,
-            o => return Err(Trace::error(
-                "Call",
-                &format!("The data '{}' is not a function and can not be called", o),
+    /// Takes the items of a list, byte string, or string
+    /// from the start of a range up to (but not including) the end.
+    /// Strings are sliced by character, not by byte.
+    /// Bounds are not clamped: a range that is backwards,
+    /// or reaches outside of the data, raises an error.
+    pub fn slice(&mut self, data: Data, start: i64, end: i64) -> Result<(), Trace> {
+        let length = match &data {
+            Data::List(l)   => l.len(),
+            Data::Bytes(b)  => b.len(),
+            Data::String(s) => s.chars().count(),
+            other => return Err(Trace::error(
+                "Type",
+                &format!("The data '{}' can not be sliced", other),
                 vec![self.current_span()],
             )),
         };
-        let arg = self.stack.pop_data();
 
-        // TODO: make all programs end in return,
-        // so bounds check (i.e. is_terminated) is never required
-        self.next();
-        let tail_call = !self.is_terminated()
-                     && Opcode::Return
-                     == Opcode::from_byte(self.peek_byte());
+        let bounds = usize::try_from(start).ok()
+            .zip(usize::try_from(end).ok())
+            .filter(|(s, e)| s <= e && *e <= length);
 
-        // clear the stack if there's a tail call
-        // we must do this before we suspend the calling context
-        if tail_call {
-            let locals = self.next_number();
-            for _ in 0..locals { self.del()?; }
-        }
+        let (s, e) = match bounds {
+            Some(bounds) => bounds,
+            None => return Err(Trace::error(
+                "Indexing",
+                &format!("The range {}..{} is out of bounds for '{}'", start, end, data),
+                vec![self.current_span()],
+            )),
+        };
 
-        // suspend the calling context
-        let old_closure = mem::replace(&mut self.closure, fun);
-        let old_ip      = mem::replace(&mut self.ip,      0);
-        let suspend = Suspend {
-            ip: old_ip,
+        let slice = match data {
+            Data::List(l)   => Data::List(l[s..e].to_vec()),
+            Data::Bytes(b)  => Data::Bytes(b[s..e].to_vec()),
+            Data::String(string) => Data::String(string.chars().skip(s).take(e - s).collect()),
+            _ => unreachable!(),
+        };
+
+        self.stack.push_data(slice);
+        self.done()
+    }
+
+    /// Raises a user error, using the data on top of the stack as the message.
+    /// An error caught by `try` is raised again with its message,
+    /// and the span it was first raised at.
+    pub fn raise(&mut self) -> Result<(), Trace> {
+        let message = match self.stack.pop_data() {
+            Data::String(s) => s,
+            // an error that was caught is raised again from where it was first raised
+            Data::Error { message, span } if !span.is_empty() => return Err(
+                Trace::error("User", &message, vec![span, self.current_span()])
+            ),
+            Data::Error { message, .. } => message,
+            other => format!("{}", other),
+        };
+
+        Err(Trace::error("User", &message, vec![self.current_span()]))
+    }
+
+    /// Compares the two items of a tuple, i.e. `[.., (a, b)]` becomes `[.., a == b]`.
+    /// If `equal` is false, pushes whether they are not equal instead.
+    /// Values of different types are never equal,
+    /// unless `VM::strict_equality` is set, in which case comparing them is an error,
+    /// as is comparing tuples, lists, maps, or labels holding items of different types.
+    pub fn equal(&mut self, equal: bool) -> Result<(), Trace> {
+        let (left, right) = match self.stack.pop_data() {
+            Data::Tuple(t) if t.len() == 2 => {
+                let mut items = t.into_iter();
+                (items.next().unwrap(), items.next().unwrap())
+            },
+            other => return Err(Trace::error(
+                "Type",
+                &format!("Expected a tuple of two values to compare, found '{}'", other),
+                vec![self.current_span()],
+            )),
+        };
+
+        if self.strict_equality {
+            if let Some((l, r)) = VM::mismatched(&left, &right) {
+                return Err(Trace::error(
+                    "Type",
+                    &format!("Can not compare '{}' and '{}', as they are of different types", l, r),
+                    vec![self.current_span()],
+                ));
+            }
+        }
+
+        self.stack.push_data(Data::Boolean((left == right) == equal));
+        self.done()
+    }
+
+    /// Finds the first pair of values of different types being compared,
+    /// looking inside tuples, lists, maps, and labels,
+    /// so that `(1, 2.0)` and `(1, 2)` don't match.
+    /// Items that are only on one side are not compared.
+    fn mismatched<'a>(left: &'a Data, right: &'a Data) -> Option<(&'a Data, &'a Data)> {
+        match (left, right) {
+            (Data::Tuple(l), Data::Tuple(r))
+            | (Data::List(l),  Data::List(r)) => l.iter().zip(r.iter())
+                .find_map(|(l, r)| VM::mismatched(l, r)),
+            (Data::Map(l), Data::Map(r)) => l.iter()
+                .filter_map(|(key, l)| r.get(key).map(|r| (l, r)))
+                .find_map(|(l, r)| VM::mismatched(l, r)),
+            (Data::Label(lk, l), Data::Label(rk, r)) if lk == rk => VM::mismatched(l, r),
+            _ if mem::discriminant(left) != mem::discriminant(right) => Some((left, right)),
+            _ => None,
+        }
+    }
+
+    /// Installs an error handler that resumes at a target index in the bytecode.
+    pub fn try_catch(&mut self) -> Result<(), Trace> {
+        let target = self.next_number();
+        self.handlers.push(Handler {
+            target,
+            frames: self.stack.frames.len(),
+            height: self.stack.stack.len(),
+        });
+        self.done()
+    }
+
+    /// Removes the topmost error handler,
+    /// wrapping the data on top of the stack as `Ok data`.
+    pub fn end_try(&mut self) -> Result<(), Trace> {
+        self.handlers.pop();
+        let data = self.stack.pop_data();
+        self.stack.push_data(Data::Label(Box::new("Ok".to_string()), Box::new(data)));
+        self.done()
+    }
+
+    /// Leaves the value on top of the stack as is, unless it is `nil`,
+    /// in which case the current function returns `nil` early, see `VM::exit`.
+    pub fn propagate(&mut self) -> Result<(), Trace> {
+        let data = self.stack.pop_data();
+        if data != Data::Nil {
+            self.stack.push_data(data);
+            return self.done();
+        }
+        self.exit_with(data)
+    }
+
+    /// Returns the value on top of the stack from the current function early.
+    pub fn exit(&mut self) -> Result<(), Trace> {
+        let data = self.stack.pop_data();
+        self.exit_with(data)
+    }
+
+    /// Returns a value from the current function,
+    /// wherever it is in its body.
+    /// Any handlers installed by the current function are removed,
+    /// and its locals and temporaries are dropped.
+    /// At the top level, execution ends with the value as the result.
+    fn exit_with(&mut self, data: Data) -> Result<(), Trace> {
+        let frames = self.stack.frames.len();
+        while self.handlers.last().is_some_and(|h| h.frames >= frames) {
+            self.handlers.pop();
+        }
+
+        if frames > 1 {
+            self.stack.unwind_frame();
+            let suspend = self.stack.pop_frame();
+            self.ip      = suspend.ip;
+            self.closure = suspend.closure;
+        } else {
+            // the top-level locals are kept, so the final state can still be inspected
+            let locals = self.stack.frames[0] + 1 + self.closure.lambda.decls;
+            self.stack.stack.truncate(locals);
+            self.ip = self.closure.lambda.code.len();
+        }
+
+        self.stack.push_data(data);
+        Ok(())
+    }
+
+    /// Generates a random real in the range `[0, 1)`, i.e. `[.., ()]` becomes `[.., 0.42]`.
+    pub fn random(&mut self) -> Result<(), Trace> {
+        match self.stack.pop_data() {
+            Data::Unit => (),
+            other => return Err(Trace::error(
+                "Type",
+                &format!("Expected no arguments, i.e. (), to generate a random number, found '{}'", other),
+                vec![self.current_span()],
+            )),
+        }
+
+        let real = self.random.next_real();
+        self.stack.push_data(Data::Real(real));
+        self.done()
+    }
+
+    /// Generates a random integer in a half-open range,
+    /// i.e. `[.., (1, 7)]` becomes `[.., 4]`, never `7`.
+    pub fn random_int(&mut self) -> Result<(), Trace> {
+        let bounds = match self.stack.pop_data() {
+            Data::Tuple(t) => match t.as_slice() {
+                [Data::Integer(low), Data::Integer(high)] => Some((*low, *high)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let int = bounds.and_then(|(low, high)| self.random.next_int(low, high))
+            .ok_or_else(|| Trace::error(
+                "Type",
+                "Expected a tuple of two integers (low, high), where low is less than high",
+                vec![self.current_span()],
+            ))?;
+
+        self.stack.push_data(Data::Integer(int));
+        self.done()
+    }
+
+    /// Returns the items in a list, range, or map, used by ops like `Map`.
+    /// The items of a map are its `(key, value)` pairs, in insertion order.
+    fn items(&self, data: Data) -> Result<Vec<Data>, Trace> {
+        match data {
+            Data::List(items) => Ok(items),
+            Data::Range(s, e) => Ok((s..e).map(Data::Integer).collect()),
+            Data::Map(m)      => Ok(m.into_iter()
+                .map(|(k, v)| Data::Tuple(vec![k.into(), v]))
+                .collect()),
+            other => Err(Trace::error(
+                "Type",
+                &format!("The data '{}' can not be iterated over", other),
+                vec![self.current_span()],
+            )),
+        }
+    }
+
+    /// Pops a tuple of a function followed by other arguments off the stack,
+    /// as passed to ops like `Map`.
+    fn callback_args(&mut self, arity: usize) -> Result<(Closure, Vec<Data>), Trace> {
+        let mut args = match self.stack.pop_data() {
+            Data::Tuple(t) if t.len() == arity => t,
+            other => return Err(Trace::error(
+                "Call",
+                &format!("Expected a tuple of a function and {} other values, found '{}'", arity - 1, other),
+                vec![self.current_span()],
+            )),
+        };
+
+        match args.remove(0) {
+            Data::Closure(c) => Ok((*c, args)),
+            o => Err(Trace::error(
+                "Call",
+                &format!("The data '{}' is not a function and can not be called", o),
+                vec![self.current_span()],
+            )),
+        }
+    }
+
+    /// Calls a function on each item of a list, collecting the results into a new list,
+    /// i.e. `[.., (f, [a, b])]` becomes `[.., [f a, f b]]`.
+    pub fn map(&mut self) -> Result<(), Trace> {
+        let (fun, mut args) = self.callback_args(2)?;
+        let items = self.items(args.remove(0))?;
+
+        let mut mapped = vec![];
+        for item in items {
+            mapped.push(self.call_closure(fun.clone(), item)?);
+        }
+
+        self.stack.push_data(Data::List(mapped));
+        self.done()
+    }
+
+    /// Keeps only the items of a list for which a function returns `true`,
+    /// i.e. `[.., (f, [a, b])]` becomes `[.., [a]]` if only `f a` is `true`.
+    pub fn filter(&mut self) -> Result<(), Trace> {
+        let (fun, mut args) = self.callback_args(2)?;
+        let items = self.items(args.remove(0))?;
+
+        let mut kept = vec![];
+        for item in items {
+            match self.call_closure(fun.clone(), item.clone())? {
+                Data::Boolean(true)  => kept.push(item),
+                Data::Boolean(false) => (),
+                other => return Err(Trace::error(
+                    "Type",
+                    &format!("Expected the function passed to filter to return a boolean, found '{}'", other),
+                    vec![self.current_span()],
+                )),
+            }
+        }
+
+        self.stack.push_data(Data::List(kept));
+        self.done()
+    }
+
+    /// Combines the items of a list, from first to last, using a function,
+    /// i.e. `[.., (f, init, [a, b])]` becomes `[.., f (f (init, a), b)]`.
+    pub fn fold(&mut self) -> Result<(), Trace> {
+        let (fun, mut args) = self.callback_args(3)?;
+        let items = self.items(args.remove(1))?;
+        let mut accumulator = args.remove(0);
+
+        for item in items {
+            accumulator = self.call_closure(fun.clone(), Data::Tuple(vec![accumulator, item]))?;
+        }
+
+        self.stack.push_data(accumulator);
+        self.done()
+    }
+
+    fn un_data(&mut self) -> Result<(), Trace> {
+        let expected = self.stack.pop_data();
+        let data = self.stack.pop_data();
+
+        if data != expected {
+            return Err(Trace::error(
+                "Pattern Matching",
+                &format!("The data '{}' does not match the expected data '{}'", data, expected),
+                vec![self.current_span()],
+            ));
+        }
+
+        self.done()
+    }
+
+    fn un_label(&mut self) -> Result<(), Trace> {
+        let kind = match self.stack.pop_data() {
+            Data::Kind(n) => n,
+            _ => unreachable!(),
+        };
+
+        let d = match self.stack.pop_data() {
+            Data::Label(n, d) if *n == kind => d,
+            Data::Error { message, .. } if kind == "Error" => Box::new(Data::String(message)),
+            other => return Err(Trace::error(
+                "Pattern Matching",
+                &format!("The data '{}' does not match the Label '{}'", other, kind),
+                vec![self.current_span()],
+            )),
+        };
+
+        self.stack.push_data(*d);
+        self.done()
+    }
+
+    fn un_tuple(&mut self) -> Result<(), Trace> {
+        let index = self.next_number();
+        let t = match self.stack.pop_data() {
+            Data::Tuple(t) => t,
+            other => return Err(Trace::error(
+                "Pattern Matching",
+                &format!("The data '{}' is not a tuple", other),
+                vec![self.current_span()],
+            )),
+        };
+
+        let length = t.len();
+        if index >= length {
+            return Err(Trace::error(
+                "Indexing",
+                &format!(
+                    "The tuple '{}' is of length {}, so the index {} is out-of-bounds",
+                    Data::Tuple(t), length, index
+                ),
+                vec![self.current_span()],
+            ));
+        }
+
+        let data = t[index].clone();
+        self.stack.push_data(Data::Tuple(t));
+        self.stack.push_data(data);
+        self.done()
+    }
+
+    /// Call a function on the top of the stack, passing the next value as an argument.
+    /// Every function takes exactly one argument:
+    /// `a b -> c` is desugared into `a -> b -> c`,
+    /// so calling it with a single argument returns a partially applied closure,
+    /// and each call accumulates one more argument until the body is reached.
+    /// Native functions, see `Partial`, accumulate arguments in the same way,
+    /// and are called once they have been applied to as many as their arity.
+    /// Calling the result of a fully applied function with more arguments
+    /// is an error, unless that result is itself a function.
+    pub fn call(&mut self) -> Result<(), Trace> {
+        // get the function and argument to run
+        let fun = match self.stack.pop_data() {
+            Data::Closure(c) => *c,
+            Data::Partial(p) => return self.call_partial(*p),
+            o => return Err(Trace::error(
+                "Call",
+                &format!("The data '{}' is not a function and can not be called", o),
+                vec![self.current_span()],
+            )),
+        };
+        let arg  = self.stack.pop_data();
+        let span = self.current_span();
+
+        // TODO: make all programs end in return,
+        // so bounds check (i.e. is_terminated) is never required
+        self.next();
+        let tail_call = !self.is_terminated()
+                     && Some(Opcode::Return)
+                     == Opcode::from_byte(self.peek_byte());
+
+        if !tail_call { self.check_recursion(span)?; }
+
+        // clear the stack if there's a tail call
+        // we must do this before we suspend the calling context
+        if tail_call {
+            let locals = self.next_number();
+            for _ in 0..locals { self.del()?; }
+        }
+
+        // suspend the calling context
+        let old_closure = mem::replace(&mut self.closure, fun);
+        let old_ip      = mem::replace(&mut self.ip,      0);
+        let suspend = Suspend {
+            ip: old_ip,
             closure: old_closure,
         };
 
@@ -413,11 +1409,29 @@ impl VM {
         Ok(())
     }
 
+    /// Applies a native function to the next value on the stack,
+    /// calling it if that was the last argument it was waiting on, see `VM::call`.
+    fn call_partial(&mut self, mut partial: Partial) -> Result<(), Trace> {
+        partial.args.push(self.stack.pop_data());
+
+        let result = if partial.remaining() == 0 {
+            let function = partial.function.clone();
+            self.call_native(&function, partial.into_argument(), vec![])?
+        } else {
+            Data::Partial(Box::new(partial))
+        };
+
+        self.stack.push_data(result);
+        self.done()
+    }
+
     /// Return a value from a function.
     /// End the execution of the current lambda.
     /// Takes the number of locals on the stack
     /// Relpaces the last frame with the value on the top of the stack.
     /// Expects the stack to be a `[..., Frame, Local 1, ..., Local N, Data]`
+    /// The result is popped before the locals are deleted,
+    /// so it survives even if the locals are captured or on the heap.
     pub fn return_val(&mut self) -> Result<(), Trace> {
         // the value to be returned
         let val = self.stack.pop_data();
@@ -450,7 +1464,7 @@ impl VM {
             let reference = match captured {
                 Captured::Local(index) => {
                     match self.stack.local_data(*index) {
-                        Data::Heaped(h) => h,
+                        Data::Heaped(h) => Capture::Strong(h),
                         _ => unreachable!("Expected data to be on the heap"),
                     }
                 },
@@ -465,19 +1479,62 @@ impl VM {
 
     pub fn ffi_call(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
-        let ffi_function = &self.closure.lambda.ffi[index];
+        let ffi_function = self.closure.lambda.ffi[index].clone();
 
         let argument = self.stack.pop_data();
-        let returned = match ffi_function.call(argument) {
-            Ok(d) => d,
-            Err(e) => return Err(Trace::error(
-                "FFI Call", &e, vec![self.current_span()],
-            )),
+        let returned = self.call_native(&ffi_function, argument, vec![])?;
+
+        self.stack.push_data(returned);
+        self.done()
+    }
+
+    /// Calls out to a Rust function via FFI, passing it keyword arguments,
+    /// i.e. `[.., argument, ((name, value), ..)]` becomes `[.., result]`.
+    /// See `FFIFunction::call_with_keywords`.
+    pub fn ffi_call_keywords(&mut self) -> Result<(), Trace> {
+        let index = self.next_number();
+        let ffi_function = self.closure.lambda.ffi[index].clone();
+
+        let keywords = match self.stack.pop_data() {
+            Data::Tuple(pairs) => pairs.into_iter().map(|pair| match pair {
+                Data::Tuple(pair) => match <[Data; 2]>::try_from(pair) {
+                    Ok([Data::String(name), value]) => (name, value),
+                    _ => unreachable!("Expected a keyword argument"),
+                },
+                _ => unreachable!("Expected a keyword argument"),
+            }).collect(),
+            _ => unreachable!("Expected a tuple of keyword arguments"),
         };
 
+        let argument = self.stack.pop_data();
+        let returned = self.call_native(&ffi_function, argument, keywords)?;
+
         self.stack.push_data(returned);
         self.done()
     }
+
+    /// Calls a native function with some keyword arguments,
+    /// so long as the VM has been granted the capability it needs, if any.
+    fn call_native(
+        &self,
+        ffi_function: &FFIFunction,
+        argument: Data,
+        keywords: Vec<(String, Data)>,
+    ) -> Result<Data, Trace> {
+        if let Some(capability) = ffi_function.capability() {
+            if !self.capabilities.contains(&capability) {
+                return Err(Trace::error(
+                    "Capability",
+                    &format!("This ffi function needs {} access, which has been denied", capability.name()),
+                    vec![self.current_span()],
+                ));
+            }
+        }
+
+        ffi_function.call_with_keywords(argument, keywords).map_err(|e| Trace::error(
+            "FFI Call", &e, vec![self.current_span()],
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -487,22 +1544,31 @@ mod test {
         lex::lex,
         parse::parse,
         desugar::desugar,
-        hoist::hoist,
-        gen::gen,
+        hoist::{hoist, hoist_with_globals},
+        gen::{gen, gen_with_globals},
     };
-    use crate::common::source::Source;
+    use crate::common::{
+        source::Source,
+        lambda::Lambda,
+        number::split_number,
+    };
+    use crate::vm::gc::MarkSweep;
 
-    fn inspect(source: &str) -> VM {
+    fn compile(source: &str) -> Closure {
         let lambda = lex(Source::source(source))
             .and_then(parse)
             .and_then(desugar)
             .and_then(hoist)
-            .and_then(gen)
+            .and_then(|sst| Ok(gen(sst)?))
             .map_err(|e| println!("{}", e))
             .unwrap();
 
         // println!("{:?}", lambda);
-        let mut vm = VM::init(Closure::wrap(lambda));
+        Closure::wrap(lambda)
+    }
+
+    fn inspect(source: &str) -> VM {
+        let mut vm = VM::init(compile(source));
 
         match vm.run() {
             Ok(()) => vm,
@@ -531,6 +1597,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn fun_scope() {
         // y = (x -> { y = x; y ) 7.0; y
         let mut vm = inspect("one = 1.0\npi = 3.14\ne = 2.72\n\nx = w -> pi\nx 37.6");
@@ -568,6 +1635,688 @@ mod test {
         ");
     }
 
+    #[test]
+    fn range() {
+        let mut vm = inspect("x = 3; 1..(x + 7)");
+        assert_eq!(vm.stack.pop_data(), Data::Range(1, 10));
+        assert_eq!(format!("{}", Data::Range(1, 10)), "1..10");
+    }
+
+    #[test]
+    fn trace_frames() {
+        let trace = VM::init(compile("
+            inner = x -> x + true
+            outer = y -> { z = inner y; z }
+            outer 1
+        ")).run().unwrap_err();
+        let frames = trace.spans().iter()
+            .map(|s| s.contents())
+            .collect::<Vec<_>>();
+        assert_eq!(frames, vec!["x + true", "inner y", "outer 1"]);
+    }
+
+    #[test]
+    fn user_error() {
+        let trace = VM::init(compile("x = 1\nerror \"bad input\"")).run().unwrap_err();
+        // raised inside of `error`, called on the second line
+        let spans = trace.spans().iter().map(|s| s.contents()).collect::<Vec<_>>();
+        assert_eq!(spans, vec!["error", "error \"bad input\""]);
+        assert!(format!("{}", trace).ends_with("Runtime User Error: bad input"));
+    }
+
+    #[test]
+    fn try_error() {
+        let mut vm = inspect("try (1 / 0)");
+        match vm.stack.pop_data() {
+            Data::Error { message, span } => {
+                assert_eq!(message, "Division by zero");
+                assert_eq!(span.contents(), "(1 / 0)");
+            },
+            other => panic!("Expected an error, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn float_precision() {
+        let mut vm = inspect("x = 3.14159; (x, [x, 2.5], 7)");
+        let printed = vm.stack.pop_data();
+
+        // reals are shown in full by default
+        assert_eq!(vm.display(&Data::Real(3.14159)), "3.14159");
+
+        vm.float_precision(Some(2));
+        assert_eq!(vm.display(&Data::Real(3.14159)), "3.14");
+        assert_eq!(vm.display(&printed), "(3.14, [3.14, 2.50], 7)");
+
+        // the precision counts decimal places, not significant digits
+        assert_eq!(vm.display(&Data::Real(31.4159)), "31.42");
+        assert_eq!(vm.display(&Data::Real(0.0314)), "0.03");
+
+        // halfway cases are rounded the same way as `magic "round"`
+        vm.float_precision(Some(1));
+        assert_eq!(vm.display(&Data::Real(0.25)), "0.3");
+
+        vm.float_precision(None);
+        assert_eq!(vm.display(&printed), "(3.14159, [3.14159, 2.5], 7)");
+    }
+
+    #[test]
+    fn globals_persist() {
+        let compile = |source: &str, globals: &[String]| {
+            let cst = desugar(parse(lex(Source::source(source)).unwrap()).unwrap()).unwrap();
+            gen_with_globals(hoist_with_globals(cst, globals).unwrap()).unwrap()
+        };
+
+        let first = compile("x = 40\ninc = n -> n + step\nstep = 1", &[]);
+        assert_eq!(first.decls, 0);
+        let mut vm = VM::init(Closure::wrap(first));
+        vm.run().unwrap();
+        assert_eq!(vm.globals["x"], Data::Integer(40));
+
+        // the second run picks up where the first left off
+        let names = vm.globals.keys().cloned().collect::<Vec<_>>();
+        let second = compile("step = 2\nx = inc x\nx", &names);
+        let mut vm = VM::with_globals(Closure::wrap(second), vm.globals);
+        vm.run().unwrap();
+        assert_eq!(vm.stack.pop_data(), Data::Integer(42));
+        assert_eq!(vm.globals["x"], Data::Integer(42));
+    }
+
+    #[test]
+    fn seeded_random() {
+        let closure = compile("
+            random = () -> magic \"random\" ()
+            random_int = r -> magic \"random_int\" r
+            (random (), random (), random_int (1, 7), random_int (1, 7))
+        ");
+
+        let run_seeded = |seed| {
+            let mut vm = VM::init(closure.clone());
+            vm.seed(seed);
+            vm.run().unwrap();
+            vm.stack.pop_data()
+        };
+
+        // the same seed generates the same sequence
+        let first = run_seeded(42);
+        assert_eq!(first, run_seeded(42));
+        assert_ne!(first, run_seeded(43));
+
+        match first {
+            Data::Tuple(items) => match items.as_slice() {
+                [Data::Real(a), Data::Real(b), Data::Integer(c), Data::Integer(d)] => {
+                    assert!((0.0..1.0).contains(a) && (0.0..1.0).contains(b));
+                    assert_ne!(a, b);
+                    assert!((1..7).contains(c) && (1..7).contains(d));
+                },
+                other => panic!("Unexpected random numbers {:?}", other),
+            },
+            other => panic!("Expected a tuple, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reraise_error() {
+        // the error points to where it was first raised, then to where it was raised again
+        let trace = VM::init(compile("caught = try (1 / 0)\nerror caught")).run().unwrap_err();
+        assert_eq!(trace.message(), "Division by zero");
+        assert_eq!(trace.spans()[0].contents(), "(1 / 0)");
+        assert_eq!(trace.spans()[1].line_col(), Some((2, 1)));
+    }
+
+    #[test]
+    fn try_nested() {
+        // the error is raised two calls deep
+        let mut vm = inspect("
+            inner = x -> x / 0
+            outer = y -> { z = inner y; z }
+            Error message = try (outer 1)
+            Ok n = try { message; 2 }
+            n
+        ");
+        assert_eq!(vm.stack.pop_data(), Data::Integer(2));
+    }
+
+    #[test]
+    fn dup() {
+        let mut lambda = Lambda::empty();
+        let index = lambda.index_data(Data::String("Hello".to_string()));
+        lambda.emit(Opcode::Con);
+        lambda.emit_bytes(&mut vec![index as u8]);
+        lambda.emit(Opcode::Dup);
+
+        let mut vm = VM::init(Closure::wrap(Rc::new(lambda)));
+        vm.run().unwrap();
+        assert_eq!(vm.stack.pop_data(), Data::String("Hello".to_string()));
+        assert_eq!(vm.stack.pop_data(), Data::String("Hello".to_string()));
+    }
+
+    #[test]
+    fn con_long() {
+        let mut lambda = Lambda::empty();
+        for n in 0..300 { lambda.index_data(Data::Integer(n)); }
+
+        // short and long forms can both load constants that fit in a byte
+        lambda.emit(Opcode::Con);
+        lambda.emit_bytes(&mut vec![200]);
+        lambda.emit(Opcode::ConLong);
+        lambda.emit_bytes(&mut split_number(200));
+        lambda.emit(Opcode::ConLong);
+        lambda.emit_bytes(&mut split_number(299));
+
+        let mut vm = VM::init(Closure::wrap(Rc::new(lambda)));
+        vm.run().unwrap();
+        assert_eq!(vm.snapshot(), vec![Data::Integer(200), Data::Integer(200), Data::Integer(299)]);
+    }
+
+    #[test]
+    fn nop() {
+        // the same chunk, with and without padding
+        let chunk = |padded: bool| {
+            let mut lambda = Lambda::empty();
+            for n in 1..=2 {
+                if padded { lambda.emit(Opcode::Nop); }
+                let index = lambda.index_data(Data::Integer(n));
+                lambda.emit(Opcode::Con);
+                lambda.emit_bytes(&mut vec![index as u8]);
+                if padded { lambda.align(4); }
+            }
+            lambda.emit(Opcode::Swap);
+            lambda
+        };
+
+        let padded = chunk(true);
+        assert_eq!(padded.code.len(), 9);
+        assert_eq!(padded.code[3], Opcode::Nop.to_byte());
+
+        // there is nothing to align to
+        let mut unaligned = chunk(false);
+        unaligned.align(0);
+        assert_eq!(unaligned.code, chunk(false).code);
+
+        let mut vm = VM::init(Closure::wrap(Rc::new(chunk(false))));
+        vm.run().unwrap();
+        let mut padded_vm = VM::init(Closure::wrap(Rc::new(padded)));
+        padded_vm.run().unwrap();
+        assert_eq!(padded_vm.snapshot(), vm.snapshot());
+        assert_eq!(padded_vm.snapshot(), vec![Data::Integer(2), Data::Integer(1)]);
+    }
+
+    #[test]
+    fn equality() {
+        let source = "(1 == true, 1 != true, (1, 2) == (1, 2), \"a\" != \"b\")";
+        let mut vm = inspect(source);
+        assert_eq!(vm.stack.pop_data(), Data::Tuple(vec![
+            Data::Boolean(false),
+            Data::Boolean(true),
+            Data::Boolean(true),
+            Data::Boolean(true),
+        ]));
+
+        // in strict mode, comparing values of different types is an error
+        let mut vm = VM::init(compile(source));
+        vm.strict_equality = true;
+        let trace = vm.run().unwrap_err();
+        assert_eq!(trace.kind(), "Type");
+        assert_eq!(trace.message(), "Can not compare '1' and 'true', as they are of different types");
+
+        // but values of the same type can still be compared
+        let mut vm = VM::init(compile("(1, 2) == (1, 3)"));
+        vm.strict_equality = true;
+        vm.run().unwrap();
+        assert_eq!(vm.stack.pop_data(), Data::Boolean(false));
+
+        // types are compared inside of tuples, lists, and labels too
+        let mut vm = VM::init(compile("(1, 2.5) == (1, 2)"));
+        vm.strict_equality = true;
+        let trace = vm.run().unwrap_err();
+        assert_eq!(trace.message(), "Can not compare '2.5' and '2', as they are of different types");
+
+        let mut vm = VM::init(compile("[Some 1] == [Some true]"));
+        vm.strict_equality = true;
+        let trace = vm.run().unwrap_err();
+        assert_eq!(trace.message(), "Can not compare '1' and 'true', as they are of different types");
+    }
+
+    #[test]
+    fn profile() {
+        let source = "total = 0\nfor x in [1, 2, 3] { total = total + x }\ntotal";
+
+        let mut vm = VM::init(compile(source));
+        vm.run().unwrap();
+        assert_eq!(vm.profile(), None);
+
+        let mut vm = VM::with_profiling(compile(source));
+        vm.run().unwrap();
+        let profile = vm.profile().unwrap();
+        // `total` and `x` are loaded in each of the three iterations, then `total` at the end
+        assert_eq!(profile[&Opcode::Load], 7);
+        // one jump back per iteration, and one more `Next` to find the list is exhausted
+        assert_eq!(profile[&Opcode::Jump], 3);
+        assert_eq!(profile[&Opcode::Next], 4);
+        assert_eq!(profile.get(&Opcode::Call), None);
+    }
+
+    #[test]
+    fn stats() {
+        let source = "x = 1\nx + 2";
+
+        let mut vm = VM::init(compile(source));
+        vm.run().unwrap();
+        assert_eq!(vm.stats(), None);
+
+        let mut vm = VM::with_profiling(compile(source));
+        vm.run().unwrap();
+        let stats = vm.stats().unwrap();
+        // Con, Declare, then Load, Con, Tuple, FFICall
+        assert_eq!(stats.instructions, 6);
+    }
+
+    #[test]
+    fn recursion_limit() {
+        let mut vm = VM::with_recursion_limit(compile("loop = n -> 1 + loop n\nloop 0"), 100);
+        let trace = vm.run().unwrap_err();
+        assert_eq!(trace.kind(), "Recursion");
+        assert_eq!(trace.message(), "Maximum recursion depth of 100 exceeded");
+        assert_eq!(trace.spans()[0].contents(), "loop n");
+    }
+
+    #[test]
+    fn swap() {
+        let mut lambda = Lambda::empty();
+        for n in 1..=2 {
+            let index = lambda.index_data(Data::Integer(n));
+            lambda.emit(Opcode::Con);
+            lambda.emit_bytes(&mut vec![index as u8]);
+        }
+        lambda.emit(Opcode::Swap);
+
+        let mut vm = VM::init(Closure::wrap(Rc::new(lambda)));
+        vm.run().unwrap();
+        assert_eq!(vm.stack.pop_data(), Data::Integer(1));
+        assert_eq!(vm.stack.pop_data(), Data::Integer(2));
+    }
+
+    #[test]
+    fn step_once() {
+        let mut lambda = Lambda::empty();
+        for n in 1..=2 {
+            let index = lambda.index_data(Data::Integer(n));
+            lambda.emit(Opcode::Con);
+            lambda.emit_bytes(&mut vec![index as u8]);
+        }
+        lambda.emit(Opcode::Swap);
+        lambda.emit(Opcode::Del);
+
+        let mut vm = VM::init(Closure::wrap(Rc::new(lambda)));
+        let expected = [
+            (Opcode::Con,  1, 2),
+            (Opcode::Con,  2, 4),
+            (Opcode::Swap, 2, 5),
+            (Opcode::Del,  1, 6),
+        ];
+
+        for (opcode, stack_depth, ip) in expected.iter() {
+            assert_eq!(
+                vm.step_once(),
+                Ok(StepOutcome { opcode: *opcode, stack_depth: *stack_depth, ip: *ip }),
+            );
+        }
+
+        assert!(vm.is_terminated());
+        assert_eq!(vm.snapshot(), vec![Data::Integer(2)]);
+        assert_eq!(vm.step_once().unwrap_err().kind(), "Terminated");
+    }
+
+    #[test]
+    fn breakpoint() {
+        let mut vm = VM::init(compile("x = 1\ny = x + 1\nz = y + 1"));
+        vm.set_breakpoint(2);
+
+        // pauses before anything on the second line is run
+        assert_eq!(vm.run_to_breakpoint(), Ok(RunState::Paused(2)));
+        assert_eq!(vm.current_span().line_col().unwrap().0, 2);
+        assert_eq!(vm.snapshot()[..2], [Data::Integer(1), Data::NotInit]);
+
+        // resuming runs to the end
+        assert_eq!(vm.run_to_breakpoint(), Ok(RunState::Finished));
+        assert_eq!(vm.snapshot()[..3], [Data::Integer(1), Data::Integer(2), Data::Integer(3)]);
+    }
+
+    #[test]
+    fn swap_underflow() {
+        let mut lambda = Lambda::empty();
+        let index = lambda.index_data(Data::Integer(1));
+        lambda.emit(Opcode::Con);
+        lambda.emit_bytes(&mut vec![index as u8]);
+        lambda.emit(Opcode::Swap);
+
+        let trace = VM::init(Closure::wrap(Rc::new(lambda))).run().unwrap_err();
+        assert_eq!(trace.kind(), "Stack");
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        let mut lambda = Lambda::empty();
+        lambda.emit(Opcode::NotInit);
+        lambda.emit_bytes(&mut vec![u8::MAX]);
+
+        let trace = VM::init(Closure::wrap(Rc::new(lambda))).run().unwrap_err();
+        assert_eq!(trace.kind(), "Unknown Opcode");
+    }
+
+    #[test]
+    fn collect_cycle() {
+        // `forever` captures itself, creating a cycle,
+        // which is unreachable once `make` returns.
+        let closure = compile("make = () -> { forever = x -> forever x; () }; make ()");
+        let collector = Box::new(MarkSweep::new(usize::MAX));
+        let mut vm = VM::with_collector(closure, collector);
+        vm.run().unwrap();
+        assert_eq!(vm.collect(), 1);
+        assert_eq!(vm.collect(), 0);
+    }
+
+    #[test]
+    fn collect_reachable() {
+        // collect after every capture, while closures are still in use
+        let closure = compile("
+            counter = 0
+            bump = () -> { counter = counter + 1 }
+            twice = f -> { f (); f () }
+            twice bump
+            counter
+        ");
+        let mut vm = VM::with_collector(closure, Box::new(MarkSweep::new(1)));
+        vm.run().unwrap();
+        assert_eq!(vm.stack.pop_data(), Data::Integer(2));
+    }
+
+    #[test]
+    fn bytes() {
+        let mut vm = inspect("b\"abc\"");
+        assert_eq!(vm.stack.pop_data(), Data::Bytes(vec![97, 98, 99]));
+
+        let mut vm = inspect("bytes = b\"abc\"; bytes[1]");
+        assert_eq!(vm.stack.pop_data(), Data::Integer(98));
+        assert_eq!(format!("{}", Data::Bytes(vec![104, 105, 10])), "b\"hi\\n\"");
+    }
+
+    #[test]
+    fn index_list() {
+        let mut vm = inspect("xs = [1, 2, 3]; xs[1 + 1]");
+        assert_eq!(vm.stack.pop_data(), Data::Integer(3));
+    }
+
+    #[test]
+    fn snapshot() {
+        let vm = inspect("x = 1; y = [x, 2]; z = x + 1");
+        assert_eq!(
+            vm.snapshot(),
+            vec![
+                Data::Integer(1),
+                Data::List(vec![Data::Integer(1), Data::Integer(2)]),
+                Data::Integer(2),
+                Data::Integer(2),
+            ],
+        );
+        assert_eq!(vm.stack_depth(), 4);
+        assert_eq!(vm.ip(), vm.closure.lambda.code.len());
+    }
+
+    #[test]
+    fn fast_constants() {
+        let vm = inspect("a = true; b = false; c = ()");
+        assert_eq!(
+            vm.snapshot(),
+            vec![Data::Boolean(true), Data::Boolean(false), Data::Unit, Data::Unit],
+        );
+    }
+
+    #[test]
+    fn block_locals() {
+        let mut vm = inspect("x = 1; z = { x = x + 1; y = 2; x + y }; (x, z)");
+        assert_eq!(
+            vm.stack.pop_data(),
+            Data::Tuple(vec![Data::Integer(2), Data::Integer(4)]),
+        );
+
+        // `y` goes out of scope at the end of the block
+        let error = lex(Source::source("x = { y = 2; y + 1 }; x + y"))
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(hoist)
+            .unwrap_err();
+        assert_eq!(error.message, "'y' were referenced before assignment");
+
+        // its slot is reset, so the value it held is dropped
+        let vm = inspect("x = { y = [1, 2]; y }");
+        assert_eq!(
+            vm.snapshot()[..2],
+            [Data::List(vec![Data::Integer(1), Data::Integer(2)]), Data::NotInit],
+        );
+    }
+
+    #[test]
+    fn redeclare_captured() {
+        // each pass through the loop declares `x` again,
+        // but updating `total` is seen by the closure that captured it
+        let mut vm = inspect("\
+            fs = []\n\
+            total = 0\n\
+            get = () -> total\n\
+            for x in [1, 2, 3] { fs = magic \"push\" (fs, () -> x); total = total + x }\n\
+            (fs[0] (), fs[2] (), get ())\
+        ");
+        assert_eq!(
+            vm.stack.pop_data(),
+            Data::Tuple(vec![Data::Integer(1), Data::Integer(3), Data::Integer(6)]),
+        );
+    }
+
+    #[test]
+    fn loop_block_locals() {
+        // each pass through the loop gets its own `y`, even once captured
+        let mut vm = inspect("\
+            fs = []\n\
+            for x in [1, 2, 3] { y = x * 2; fs = magic \"push\" (fs, () -> y) }\n\
+            (fs[0] (), fs[2] ())\
+        ");
+        assert_eq!(
+            vm.stack.pop_data(),
+            Data::Tuple(vec![Data::Integer(2), Data::Integer(6)]),
+        );
+    }
+
+    #[test]
+    fn return_drops_locals() {
+        // only `f` and `z` are left, the locals of `f` were dropped on return
+        let vm = inspect("f = x -> { a = x; b = a + 1; b }; z = f 1");
+        assert_eq!(
+            vm.snapshot()[1..],
+            [Data::Integer(2), Data::Integer(2)],
+        );
+        assert_eq!(vm.stack_depth(), 3);
+    }
+
+    #[test]
+    fn block_result_survives_locals() {
+        let mut vm = inspect("\
+            f = x -> { a = x * 2; g = () -> a; { b = g (); b + 1 } }\n\
+            h = x -> { c = x + 1; f c }\n\
+            (f 5, h 4)\
+        ");
+        assert_eq!(
+            vm.stack.pop_data(),
+            Data::Tuple(vec![Data::Integer(11), Data::Integer(11)]),
+        );
+    }
+
+    #[test]
+    fn do_block() {
+        let mut vm = inspect("x = 1; y = do { x = x + 1; x = x * 3; x }; (x, y)");
+        assert_eq!(
+            vm.stack.pop_data(),
+            Data::Tuple(vec![Data::Integer(6), Data::Unit]),
+        );
+    }
+
+    #[test]
+    fn partial_application() {
+        let mut vm = inspect("add = a b c -> a + b + c; inc = add 1; inc_two = inc 1; inc_two 40");
+        assert_eq!(vm.stack.pop_data(), Data::Integer(42));
+    }
+
+    #[test]
+    fn over_application() {
+        let trace = VM::init(compile("add = a b -> a + b; add 1 2 3")).run().unwrap_err();
+        assert_eq!(trace.kind(), "Call");
+        assert_eq!(trace.message(), "The data '3' is not a function and can not be called");
+
+        // natives take no more arguments than their arity either
+        let trace = VM::init(compile("add = magic \"native\" \"add\"; add 1 2 3")).run().unwrap_err();
+        assert_eq!(trace.kind(), "Call");
+        assert_eq!(trace.message(), "The data '3' is not a function and can not be called");
+    }
+
+    #[test]
+    fn native_partial_application() {
+        let mut vm = inspect("add = magic \"native\" \"add\"; inc = add 1; (inc, inc 41, add 1 2)");
+        assert_eq!(
+            format!("{}", vm.stack.pop_data()),
+            "(<fn/1>, 42, 3)",
+        );
+
+        // natives of one argument are called straight away
+        let mut vm = inspect("neg = magic \"native\" \"neg\"; neg 7");
+        assert_eq!(vm.stack.pop_data(), Data::Integer(-7));
+
+        // the same native applied to the same arguments is the same function
+        let mut vm = inspect("add = magic \"native\" \"add\"; (add, add 1 == add 1, add 1 == add 2)");
+        assert_eq!(format!("{}", vm.stack.pop_data()), "(<fn/2>, true, false)");
+
+        // errors raised once every argument has been applied point at the last call
+        let trace = VM::init(compile("add = magic \"native\" \"add\"; add 1 true")).run().unwrap_err();
+        assert_eq!(trace.kind(), "FFI Call");
+        assert_eq!(trace.message(), "Addition between unsupported datatypes");
+    }
+
+    #[test]
+    fn function_display() {
+        let mut vm = inspect("f = x -> x; f");
+        assert_eq!(format!("{}", vm.stack.pop_data()), "<fn/1>");
+
+        let mut vm = inspect("f = a b c -> a; (f, f 1)");
+        assert_eq!(format!("{}", vm.stack.pop_data()), "(<fn/3>, <fn/2>)");
+    }
+
+    #[test]
+    fn function_identity() {
+        // structurally identical functions are still distinct
+        let mut vm = inspect("f = x -> x; g = x -> x; h = f; (f == g, f == h, f == f)");
+        assert_eq!(
+            vm.stack.pop_data(),
+            Data::Tuple(vec![
+                Data::Boolean(false),
+                Data::Boolean(true),
+                Data::Boolean(true),
+            ]),
+        );
+    }
+
+    #[test]
+    fn closure_identity() {
+        // closures over different variables are distinct
+        let mut vm = inspect("adder = n -> x -> x + n; adder 1 == adder 1");
+        assert_eq!(vm.stack.pop_data(), Data::Boolean(false));
+    }
+
+    #[test]
+    fn slice_list() {
+        let mut vm = inspect("xs = [1, 2, 3, 4]; xs[1..3]");
+        assert_eq!(
+            vm.stack.pop_data(),
+            Data::List(vec![Data::Integer(2), Data::Integer(3)]),
+        );
+    }
+
+    #[test]
+    fn slice_string() {
+        let mut vm = inspect("s = \"héllo\"; (s[1..4], s[2..2])");
+        assert_eq!(
+            vm.stack.pop_data(),
+            Data::Tuple(vec![
+                Data::String("éll".to_string()),
+                Data::String("".to_string()),
+            ]),
+        );
+    }
+
+    #[test]
+    fn index_string() {
+        let mut vm = inspect("a = \"abc\"; b = \"héllo 🐦\"; (a[0], a[2], b[1], b[6])");
+        assert_eq!(
+            vm.stack.pop_data(),
+            Data::Tuple(vec![
+                Data::Char('a'),
+                Data::Char('c'),
+                Data::Char('é'),
+                Data::Char('🐦'),
+            ]),
+        );
+    }
+
+    #[test]
+    fn list() {
+        let mut vm = inspect("x = 2; [1, x, [3]]");
+        assert_eq!(
+            vm.stack.pop_data(),
+            Data::List(vec![
+                Data::Integer(1),
+                Data::Integer(2),
+                Data::List(vec![Data::Integer(3)]),
+            ]),
+        );
+    }
+
+    #[test]
+    fn for_range() {
+        let mut vm = inspect("total = 0; for i in 0..5 { total = total + i }; total");
+        assert_eq!(vm.stack.pop_data(), Data::Integer(10));
+    }
+
+    #[test]
+    fn read_write_file() {
+        let path = std::env::temp_dir().join(format!("passerine_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().replace('\\', "/");
+
+        let mut vm = inspect(&format!("
+            path = \"{}\"
+            magic \"write_file\" (path, \"Hello, file!\")
+            magic \"read_file\" path
+        ", path));
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(vm.stack.pop_data(), Data::String("Hello, file!".to_string()));
+    }
+
+    #[test]
+    fn file_capability_denied() {
+        let mut vm = VM::init(compile("magic \"read_file\" \"secrets.txt\""));
+        vm.capabilities.remove(&Capability::FileSystem);
+
+        let trace = vm.run().unwrap_err();
+        assert_eq!(trace.kind(), "Capability");
+        assert_eq!(trace.message(), "This ffi function needs file system access, which has been denied");
+    }
+
+    #[test]
+    fn for_list() {
+        let mut vm = inspect("total = 0; for x in [1, 2, 3] { total = total + x }");
+        assert_eq!(vm.stack.pop_data(), Data::Unit);
+    }
+
     // TODO: figure out how to make the following passerine code into a test
     // without entering into an infinite loop (which is the intended behaviour)
     // maybe try running it a large number of times,
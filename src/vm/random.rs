@@ -0,0 +1,82 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A small, fast pseudo-random number generator, used by `magic "random"`.
+/// It is not cryptographically secure,
+/// but seeding it makes the numbers it generates reproducible,
+/// which is useful for simulations and tests.
+///
+/// > NOTE: implementation of SplitMix64, by Sebastiano Vigna.
+#[derive(Debug, Clone)]
+pub struct Prng {
+    state: u64,
+}
+
+impl Prng {
+    /// Creates a new generator from a seed.
+    /// The same seed always generates the same sequence of numbers.
+    pub fn new(seed: u64) -> Prng {
+        Prng { state: seed }
+    }
+
+    /// Creates a new generator seeded with the current time.
+    pub fn from_time() -> Prng {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Prng::new(nanos)
+    }
+
+    /// Generates the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Generates a real in the half-open range `[0, 1)`.
+    pub fn next_real(&mut self) -> f64 {
+        // the top 53 bits fill the mantissa of the real exactly
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Generates an integer in the half-open range `[low, high)`,
+    /// like a range `low..high`.
+    /// Returns `None` if the range is empty.
+    pub fn next_int(&mut self, low: i64, high: i64) -> Option<i64> {
+        if low >= high { return None; }
+        let width = high.wrapping_sub(low) as u64 as u128;
+        let offset = ((self.next_u64() as u128 * width) >> 64) as u64;
+        Some(low.wrapping_add(offset as i64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reproducible() {
+        let mut a = Prng::new(42);
+        let mut b = Prng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn bounds() {
+        let mut prng = Prng::new(7);
+        for _ in 0..1000 {
+            let real = prng.next_real();
+            assert!((0.0..1.0).contains(&real));
+            let int = prng.next_int(-3, 4).unwrap();
+            assert!((-3..4).contains(&int));
+        }
+
+        assert_eq!(prng.next_int(1, 1), None);
+        assert!(prng.next_int(i64::MIN, i64::MAX).is_some());
+    }
+}
@@ -106,6 +106,17 @@ impl Stack {
         self.stack.push(Tagged::frame());
     }
 
+    /// Swaps the top two values on the stack.
+    /// Returns `false`, leaving the stack untouched,
+    /// if there are fewer than two values above the current frame.
+    #[inline]
+    pub fn swap_top(&mut self) -> bool {
+        let len = self.stack.len();
+        if len < self.frame_index() + 3 { return false; }
+        self.stack.swap(len - 1, len - 2);
+        true
+    }
+
     /// Shorcut for pushing a `Tagged(Slot::NotInit)` on top of the stack.
     #[inline]
     pub fn push_not_init(&mut self) {
@@ -119,14 +130,37 @@ impl Stack {
     }
 
     /// Wraps the top data value on the stack in `Data::Heaped`,
-    /// data must not already be on the heap
+    /// data must not already be on the heap.
+    /// Returns a reference to the newly heaped cell.
     #[inline]
-    pub fn heapify(&mut self, index: usize) {
+    pub fn heapify(&mut self, index: usize) -> Rc<RefCell<Data>> {
         let local_index = self.frame_index() + index + 1;
 
         let data = self.swap(local_index, Tagged::not_init()).slot().data();
-        let heaped = Slot::Data(Data::Heaped(Rc::new(RefCell::new(data))));
+        let cell = Rc::new(RefCell::new(data));
+        let heaped = Slot::Data(Data::Heaped(cell.clone()));
         mem::drop(mem::replace(&mut self.stack[local_index], Tagged::new(heaped)));
+        cell
+    }
+
+    /// Returns whether a local variable has been moved to the heap
+    /// and holds a value, i.e. whether it was captured after being assigned to.
+    #[inline]
+    pub fn local_captured(&self, index: usize) -> bool {
+        let local_index = self.frame_index() + index + 1;
+        self.stack[local_index].peek(|slot| match slot {
+            Slot::Data(Data::Heaped(cell)) => !matches!(*cell.borrow(), Data::NotInit),
+            _ => false,
+        })
+    }
+
+    /// Resets a local variable on the stack to `NotInit`, dropping its value.
+    /// If the local was moved to the heap,
+    /// any closures that captured it keep their reference to it.
+    #[inline]
+    pub fn del_local(&mut self, index: usize) {
+        let local_index = self.frame_index() + index + 1;
+        mem::drop(self.swap(local_index, Tagged::not_init()));
     }
 
     /// Truncates the stack to the last frame.
@@ -46,6 +46,7 @@ const U_FLAG: u64 = 0x0000_0000_0000_0001; // unit
 const F_FLAG: u64 = 0x0000_0000_0000_0002; // false
 const T_FLAG: u64 = 0x0000_0000_0000_0003; // true
 const N_FLAG: u64 = 0x0000_0000_0000_0004; // not initialized
+const L_FLAG: u64 = 0x0000_0000_0000_0005; // nil
 
 impl Tagged {
     /// Wraps `Data` to create a new tagged pointer.
@@ -55,6 +56,8 @@ impl Tagged {
             Slot::Data(Data::Real(f)) => Tagged(f.to_bits()),
             // Unit
             Slot::Data(Data::Unit) => Tagged(QNAN | U_FLAG),
+            // Nil
+            Slot::Data(Data::Nil) => Tagged(QNAN | L_FLAG),
             // True and false
             Slot::Data(Data::Boolean(false)) => Tagged(QNAN | F_FLAG),
             Slot::Data(Data::Boolean(true))  => Tagged(QNAN | T_FLAG),
@@ -98,6 +101,7 @@ impl Tagged {
         match self.0 {
             n if (n & QNAN) != QNAN     => Slot::Data(Data::Real(f64::from_bits(n))),
             u if u == (QNAN | U_FLAG)   => Slot::Data(Data::Unit),
+            l if l == (QNAN | L_FLAG)   => Slot::Data(Data::Nil),
             f if f == (QNAN | F_FLAG)   => Slot::Data(Data::Boolean(false)),
             t if t == (QNAN | T_FLAG)   => Slot::Data(Data::Boolean(true)),
             s if s == (QNAN | S_FLAG)   => Slot::Frame,
@@ -117,6 +121,19 @@ impl Tagged {
         })
     }
 
+    /// Calls a function with a reference to the underlying `Slot`,
+    /// without copying or consuming it.
+    pub fn peek<T>(&self, f: impl FnOnce(&Slot) -> T) -> T {
+        match self.0 {
+            n if (n & QNAN) != QNAN     => f(&self.copy()),
+            p if (p & P_FLAG) == P_FLAG => f(unsafe {
+                // Safety: We have a shared borrow of `self`
+                &*((p & P_MASK) as *const Slot)
+            }),
+            _ => f(&self.copy()),
+        }
+    }
+
     /// Deeply copies some `Tagged` data.
     pub fn copy(&self) -> Slot {
         self.extract(|p| unsafe {
@@ -230,6 +247,7 @@ mod test {
             Data::Boolean(true),
             Data::Boolean(false),
             Data::Unit,
+            Data::Nil,
             Data::String("Hello, World!".to_string()),
             Data::String("".to_string()),
             Data::String("Whoop 😋".to_string()),
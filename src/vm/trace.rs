@@ -23,6 +23,23 @@ impl Trace {
     pub fn add_context(&mut self, span: Span) {
         self.spans.push(span);
     }
+
+    /// Returns the kind of error, e.g. `Type`.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// Returns the message describing the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the call stack the error propagated through.
+    /// The first span is where the error was raised,
+    /// each following span is the call in the frame below it.
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
 }
 
 impl fmt::Display for Trace {
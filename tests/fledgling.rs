@@ -1,4 +1,4 @@
-///! Snippet tests for the passerine compiler pipeline as a whole.
+//! Snippet tests for the passerine compiler pipeline as a whole.
 
 use std::{
     fs,
@@ -31,9 +31,9 @@ pub enum Outcome {
 impl Outcome {
     pub fn parse(outcome: &str) -> Outcome {
         match outcome {
-            s if s == "success" => Outcome::Success,
-            s if s == "syntax"  => Outcome::Syntax,
-            t if t == "trace"   => Outcome::Trace,
+            "success" => Outcome::Success,
+            "syntax"  => Outcome::Syntax,
+            "trace"   => Outcome::Trace,
             invalid => {
                 println!("invalid: '{}'", invalid);
                 panic!("invalid outcome in strat heading");
@@ -56,12 +56,12 @@ pub enum Action {
 impl Action {
     pub fn parse(action: &str) -> Action {
         match action {
-            l if l == "lex"     => Action::Lex,
-            p if p == "parse"   => Action::Parse,
-            d if d == "desugar" => Action::Desugar,
-            d if d == "hoist"   => Action::Hoist,
-            g if g == "gen"     => Action::Gen,
-            r if r == "run"     => Action::Run,
+            "lex"     => Action::Lex,
+            "parse"   => Action::Parse,
+            "desugar" => Action::Desugar,
+            "hoist"   => Action::Hoist,
+            "gen"     => Action::Gen,
+            "run"     => Action::Run,
             invalid => {
                 println!("invalid: '{}'", invalid);
                 panic!("invalid action in strat heading");
@@ -91,10 +91,10 @@ impl TestStrat {
         let mut expect = None;
 
         for (strat, result) in heading.iter() {
-            match strat {
-                o if o == "outcome" => outcome = Some(Outcome::parse(result)),
-                a if a == "action"  => action  = Some(Action::parse(result)),
-                e if e == "expect"  => expect  = {
+            match strat.as_str() {
+                "outcome" => outcome = Some(Outcome::parse(result)),
+                "action"  => action  = Some(Action::parse(result)),
+                "expect"  => expect  = {
                     let tokens = lex(Source::source(result)).expect("Could not lex expectation");
                     let ast    = parse(tokens).expect("Could not parse expectation");
 
@@ -137,7 +137,7 @@ impl TestStrat {
             }
         }
 
-        return TestStrat::heading(heading);
+        TestStrat::heading(heading)
     }
 }
 
@@ -165,7 +165,7 @@ fn test_snippet(source: Rc<Source>, strat: TestStrat) {
             .and_then(parse)
             .and_then(desugar)
             .and_then(hoist)
-            .and_then(gen)
+            .and_then(|sst| Ok(gen(sst)?))
             .is_ok() { Outcome::Success } else { Outcome::Syntax },
 
         Action::Run => {
@@ -173,7 +173,7 @@ fn test_snippet(source: Rc<Source>, strat: TestStrat) {
                 .and_then(parse)
                 .and_then(desugar)
                 .and_then(hoist)
-                .and_then(gen)
+                .and_then(|sst| Ok(gen(sst)?))
             {
                 Ok(lambda) => {
                     let mut vm = VM::init(Closure::wrap(lambda));